@@ -1,12 +1,12 @@
-use std::path::PathBuf;
 use std::{env::current_dir, process};
+use std::{net::Ipv4Addr, path::PathBuf};
 
 use clap::Parser;
 use dirs::home_dir;
 use eyre::Result;
 
 use magi::{
-    config::{ChainConfig, CliConfig, Config, SyncMode},
+    config::{ChainConfig, CliConfig, CliP2PConfig, Config, Muxer, SyncMode},
     runner::Runner,
     telemetry::{self, metrics},
 };
@@ -51,6 +51,9 @@ pub struct Cli {
     l1_rpc_url: Option<String>,
     #[clap(long)]
     l1_beacon_url: Option<String>,
+    /// Fallback blob sources (e.g. a blob archiver), tried in order when the beacon node has pruned a blob
+    #[clap(long, value_delimiter = ',')]
+    l1_beacon_fallback_urls: Option<Vec<String>>,
     #[clap(long)]
     l2_rpc_url: Option<String>,
     #[clap(short = 'm', long, default_value = "full")]
@@ -78,6 +81,60 @@ pub struct Cli {
     checkpoint_sync_url: Option<String>,
     #[clap(long)]
     devnet: bool,
+    /// Directory used to persist node data (defaults to ~/.magi/data)
+    #[clap(long)]
+    data_dir: Option<PathBuf>,
+    /// Number of L1 confirmations to wait for before ingesting a block
+    #[clap(long)]
+    l1_confs: Option<u64>,
+    /// IP address the p2p services listen on (defaults to 0.0.0.0)
+    #[clap(long)]
+    p2p_listen_ip: Option<Ipv4Addr>,
+    /// TCP port libp2p listens on (defaults to 9876)
+    #[clap(long)]
+    p2p_listen_tcp_port: Option<u16>,
+    /// UDP port peer discovery listens on (defaults to 9876)
+    #[clap(long)]
+    p2p_listen_udp_port: Option<u16>,
+    /// IP address advertised to peers (found with UPnP or voted on by peers if unset)
+    #[clap(long)]
+    p2p_advertise_ip: Option<Ipv4Addr>,
+    /// TCP port advertised to peers (defaults to the listen port)
+    #[clap(long)]
+    p2p_advertise_tcp_port: Option<u16>,
+    /// UDP port advertised to peers (defaults to the listen port)
+    #[clap(long)]
+    p2p_advertise_udp_port: Option<u16>,
+    /// Multiaddrs of peers to always stay connected to, including their /p2p/ peer ID
+    #[clap(long, value_delimiter = ',')]
+    p2p_static_peers: Option<Vec<String>>,
+    /// ENRs of the discovery bootnodes, replacing the built-in bootnodes
+    #[clap(long, value_delimiter = ',')]
+    p2p_bootnodes: Option<Vec<String>>,
+    /// The number of peers to dial up to (defaults to 20)
+    #[clap(long)]
+    p2p_target_peers: Option<u32>,
+    /// The maximum number of connected peers (defaults to 30)
+    #[clap(long)]
+    p2p_max_peers: Option<u32>,
+    /// Stream multiplexers to negotiate, in order of preference (defaults to yamux,mplex)
+    #[clap(long, value_delimiter = ',')]
+    p2p_muxers: Option<Vec<Muxer>>,
+    /// UDP port to accept QUIC connections on (QUIC is disabled if unset)
+    #[clap(long)]
+    p2p_quic_port: Option<u16>,
+    /// Forward the advertised ports on the local internet gateway with UPnP
+    #[clap(long)]
+    p2p_upnp: bool,
+    /// File to record valid gossiped unsafe blocks to (SSZ if it ends in .ssz, JSON lines otherwise)
+    #[clap(long)]
+    p2p_record_blocks: Option<PathBuf>,
+    /// Recording to replay unsafe blocks from, instead of joining the gossip network
+    #[clap(long, conflicts_with = "p2p-record-blocks")]
+    p2p_replay_blocks: Option<PathBuf>,
+    /// How many times faster than recorded to replay blocks, or 0 for no delay (defaults to 1)
+    #[clap(long)]
+    p2p_replay_speed: Option<u32>,
 }
 
 impl Cli {
@@ -118,6 +175,7 @@ impl From<Cli> for CliConfig {
         Self {
             l1_rpc_url: value.l1_rpc_url,
             l1_beacon_url: value.l1_beacon_url,
+            l1_beacon_fallback_urls: value.l1_beacon_fallback_urls,
             l2_rpc_url: value.l2_rpc_url,
             l2_engine_url: value.l2_engine_url,
             jwt_secret,
@@ -125,6 +183,26 @@ impl From<Cli> for CliConfig {
             rpc_port: value.rpc_port,
             rpc_addr: value.rpc_addr,
             devnet: value.devnet,
+            data_dir: value.data_dir,
+            l1_confs: value.l1_confs,
+            p2p: CliP2PConfig {
+                listen_ip: value.p2p_listen_ip,
+                listen_tcp_port: value.p2p_listen_tcp_port,
+                listen_udp_port: value.p2p_listen_udp_port,
+                advertise_ip: value.p2p_advertise_ip,
+                advertise_tcp_port: value.p2p_advertise_tcp_port,
+                advertise_udp_port: value.p2p_advertise_udp_port,
+                static_peers: value.p2p_static_peers,
+                bootnodes: value.p2p_bootnodes,
+                target_peers: value.p2p_target_peers,
+                max_peers: value.p2p_max_peers,
+                muxers: value.p2p_muxers,
+                quic_port: value.p2p_quic_port,
+                upnp: value.p2p_upnp.then_some(true),
+                record_blocks: value.p2p_record_blocks,
+                replay_blocks: value.p2p_replay_blocks,
+                replay_speed: value.p2p_replay_speed,
+            },
         }
     }
 }
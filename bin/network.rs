@@ -1,32 +1,213 @@
-use std::str::FromStr;
+use std::{
+    fs::File,
+    io::{self, Write},
+    net::{Ipv4Addr, SocketAddr},
+    path::PathBuf,
+    str::FromStr,
+    time::Duration,
+};
 
+use clap::{Parser, Subcommand};
+use discv5::enr::{CombinedKey, Enr};
 use ethers::types::Address;
 use eyre::Result;
+use libp2p::Multiaddr;
+use serde_json::json;
+use tokio::sync::watch;
 
 use magi::{
-    network::{handlers::block_handler::BlockHandler, service::Service},
+    config::ChainConfig,
+    network::{
+        handlers::block_handler::BlockHandler,
+        recorder::{BlockRecorder, RecordFormat},
+        service::{self, Service},
+    },
     telemetry,
 };
-use tokio::sync::watch;
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    let cli = Cli::parse();
     let _guards = telemetry::init(false, None, None);
+    let chain = ChainConfig::from_network_name(&cli.network);
+
+    match cli.command {
+        Command::Listen {
+            output,
+            format,
+            port,
+        } => listen(chain, output, format, port).await,
+        Command::Crawl {
+            duration,
+            output,
+            port,
+            filter,
+        } => crawl(chain, duration, output, port, filter).await,
+        Command::Ping { peer, timeout } => ping(chain, &peer, timeout).await,
+    }
+}
+
+/// Listens for gossiped unsafe blocks, recording the valid ones to a file
+async fn listen(
+    chain: ChainConfig,
+    output: PathBuf,
+    format: RecordFormat,
+    port: u16,
+) -> Result<()> {
+    let chain_id = chain.l2_chain_id;
+    let signer = Address::from_slice(chain.system_config.unsafe_block_signer.as_slice());
+    let (_signer_sender, signer_recv) = watch::channel(signer);
+    let (block_handler, mut block_recv) = BlockHandler::new(chain_id, signer_recv);
 
-    let addr = "0.0.0.0:9876".parse()?;
-    let chain_id = 420;
-    let (_, recv) = watch::channel(Address::from_str(
-        "0x715b7219d986641df9efd9c7ef01218d528e19ec",
-    )?);
-    let (block_handler, block_recv) = BlockHandler::new(chain_id, recv);
+    let mut recorder = BlockRecorder::create(&output, format)?;
+    tracing::info!("recording {} blocks to {:?}", format, output);
 
+    let addr = SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), port);
     Service::new(addr, chain_id)
+        .set_block_time(chain.blocktime)
         .add_handler(Box::new(block_handler))
         .start()?;
 
-    while let Ok(payload) = block_recv.recv() {
-        tracing::info!("received unsafe block with hash: {:?}", payload.block_hash);
+    while let Some(payload) = block_recv.recv().await {
+        tracing::info!(
+            "received unsafe block {} with hash {:?}",
+            payload.block_number,
+            payload.block_hash
+        );
+        recorder.record(&payload)?;
     }
 
     Ok(())
 }
+
+/// Walks the discovery network and dumps the nodes found as JSON lines
+async fn crawl(
+    chain: ChainConfig,
+    duration: u64,
+    output: Option<PathBuf>,
+    port: u16,
+    filter: bool,
+) -> Result<()> {
+    let addr = SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), port);
+    let nodes = service::crawl(addr, Vec::new(), Duration::from_secs(duration)).await?;
+
+    let mut writer: Box<dyn Write> = match &output {
+        Some(path) => Box::new(File::create(path)?),
+        None => Box::new(io::stdout()),
+    };
+
+    let mut on_chain = 0;
+    for enr in &nodes {
+        let chain_id = service::opstack_chain_id(enr);
+        if chain_id == Some(chain.l2_chain_id) {
+            on_chain += 1;
+        } else if filter {
+            continue;
+        }
+
+        let node = json!({
+            "enr": enr.to_base64(),
+            "nodeId": hex::encode(enr.node_id().raw()),
+            "multiaddr": service::enr_multiaddr(enr).map(|addr| addr.to_string()),
+            "chainId": chain_id,
+            "ip": enr.ip4(),
+            "tcp": enr.tcp4(),
+            "udp": enr.udp4(),
+        });
+        writeln!(writer, "{}", node)?;
+    }
+
+    tracing::info!(
+        "found {} nodes, {} on chain {}",
+        nodes.len(),
+        on_chain,
+        chain.l2_chain_id
+    );
+
+    Ok(())
+}
+
+/// Checks that a peer is reachable, given its multiaddr or ENR
+async fn ping(chain: ChainConfig, peer: &str, timeout: u64) -> Result<()> {
+    let addr = if peer.starts_with("enr:") {
+        let enr = Enr::<CombinedKey>::from_str(peer).map_err(|err| eyre::eyre!(err))?;
+        match service::opstack_chain_id(&enr) {
+            Some(chain_id) if chain_id == chain.l2_chain_id => {
+                tracing::info!("enr is on chain {}", chain_id)
+            }
+            Some(chain_id) => {
+                tracing::warn!("enr is on chain {}, not {}", chain_id, chain.l2_chain_id)
+            }
+            None => tracing::warn!("enr has no opstack field"),
+        }
+
+        service::enr_multiaddr(&enr).ok_or(eyre::eyre!("enr has no ip or tcp port"))?
+    } else {
+        Multiaddr::from_str(peer)?
+    };
+
+    tracing::info!("pinging {}", addr);
+    let res = service::ping_peer(addr, Duration::from_secs(timeout)).await?;
+
+    let identity = res.identity.as_ref();
+    let result = json!({
+        "peerId": res.peer_id.to_string(),
+        "rttMs": res.rtt.as_secs_f64() * 1000.0,
+        "agentVersion": identity.map(|identity| &identity.agent_version),
+        "protocolVersion": identity.map(|identity| &identity.protocol_version),
+        "protocols": identity.map(|identity| &identity.protocols),
+    });
+    println!("{}", serde_json::to_string_pretty(&result)?);
+
+    Ok(())
+}
+
+/// A p2p diagnostic tool to debug the OP Stack gossip network without running a full node
+#[derive(Parser)]
+pub struct Cli {
+    /// The network to use: optimism, optimism-sepolia, base, base-sepolia, or a chain config
+    /// JSON file
+    #[clap(short, long, default_value = "optimism", global = true)]
+    network: String,
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Listens for gossiped unsafe blocks, recording the valid ones to a file
+    Listen {
+        /// The file to record blocks to
+        #[clap(short, long, default_value = "blocks.jsonl")]
+        output: PathBuf,
+        /// The record format: jsonl or ssz
+        #[clap(long, default_value = "jsonl")]
+        format: RecordFormat,
+        /// The TCP and UDP port to listen on
+        #[clap(long, default_value = "9876")]
+        port: u16,
+    },
+    /// Walks the discovery network and dumps the ENRs found with their opstack chain IDs
+    Crawl {
+        /// How long to crawl for, in seconds
+        #[clap(long, default_value = "60")]
+        duration: u64,
+        /// The file to write the nodes to, as JSON lines. Defaults to stdout.
+        #[clap(short, long)]
+        output: Option<PathBuf>,
+        /// The UDP port to run discovery on
+        #[clap(long, default_value = "9876")]
+        port: u16,
+        /// Only dump nodes on the selected network
+        #[clap(long)]
+        filter: bool,
+    },
+    /// Checks that a peer is reachable, given its multiaddr or ENR
+    Ping {
+        /// The peer's multiaddr, or its `enr:` record
+        peer: String,
+        /// How long to wait for the peer, in seconds
+        #[clap(long, default_value = "10")]
+        timeout: u64,
+    },
+}
@@ -1,4 +1,4 @@
-use std::{fmt, iter, path::PathBuf, process::exit, str::FromStr};
+use std::{fmt, iter, net::Ipv4Addr, path::PathBuf, process::exit, str::FromStr};
 
 use alloy_primitives::{Address, B256, U256};
 use figment::{
@@ -20,6 +20,8 @@ pub enum SyncMode {
     Challenge,
     /// Full sync mode
     Full,
+    /// Execution layer sync mode
+    ExecutionLayer,
 }
 
 impl FromStr for SyncMode {
@@ -31,6 +33,7 @@ impl FromStr for SyncMode {
             "checkpoint" => Ok(Self::Checkpoint),
             "challenge" => Ok(Self::Challenge),
             "full" => Ok(Self::Full),
+            "execution-layer" => Ok(Self::ExecutionLayer),
             _ => Err("invalid sync mode".to_string()),
         }
     }
@@ -43,6 +46,7 @@ impl fmt::Display for SyncMode {
             Self::Checkpoint => write!(f, "checkpoint"),
             Self::Challenge => write!(f, "challenge"),
             Self::Full => write!(f, "full"),
+            Self::ExecutionLayer => write!(f, "execution-layer"),
         }
     }
 }
@@ -54,6 +58,10 @@ pub struct Config {
     pub l1_rpc_url: String,
     /// The base chain beacon client RPC URL
     pub l1_beacon_url: String,
+    /// Fallback beacon API compatible URLs (such as a blob archiver) used to fetch
+    /// blobs that are no longer available from the beacon client. Tried in order.
+    #[serde(default)]
+    pub l1_beacon_fallback_urls: Vec<String>,
     /// The L2 chain RPC URL
     pub l2_rpc_url: String,
     /// The L2 engine API URL
@@ -72,6 +80,111 @@ pub struct Config {
     /// The devnet mode.
     /// If devnet is enabled.
     pub devnet: bool,
+    /// The directory used to persist node data, such as fetched blobs
+    pub data_dir: Option<PathBuf>,
+    /// Number of confirmations an L1 block needs before the chain watcher ingests it.
+    /// Keeps shallow L1 reorgs from resetting the safe head.
+    #[serde(default)]
+    pub l1_confs: u64,
+    /// The p2p networking config
+    #[serde(default)]
+    pub p2p: P2PConfig,
+}
+
+/// The p2p networking configuration, set in the `[p2p]` TOML section or the `--p2p-*` flags
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct P2PConfig {
+    /// The IP address the p2p services listen on
+    pub listen_ip: Ipv4Addr,
+    /// The TCP port libp2p listens on
+    pub listen_tcp_port: u16,
+    /// The UDP port peer discovery listens on
+    pub listen_udp_port: u16,
+    /// The IP address advertised to peers. If unset, it is found with UPnP if enabled, and
+    /// voted on by the peers discovery talks to.
+    pub advertise_ip: Option<Ipv4Addr>,
+    /// The TCP port advertised to peers. Defaults to the listen port.
+    pub advertise_tcp_port: Option<u16>,
+    /// The UDP port advertised to peers. Defaults to the listen port.
+    pub advertise_udp_port: Option<u16>,
+    /// Multiaddrs of peers to always stay connected to, including their `/p2p/` peer ID
+    pub static_peers: Vec<String>,
+    /// ENRs of the discovery bootnodes. Defaults to the built-in bootnodes if empty.
+    pub bootnodes: Vec<String>,
+    /// The number of peers to dial up to
+    pub target_peers: u32,
+    /// The maximum number of connected peers
+    pub max_peers: u32,
+    /// The stream multiplexers to negotiate over TCP, in order of preference
+    pub muxers: Vec<Muxer>,
+    /// The UDP port QUIC listens on. QUIC is disabled if unset.
+    pub quic_port: Option<u16>,
+    /// Whether to forward the advertised ports to the listen ports on the local internet
+    /// gateway with UPnP, and advertise its external IP if `advertise_ip` is unset
+    pub upnp: bool,
+    /// A file to record valid unsafe blocks received via gossip to. Recorded as SSZ if the
+    /// file ends in `.ssz`, and as JSON lines otherwise.
+    pub record_blocks: Option<PathBuf>,
+    /// A recording to replay unsafe blocks from, instead of joining the gossip network.
+    /// Cannot be combined with `record_blocks`.
+    pub replay_blocks: Option<PathBuf>,
+    /// How many times faster than recorded to replay blocks. Replayed without delay if zero.
+    pub replay_speed: u32,
+}
+
+/// A libp2p stream multiplexer
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Muxer {
+    /// The yamux multiplexer
+    Yamux,
+    /// The deprecated mplex multiplexer
+    Mplex,
+}
+
+impl FromStr for Muxer {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "yamux" => Ok(Self::Yamux),
+            "mplex" => Ok(Self::Mplex),
+            _ => Err("invalid muxer".to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Muxer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Yamux => write!(f, "yamux"),
+            Self::Mplex => write!(f, "mplex"),
+        }
+    }
+}
+
+impl Default for P2PConfig {
+    fn default() -> Self {
+        Self {
+            listen_ip: Ipv4Addr::UNSPECIFIED,
+            listen_tcp_port: 9876,
+            listen_udp_port: 9876,
+            advertise_ip: None,
+            advertise_tcp_port: None,
+            advertise_udp_port: None,
+            static_peers: Vec::new(),
+            bootnodes: Vec::new(),
+            target_peers: 20,
+            max_peers: 30,
+            muxers: vec![Muxer::Yamux, Muxer::Mplex],
+            quic_port: None,
+            upnp: false,
+            record_blocks: None,
+            replay_blocks: None,
+            replay_speed: 1,
+        }
+    }
 }
 
 impl Config {
@@ -119,6 +232,9 @@ pub struct CliConfig {
     /// The L1 beacon chain RPC URL
     #[serde(skip_serializing_if = "Option::is_none")]
     pub l1_beacon_url: Option<String>,
+    /// Fallback blob sources, tried in order when the beacon chain RPC cannot serve a blob
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub l1_beacon_fallback_urls: Option<Vec<String>>,
     /// The L2 execution client RPC
     #[serde(skip_serializing_if = "Option::is_none")]
     pub l2_rpc_url: Option<String>,
@@ -140,6 +256,68 @@ pub struct CliConfig {
     /// If Magi is running in devnet mode.
     #[serde(default)]
     pub devnet: bool,
+    /// The directory used to persist node data.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data_dir: Option<PathBuf>,
+    /// Number of L1 confirmations to wait for before ingesting a block.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub l1_confs: Option<u64>,
+    /// The p2p networking flags
+    #[serde(default)]
+    pub p2p: CliP2PConfig,
+}
+
+/// [P2PConfig] items derived from the CLI
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CliP2PConfig {
+    /// The IP address the p2p services listen on
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub listen_ip: Option<Ipv4Addr>,
+    /// The TCP port libp2p listens on
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub listen_tcp_port: Option<u16>,
+    /// The UDP port peer discovery listens on
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub listen_udp_port: Option<u16>,
+    /// The IP address advertised to peers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub advertise_ip: Option<Ipv4Addr>,
+    /// The TCP port advertised to peers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub advertise_tcp_port: Option<u16>,
+    /// The UDP port advertised to peers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub advertise_udp_port: Option<u16>,
+    /// Multiaddrs of peers to always stay connected to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub static_peers: Option<Vec<String>>,
+    /// ENRs of the discovery bootnodes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bootnodes: Option<Vec<String>>,
+    /// The number of peers to dial up to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_peers: Option<u32>,
+    /// The maximum number of connected peers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_peers: Option<u32>,
+    /// The stream multiplexers to negotiate, in order of preference
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub muxers: Option<Vec<Muxer>>,
+    /// The UDP port QUIC listens on
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quic_port: Option<u16>,
+    /// Whether to forward ports on the local internet gateway with UPnP
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upnp: Option<bool>,
+    /// A file to record valid unsafe blocks received via gossip to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub record_blocks: Option<PathBuf>,
+    /// A recording to replay unsafe blocks from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replay_blocks: Option<PathBuf>,
+    /// How many times faster than recorded to replay blocks
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replay_speed: Option<u32>,
 }
 
 /// Configurations for a blockchain.
@@ -206,13 +384,25 @@ pub struct SystemConfig {
     pub l1_fee_scalar: U256,
     /// Sequencer's signer for unsafe blocks
     pub unsafe_block_signer: Address,
+    /// EIP-1559 base fee max change denominator, zero until set after Holocene
+    #[serde(default)]
+    pub eip1559_denominator: u32,
+    /// EIP-1559 elasticity multiplier, zero until set after Holocene
+    #[serde(default)]
+    pub eip1559_elasticity: u32,
+    /// Operator fee scalar, zero until set after Isthmus
+    #[serde(default)]
+    pub operator_fee_scalar: u32,
+    /// Operator fee constant, zero until set after Isthmus
+    #[serde(default)]
+    pub operator_fee_constant: u64,
 }
 
 impl SystemConfig {
     /// Encodes batch sender as a [B256].
     pub fn batcher_hash(&self) -> B256 {
         let mut batch_sender_bytes = self.batch_sender.as_slice().to_vec();
-        let mut batcher_hash = iter::repeat(0).take(12).collect::<Vec<_>>();
+        let mut batcher_hash = iter::repeat_n(0, 12).collect::<Vec<_>>();
         batcher_hash.append(&mut batch_sender_bytes);
         B256::from_slice(&batcher_hash)
     }
@@ -234,11 +424,16 @@ pub struct SystemAccounts {
 struct ChainProvider {
     /// The [ChainConfig] which is unique for each blockchain
     chain: ChainConfig,
+    /// The default L1 confirmation depth for the chain
+    l1_confs: u64,
 }
 
 impl From<ChainConfig> for Serialized<ChainProvider> {
     fn from(value: ChainConfig) -> Self {
-        Serialized::defaults(ChainProvider { chain: value })
+        Serialized::defaults(ChainProvider {
+            l1_confs: value.default_l1_confs(),
+            chain: value,
+        })
     }
 }
 
@@ -252,6 +447,9 @@ struct DefaultsProvider {
     /// The port to serve the Magi RPC server on
     rpc_port: u16,
     rpc_addr: String,
+    /// The directory used to persist node data
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data_dir: Option<PathBuf>,
 }
 
 impl Default for DefaultsProvider {
@@ -262,6 +460,7 @@ impl Default for DefaultsProvider {
             l2_engine_url: "http://127.0.0.1:8551".to_string(),
             rpc_port: 9545,
             rpc_addr: "127.0.0.1".to_string(),
+            data_dir: dirs::home_dir().map(|home| home.join(".magi/data")),
         }
     }
 }
@@ -290,6 +489,20 @@ impl ChainConfig {
         }
     }
 
+    /// Returns the default L1 confirmation depth for this chain. Known networks wait
+    /// for a few confirmations, custom chains and devnets ingest blocks at the L1 head.
+    pub fn default_l1_confs(&self) -> u64 {
+        match self.network.as_str() {
+            "optimism" | "optimism-sepolia" | "base" | "base-sepolia" => 4,
+            _ => 0,
+        }
+    }
+
+    /// Returns true if the Ecotone hardfork is active at the given timestamp
+    pub fn is_ecotone(&self, timestamp: u64) -> bool {
+        timestamp >= self.ecotone_time
+    }
+
     /// Returns true if the block is the first block subject to the Ecotone hardfork
     pub fn is_ecotone_activation_block(&self, l2_block_timestamp: u64) -> bool {
         l2_block_timestamp == self.ecotone_time
@@ -335,6 +548,10 @@ impl ChainConfig {
                 l1_fee_overhead: U256::from(188),
                 l1_fee_scalar: U256::from(684000),
                 unsafe_block_signer: addr("0xAAAA45d9549EDA09E70937013520214382Ffc4A2"),
+                eip1559_denominator: 0,
+                eip1559_elasticity: 0,
+                operator_fee_scalar: 0,
+                operator_fee_constant: 0,
             },
             batch_inbox: addr("0xff00000000000000000000000000000000000010"),
             deposit_contract: addr("0xbEb5Fc579115071764c7423A4f12eDde41f106Ed"),
@@ -384,6 +601,10 @@ impl ChainConfig {
                 l1_fee_overhead: U256::from(188),
                 l1_fee_scalar: U256::from(684000),
                 unsafe_block_signer: addr("0x57CACBB0d30b01eb2462e5dC940c161aff3230D3"),
+                eip1559_denominator: 0,
+                eip1559_elasticity: 0,
+                operator_fee_scalar: 0,
+                operator_fee_constant: 0,
             },
             system_config_contract: addr("0x034edd2a225f7f429a63e0f1d2084b9e0a93b538"),
             batch_inbox: addr("0xff00000000000000000000000000000011155420"),
@@ -430,6 +651,10 @@ impl ChainConfig {
                 l1_fee_overhead: U256::from(188),
                 l1_fee_scalar: U256::from(684000),
                 unsafe_block_signer: addr("0xAf6E19BE0F9cE7f8afd49a1824851023A8249e8a"),
+                eip1559_denominator: 0,
+                eip1559_elasticity: 0,
+                operator_fee_scalar: 0,
+                operator_fee_constant: 0,
             },
             batch_inbox: addr("0xff00000000000000000000000000000000008453"),
             deposit_contract: addr("0x49048044d57e1c92a77f79988d21fa8faf74e97e"),
@@ -476,6 +701,10 @@ impl ChainConfig {
                 l1_fee_overhead: U256::from(2100),
                 l1_fee_scalar: U256::from(1000000),
                 unsafe_block_signer: addr("0xb830b99c95Ea32300039624Cb567d324D4b1D83C"),
+                eip1559_denominator: 0,
+                eip1559_elasticity: 0,
+                operator_fee_scalar: 0,
+                operator_fee_constant: 0,
             },
             system_config_contract: addr("0xf272670eb55e895584501d564AfEB048bEd26194"),
             batch_inbox: addr("0xff00000000000000000000000000000000084532"),
@@ -618,6 +847,10 @@ impl From<ExternalChainConfig> for ChainConfig {
                 l1_fee_overhead: U256::from_be_bytes(external.genesis.system_config.overhead.0),
                 l1_fee_scalar: U256::from_be_bytes(external.genesis.system_config.scalar.0),
                 unsafe_block_signer: Address::ZERO,
+                eip1559_denominator: 0,
+                eip1559_elasticity: 0,
+                operator_fee_scalar: 0,
+                operator_fee_constant: 0,
             },
             batch_inbox: external.batch_inbox_address,
             deposit_contract: external.deposit_contract_address,
@@ -921,6 +1154,77 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_l1_confs() {
+        let config_path = PathBuf::from("/nonexistent/magi.toml");
+        let cli_config = |l1_confs| CliConfig {
+            l1_rpc_url: Some("http://localhost:8545".to_string()),
+            l1_beacon_url: Some("http://localhost:5052".to_string()),
+            l1_beacon_fallback_urls: None,
+            l2_rpc_url: None,
+            l2_engine_url: None,
+            jwt_secret: Some("".to_string()),
+            checkpoint_sync_url: None,
+            rpc_port: None,
+            rpc_addr: None,
+            devnet: false,
+            data_dir: None,
+            l1_confs,
+            p2p: Default::default(),
+        };
+
+        let config = Config::new(&config_path, cli_config(None), ChainConfig::optimism());
+        assert_eq!(config.l1_confs, 4);
+
+        let mut chain = ChainConfig::optimism();
+        chain.network = "external".to_string();
+        let config = Config::new(&config_path, cli_config(None), chain);
+        assert_eq!(config.l1_confs, 0);
+
+        let config = Config::new(&config_path, cli_config(Some(10)), ChainConfig::base());
+        assert_eq!(config.l1_confs, 10);
+    }
+
+    #[test]
+    fn test_p2p_config() {
+        let config_path = PathBuf::from("/nonexistent/magi.toml");
+        let cli_config = CliConfig {
+            l1_rpc_url: Some("http://localhost:8545".to_string()),
+            l1_beacon_url: Some("http://localhost:5052".to_string()),
+            l1_beacon_fallback_urls: None,
+            l2_rpc_url: None,
+            l2_engine_url: None,
+            jwt_secret: Some("".to_string()),
+            checkpoint_sync_url: None,
+            rpc_port: None,
+            rpc_addr: None,
+            devnet: false,
+            data_dir: None,
+            l1_confs: None,
+            p2p: CliP2PConfig {
+                listen_tcp_port: Some(9222),
+                advertise_ip: Some(Ipv4Addr::new(1, 2, 3, 4)),
+                max_peers: Some(50),
+                muxers: Some(vec![Muxer::Mplex]),
+                ..Default::default()
+            },
+        };
+
+        let config = Config::new(&config_path, cli_config, ChainConfig::optimism());
+        assert_eq!(config.p2p.listen_ip, Ipv4Addr::UNSPECIFIED);
+        assert_eq!(config.p2p.listen_tcp_port, 9222);
+        assert_eq!(config.p2p.listen_udp_port, 9876);
+        assert_eq!(config.p2p.advertise_ip, Some(Ipv4Addr::new(1, 2, 3, 4)));
+        assert!(config.p2p.bootnodes.is_empty());
+        assert_eq!(config.p2p.target_peers, 20);
+        assert_eq!(config.p2p.max_peers, 50);
+        assert_eq!(config.p2p.muxers, vec![Muxer::Mplex]);
+        assert_eq!(config.p2p.quic_port, None);
+        assert!(!config.p2p.upnp);
+        assert_eq!(config.p2p.replay_blocks, None);
+        assert_eq!(config.p2p.replay_speed, 1);
+    }
+
     #[test]
     #[should_panic(expected = "Invalid network name")]
     fn test_chain_config_unknown_chain() {
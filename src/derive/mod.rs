@@ -122,6 +122,7 @@ mod tests {
             let config = Arc::new(Config {
                 l1_rpc_url: rpc.to_string(),
                 l1_beacon_url: String::new(),
+                l1_beacon_fallback_urls: Vec::new(),
                 l2_rpc_url: l2_rpc.to_string(),
                 chain: ChainConfig::optimism_sepolia(),
                 l2_engine_url: String::new(),
@@ -130,6 +131,9 @@ mod tests {
                 rpc_port: 9545,
                 rpc_addr: "127.0.0.1".to_string(),
                 devnet: false,
+                data_dir: None,
+                l1_confs: 0,
+                p2p: Default::default(),
             });
 
             let mut chain_watcher = ChainWatcher::new(
@@ -154,12 +158,12 @@ mod tests {
 
             let mut pipeline = Pipeline::new(state.clone(), config.clone(), 0).unwrap();
 
-            chain_watcher.recv_from_channel().await.unwrap();
-            let update = chain_watcher.recv_from_channel().await.unwrap();
-
-            let l1_info = match update {
-                BlockUpdate::NewBlock(block) => *block,
-                _ => panic!("wrong update type"),
+            let l1_info = loop {
+                match chain_watcher.recv_from_channel().await.unwrap() {
+                    BlockUpdate::NewBlock(block) => break *block,
+                    BlockUpdate::Reorg => panic!("wrong update type"),
+                    _ => continue,
+                }
             };
 
             pipeline
@@ -1,7 +1,7 @@
 use core::fmt::Debug;
 use std::cmp::Ordering;
 use std::collections::BTreeMap;
-use std::io::Read;
+use std::io::{BufReader, Read};
 use std::sync::{Arc, RwLock};
 
 use ethers::utils::rlp::Rlp;
@@ -407,7 +407,7 @@ where
 /// Attempts to decode channel data into a [Batch] vector
 fn decode_batches(channel: &Channel, chain_id: u64) -> Result<Vec<Batch>> {
     let mut channel_data = Vec::new();
-    let d = Decoder::new(channel.data.as_slice())?;
+    let d = BufReader::new(Decoder::new(channel.data.as_slice())?);
     for b in d.bytes() {
         if let Ok(b) = b {
             channel_data.push(b);
@@ -407,6 +407,7 @@ mod tests {
         let config = Config {
             l1_rpc_url: String::new(),
             l1_beacon_url: String::new(),
+            l1_beacon_fallback_urls: Vec::new(),
             l2_rpc_url: String::new(),
             l2_engine_url: String::new(),
             jwt_secret: String::new(),
@@ -415,6 +416,9 @@ mod tests {
             chain: ChainConfig::optimism_sepolia(),
             checkpoint_sync_url: None,
             devnet: false,
+            data_dir: None,
+            l1_confs: 0,
+            p2p: Default::default(),
         };
 
         let (tx, rx) = mpsc::channel();
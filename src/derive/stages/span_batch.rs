@@ -117,7 +117,7 @@ fn take_data(data: &[u8], length: usize) -> (&[u8], &[u8]) {
 fn decode_bitlist(data: &[u8], len: u64) -> (Vec<bool>, &[u8]) {
     let mut bitlist = Vec::new();
 
-    let len_up = (len + 7) / 8;
+    let len_up = len.div_ceil(8);
     let (bytes, data) = take_data(data, len_up as usize);
 
     for byte in bytes.iter().rev() {
@@ -454,7 +454,7 @@ fn decode_u256(data: &[u8]) -> (U256, &[u8]) {
 
 #[cfg(test)]
 mod test {
-    use std::io::Read;
+    use std::io::{BufReader, Read};
 
     use ethers::{
         types::H256,
@@ -484,7 +484,7 @@ mod test {
         }
 
         let channel = Channel::from(pending_channel);
-        let d = Decoder::new(channel.data.as_slice()).unwrap();
+        let d = BufReader::new(Decoder::new(channel.data.as_slice()).unwrap());
 
         let mut vec = Vec::new();
         for b in d.bytes() {
@@ -11,7 +11,11 @@ use eyre::Result;
 use crate::{
     common::{BlockInfo, Epoch},
     config::Config,
-    engine::{Engine, EngineApi, ExecutionPayload, ForkchoiceState, PayloadAttributes, Status},
+    driver::{DriverError, SyncStart},
+    engine::{
+        Engine, EngineApi, ExecutionPayload, ForkchoiceState, PayloadAttributes, Status,
+        ENGINE_CAPABILITIES,
+    },
 };
 
 /// The EngineDriver is responsible for initiating block production & validation via the [EngineApi]
@@ -32,6 +36,8 @@ pub struct EngineDriver<E: Engine> {
     pub finalized_head: BlockInfo,
     /// Batch epoch of the finalized head
     pub finalized_epoch: Epoch,
+    /// Engine methods supported by the execution client
+    pub engine_capabilities: Vec<String>,
 }
 
 impl<E: Engine> EngineDriver<E> {
@@ -51,6 +57,31 @@ impl<E: Engine> EngineDriver<E> {
         }
     }
 
+    /// Handles the `pending` attributes that failed to apply last time, or else the next
+    /// attributes from `attributes`. Attributes that fail to apply are kept in `pending`, as
+    /// they have already been taken from the pipeline and must be retried exactly.
+    /// Returns the L1 inclusion block of the applied attributes, or `None` if there were none.
+    pub async fn handle_next_attributes(
+        &mut self,
+        pending: &mut Option<PayloadAttributes>,
+        attributes: &mut impl Iterator<Item = PayloadAttributes>,
+    ) -> Result<Option<u64>> {
+        let Some(next_attributes) = pending.take().or_else(|| attributes.next()) else {
+            return Ok(None);
+        };
+
+        let l1_inclusion_block = next_attributes
+            .l1_inclusion_block
+            .ok_or(eyre::eyre!("attributes without inclusion block"))?;
+
+        if let Err(err) = self.handle_attributes(next_attributes.clone()).await {
+            *pending = Some(next_attributes);
+            return Err(err);
+        }
+
+        Ok(Some(l1_inclusion_block))
+    }
+
     /// Instructs the engine to create a block and updates the forkchoice, based on a payload received via p2p gossip.
     pub async fn handle_unsafe_payload(&mut self, payload: &ExecutionPayload) -> Result<()> {
         self.push_payload(payload.clone()).await?;
@@ -66,17 +97,86 @@ impl<E: Engine> EngineDriver<E> {
         Ok(())
     }
 
-    /// Updates the [EngineDriver] finalized head & epoch
-    pub fn update_finalized(&mut self, head: BlockInfo, epoch: Epoch) {
+    /// Updates the [EngineDriver] finalized head & epoch, and sends the new forkchoice to the [Engine]
+    pub async fn update_finalized(&mut self, head: BlockInfo, epoch: Epoch) -> Result<()> {
         self.finalized_head = head;
         self.finalized_epoch = epoch;
+        self.update_forkchoice().await
+    }
+
+    /// Sets the [EngineDriver] unsafe, safe & finalized heads and epochs to the given [SyncStart].
+    pub fn reset_to(&mut self, start: &SyncStart) {
+        self.unsafe_head = start.unsafe_head.l2_block_info;
+        self.safe_head = start.safe_head.l2_block_info;
+        self.safe_epoch = start.safe_head.l1_epoch;
+        self.finalized_head = start.finalized_head.l2_block_info;
+        self.finalized_epoch = start.finalized_head.l1_epoch;
     }
 
-    /// Sets the [EngineDriver] unsafe & safe heads, and safe epoch to the current finalized head & epoch.
-    pub fn reorg(&mut self) {
-        self.unsafe_head = self.finalized_head;
-        self.safe_head = self.finalized_head;
-        self.safe_epoch = self.finalized_epoch;
+    /// Exchanges capabilities with the [Engine] and stores the methods it supports.
+    /// Errors if the engine is missing any method Magi calls.
+    pub async fn exchange_capabilities(&mut self) -> Result<()> {
+        let capabilities = ENGINE_CAPABILITIES.iter().map(|m| m.to_string()).collect();
+        let supported = self.engine.exchange_capabilities(capabilities).await?;
+        tracing::info!("engine capabilities: {:?}", supported);
+
+        let missing = ENGINE_CAPABILITIES
+            .into_iter()
+            .filter(|method| !supported.iter().any(|s| s == method))
+            .collect::<Vec<_>>();
+
+        if !missing.is_empty() {
+            eyre::bail!(
+                "execution client does not support required engine methods: {}",
+                missing.join(", ")
+            );
+        }
+
+        self.engine_capabilities = supported;
+        Ok(())
+    }
+
+    /// Sends an unsafe payload to the [Engine] and makes it the head, leaving the safe and
+    /// finalized heads unset so the engine can sync to it from its own p2p network.
+    /// Returns true once the engine has synced up to the payload.
+    pub async fn sync_to_payload(&self, payload: &ExecutionPayload) -> Result<bool> {
+        let status = self.engine.new_payload(payload.clone()).await?;
+        if let Status::Invalid | Status::InvalidBlockHash = status.status {
+            eyre::bail!(
+                "invalid unsafe payload {:?}: {:?}",
+                payload.block_hash,
+                status.validation_error
+            );
+        }
+
+        let forkchoice = ForkchoiceState {
+            head_block_hash: payload.block_hash,
+            safe_block_hash: H256::zero(),
+            finalized_block_hash: H256::zero(),
+        };
+
+        let update = self.engine.forkchoice_updated(forkchoice, None).await?;
+        match update.payload_status.status {
+            Status::Valid => Ok(true),
+            Status::Syncing | Status::Accepted => Ok(false),
+            status => eyre::bail!(
+                "could not accept forkchoice {:?}: {:?}",
+                status,
+                update.payload_status.validation_error
+            ),
+        }
+    }
+
+    /// Sets the unsafe, safe & finalized heads to a block the [Engine] has synced to on its own,
+    /// and sends the updated forkchoice to the engine.
+    pub async fn finish_sync(&mut self, head: BlockInfo, epoch: Epoch) -> Result<()> {
+        self.unsafe_head = head;
+        self.safe_head = head;
+        self.safe_epoch = epoch;
+        self.finalized_head = head;
+        self.finalized_epoch = epoch;
+
+        self.update_forkchoice().await
     }
 
     /// Sends a `ForkchoiceUpdated` message to check if the [Engine] is ready.
@@ -136,7 +236,7 @@ impl<E: Engine> EngineDriver<E> {
             .await?;
 
         if update.payload_status.status != Status::Valid {
-            eyre::bail!("invalid payload attributes");
+            return Err(DriverError::reset(eyre::eyre!("invalid payload attributes")).into());
         }
 
         let id = update
@@ -150,7 +250,7 @@ impl<E: Engine> EngineDriver<E> {
     async fn push_payload(&self, payload: ExecutionPayload) -> Result<()> {
         let status = self.engine.new_payload(payload).await?;
         if status.status != Status::Valid && status.status != Status::Accepted {
-            eyre::bail!("invalid execution payload");
+            return Err(DriverError::reset(eyre::eyre!("invalid execution payload")).into());
         }
 
         Ok(())
@@ -162,10 +262,11 @@ impl<E: Engine> EngineDriver<E> {
 
         let update = self.engine.forkchoice_updated(forkchoice, None).await?;
         if update.payload_status.status != Status::Valid {
-            eyre::bail!(
+            return Err(DriverError::reset(eyre::eyre!(
                 "could not accept new forkchoice: {:?}",
                 update.payload_status.validation_error
-            );
+            ))
+            .into());
         }
 
         Ok(())
@@ -269,6 +370,274 @@ impl EngineDriver<EngineApi> {
             safe_epoch: finalized_epoch,
             finalized_head,
             finalized_epoch,
+            engine_capabilities: Vec::new(),
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use ethers::{providers::Provider, types::H256};
+
+    use crate::{
+        common::BlockInfo,
+        engine::{
+            ExecutionPayload, ForkChoiceUpdate, MockEngine, PayloadStatus, Status,
+            ENGINE_CAPABILITIES, ENGINE_GET_PAYLOAD_V2,
+        },
+    };
+
+    #[cfg(feature = "test-utils")]
+    use crate::{
+        common::Epoch,
+        driver::FinalityTracker,
+        engine::{MemoryEngine, PayloadAttributes},
+    };
+
+    use super::EngineDriver;
+
+    fn engine_driver(capabilities: Vec<String>) -> EngineDriver<MockEngine> {
+        engine_driver_with_status(Status::Valid, capabilities)
+    }
+
+    fn engine_driver_with_status(
+        status: Status,
+        capabilities: Vec<String>,
+    ) -> EngineDriver<MockEngine> {
+        let status = PayloadStatus {
+            status,
+            latest_valid_hash: None,
+            validation_error: None,
+        };
+        let update = ForkChoiceUpdate {
+            payload_status: status.clone(),
+            payload_id: None,
+        };
+
+        let engine = MockEngine {
+            forkchoice_updated_payloads_res: update.clone(),
+            forkchoice_updated_res: update,
+            new_payload_res: status,
+            get_payload_res: Default::default(),
+            capabilities_res: capabilities,
+        };
+
+        EngineDriver {
+            engine: Arc::new(engine),
+            provider: Provider::try_from("http://localhost:8545").unwrap(),
+            blocktime: 2,
+            unsafe_head: Default::default(),
+            safe_head: Default::default(),
+            safe_epoch: Default::default(),
+            finalized_head: Default::default(),
+            finalized_epoch: Default::default(),
+            engine_capabilities: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_exchange_capabilities() {
+        let mut capabilities: Vec<String> =
+            ENGINE_CAPABILITIES.iter().map(|m| m.to_string()).collect();
+        capabilities.push("engine_getPayloadBodiesByHashV1".to_string());
+
+        let mut driver = engine_driver(capabilities.clone());
+        driver.exchange_capabilities().await.unwrap();
+        assert_eq!(driver.engine_capabilities, capabilities);
+    }
+
+    #[tokio::test]
+    async fn test_exchange_capabilities_missing_method() {
+        let capabilities = ENGINE_CAPABILITIES
+            .iter()
+            .filter(|m| **m != ENGINE_GET_PAYLOAD_V2)
+            .map(|m| m.to_string())
+            .collect();
+
+        let mut driver = engine_driver(capabilities);
+        let err = driver.exchange_capabilities().await.unwrap_err();
+        assert!(err.to_string().contains(ENGINE_GET_PAYLOAD_V2));
+        assert!(driver.engine_capabilities.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_sync_to_payload() {
+        let payload = ExecutionPayload {
+            block_hash: H256::random(),
+            ..Default::default()
+        };
+
+        let driver = engine_driver_with_status(Status::Syncing, Vec::new());
+        assert!(!driver.sync_to_payload(&payload).await.unwrap());
+
+        let driver = engine_driver_with_status(Status::Invalid, Vec::new());
+        assert!(driver.sync_to_payload(&payload).await.is_err());
+
+        let mut driver = engine_driver(Vec::new());
+        assert!(driver.sync_to_payload(&payload).await.unwrap());
+
+        let head = BlockInfo {
+            hash: payload.block_hash,
+            number: 10,
+            ..Default::default()
+        };
+        driver.finish_sync(head, Default::default()).await.unwrap();
+        assert_eq!(driver.unsafe_head, head);
+        assert_eq!(driver.safe_head, head);
+        assert_eq!(driver.finalized_head, head);
+    }
+
+    #[cfg(feature = "test-utils")]
+    fn memory_engine_driver(genesis: BlockInfo) -> (Arc<MemoryEngine>, EngineDriver<MemoryEngine>) {
+        // No L2 blocks can be fetched, so every set of attributes is built by the engine
+        let engine = Arc::new(MemoryEngine::new(genesis));
+        let driver = EngineDriver {
+            engine: engine.clone(),
+            provider: Provider::try_from("http://127.0.0.1:1").unwrap(),
+            blocktime: 2,
+            unsafe_head: genesis,
+            safe_head: genesis,
+            safe_epoch: Default::default(),
+            finalized_head: genesis,
+            finalized_epoch: Default::default(),
+            engine_capabilities: Vec::new(),
+        };
+
+        (engine, driver)
+    }
+
+    #[cfg(feature = "test-utils")]
+    fn attributes(timestamp: u64, epoch: u64) -> PayloadAttributes {
+        PayloadAttributes {
+            timestamp: timestamp.into(),
+            epoch: Some(Epoch {
+                number: epoch,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[cfg(feature = "test-utils")]
+    fn genesis() -> BlockInfo {
+        BlockInfo {
+            hash: H256::from_low_u64_be(1),
+            number: 0,
+            parent_hash: H256::zero(),
+            timestamp: 100,
+        }
+    }
+
+    #[cfg(feature = "test-utils")]
+    #[tokio::test]
+    async fn test_handle_attributes_builds_chain() {
+        let genesis = genesis();
+        let (engine, mut driver) = memory_engine_driver(genesis);
+
+        for timestamp in [102u64, 104] {
+            driver
+                .handle_attributes(attributes(timestamp, 0))
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(driver.safe_head.number, 2);
+        assert_eq!(driver.unsafe_head, driver.safe_head);
+
+        let head = engine.head();
+        assert_eq!(head.block_hash, driver.safe_head.hash);
+        let parent = engine.block(head.parent_hash).unwrap();
+        assert_eq!(parent.parent_hash, genesis.hash);
+
+        let forkchoice = engine.forkchoice();
+        assert_eq!(forkchoice.safe_block_hash, driver.safe_head.hash);
+        assert_eq!(forkchoice.finalized_block_hash, genesis.hash);
+    }
+
+    #[cfg(feature = "test-utils")]
+    #[tokio::test]
+    async fn test_retry_failed_attributes() {
+        let genesis = genesis();
+        let (engine, mut driver) = memory_engine_driver(genesis);
+
+        let mut attributes = [102u64, 104]
+            .into_iter()
+            .map(|timestamp| PayloadAttributes {
+                l1_inclusion_block: Some(5),
+                ..attributes(timestamp, 0)
+            });
+        let mut pending = None;
+
+        engine.fail_next_calls(1);
+        let res = driver
+            .handle_next_attributes(&mut pending, &mut attributes)
+            .await;
+        assert!(res.is_err());
+        assert_eq!(pending.as_ref().unwrap().timestamp.as_u64(), 102);
+        assert_eq!(driver.safe_head, genesis);
+
+        // The failed attributes are retried before new ones are taken
+        let res = driver
+            .handle_next_attributes(&mut pending, &mut attributes)
+            .await;
+        assert_eq!(res.unwrap(), Some(5));
+        assert!(pending.is_none());
+        assert_eq!(driver.safe_head.number, 1);
+        assert_eq!(driver.safe_head.timestamp, 102);
+        assert_eq!(driver.safe_head.parent_hash, genesis.hash);
+
+        let block_one = driver.safe_head;
+        driver
+            .handle_next_attributes(&mut pending, &mut attributes)
+            .await
+            .unwrap();
+        assert_eq!(driver.safe_head.number, 2);
+        assert_eq!(driver.safe_head.parent_hash, block_one.hash);
+        assert_eq!(engine.head().block_hash, driver.safe_head.hash);
+
+        let res = driver
+            .handle_next_attributes(&mut pending, &mut attributes)
+            .await;
+        assert_eq!(res.unwrap(), None);
+    }
+
+    #[cfg(feature = "test-utils")]
+    #[tokio::test]
+    async fn test_finalize_mid_epoch_block() {
+        let (engine, mut driver) = memory_engine_driver(genesis());
+        let mut finality = FinalityTracker::default();
+
+        // Blocks 1 and 2 are in epoch 1 and derived at L1 block 5, block 3 starts epoch 2
+        // and is derived at L1 block 6
+        for (timestamp, epoch, inclusion) in [(102u64, 1u64, 5u64), (104, 1, 5), (106, 2, 6)] {
+            driver
+                .handle_attributes(attributes(timestamp, epoch))
+                .await
+                .unwrap();
+            finality.add_safe_block(driver.safe_head, driver.safe_epoch, inclusion);
+        }
+
+        let block_two = engine.block(engine.head().parent_hash).unwrap();
+
+        finality.update_l1_finalized(5);
+        let (head, epoch) = finality.pop_finalized().unwrap();
+        driver.update_finalized(head, epoch).await.unwrap();
+
+        assert_eq!(driver.finalized_head.number, 2);
+        assert_eq!(driver.finalized_epoch.number, 1);
+        assert_eq!(
+            engine.forkchoice().finalized_block_hash,
+            block_two.block_hash
+        );
+
+        finality.update_l1_finalized(6);
+        let (head, epoch) = finality.pop_finalized().unwrap();
+        driver.update_finalized(head, epoch).await.unwrap();
+        assert_eq!(
+            engine.forkchoice().finalized_block_hash,
+            driver.safe_head.hash
+        );
+    }
+}
@@ -0,0 +1,102 @@
+use std::fmt;
+
+use eyre::Report;
+
+use crate::engine::{EngineError, EngineErrorKind};
+
+/// An error returned while advancing the [Driver](super::Driver), classified by how the
+/// driver recovers from it
+#[derive(Debug)]
+pub enum DriverError {
+    /// A transient failure, such as an unreachable RPC. The driver backs off and retries.
+    Temporary(Report),
+    /// The driver's view of the chain is inconsistent with the engine or L1. The driver
+    /// resets the pipeline and restarts derivation from a recomputed sync start point.
+    Reset(Report),
+    /// An unrecoverable failure. The driver stops and the node shuts down.
+    Critical(Report),
+}
+
+impl DriverError {
+    /// Creates a [DriverError::Temporary]
+    pub fn temporary(err: impl Into<Report>) -> Self {
+        Self::Temporary(err.into())
+    }
+
+    /// Creates a [DriverError::Reset]
+    pub fn reset(err: impl Into<Report>) -> Self {
+        Self::Reset(err.into())
+    }
+
+    /// Creates a [DriverError::Critical]
+    pub fn critical(err: impl Into<Report>) -> Self {
+        Self::Critical(err.into())
+    }
+
+    /// Returns the underlying error
+    pub fn report(&self) -> &Report {
+        match self {
+            Self::Temporary(err) | Self::Reset(err) | Self::Critical(err) => err,
+        }
+    }
+}
+
+impl From<Report> for DriverError {
+    /// Classifies an error returned while advancing the driver. Errors that were already
+    /// classified keep their class, [EngineError]s are classified by their kind, and anything
+    /// else is assumed to be temporary.
+    fn from(err: Report) -> Self {
+        if let Some(class) = err.downcast_ref::<DriverError>() {
+            return match class {
+                Self::Temporary(_) => Self::Temporary(err),
+                Self::Reset(_) => Self::Reset(err),
+                Self::Critical(_) => Self::Critical(err),
+            };
+        }
+
+        match err.downcast_ref::<EngineError>().map(EngineError::kind) {
+            Some(EngineErrorKind::Reset) => Self::Reset(err),
+            Some(EngineErrorKind::Critical) => Self::Critical(err),
+            _ => Self::Temporary(err),
+        }
+    }
+}
+
+impl fmt::Display for DriverError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Temporary(err) => write!(f, "temporary error: {}", err),
+            Self::Reset(err) => write!(f, "reset error: {}", err),
+            Self::Critical(err) => write!(f, "critical error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for DriverError {}
+
+#[cfg(test)]
+mod tests {
+    use eyre::WrapErr;
+
+    use crate::engine::EngineError;
+
+    use super::DriverError;
+
+    #[test]
+    fn test_classify_errors() {
+        let err = DriverError::from(eyre::eyre!("connection refused"));
+        assert!(matches!(err, DriverError::Temporary(_)));
+
+        let err: eyre::Result<()> = Err(EngineError::InvalidForkchoiceState.into());
+        let err = DriverError::from(err.wrap_err("failed to handle attributes").unwrap_err());
+        assert!(matches!(err, DriverError::Reset(_)));
+
+        let err = DriverError::from(eyre::Report::from(EngineError::TooLargeRequest));
+        assert!(matches!(err, DriverError::Critical(_)));
+
+        let err: eyre::Result<()> = Err(DriverError::critical(eyre::eyre!("lock poisoned")).into());
+        let err = DriverError::from(err.wrap_err("failed to update state").unwrap_err());
+        assert!(matches!(err, DriverError::Critical(_)));
+        assert!(err.to_string().contains("failed to update state"));
+    }
+}
@@ -0,0 +1,100 @@
+use crate::common::{BlockInfo, Epoch};
+
+/// Tracks safe L2 blocks until the L1 data they were derived from is finalized.
+///
+/// A safe block is finalized once the L1 block its batch was fully derived from is finalized.
+/// Since its L1 origin comes before that L1 block, the safe block then depends on finalized
+/// L1 data only.
+#[derive(Debug, Default)]
+pub struct FinalityTracker {
+    /// Safe L2 blocks, with their epochs and the L1 blocks they were derived from, in order
+    unfinalized: Vec<(BlockInfo, Epoch, u64)>,
+    /// The most recent finalized L1 block number
+    finalized_l1_block: u64,
+}
+
+impl FinalityTracker {
+    /// Tracks a new safe L2 block, derived from data included up to `l1_inclusion_block`
+    pub fn add_safe_block(&mut self, head: BlockInfo, epoch: Epoch, l1_inclusion_block: u64) {
+        self.unfinalized.push((head, epoch, l1_inclusion_block));
+    }
+
+    /// Updates the most recent finalized L1 block number
+    pub fn update_l1_finalized(&mut self, number: u64) {
+        self.finalized_l1_block = self.finalized_l1_block.max(number);
+    }
+
+    /// Returns the most recent finalized L1 block number
+    pub fn l1_finalized(&self) -> u64 {
+        self.finalized_l1_block
+    }
+
+    /// Removes every safe block derived entirely from finalized L1 data, and returns the
+    /// highest of them
+    pub fn pop_finalized(&mut self) -> Option<(BlockInfo, Epoch)> {
+        let count = self
+            .unfinalized
+            .iter()
+            .take_while(|(_, _, inclusion)| *inclusion <= self.finalized_l1_block)
+            .count();
+
+        self.unfinalized
+            .drain(..count)
+            .next_back()
+            .map(|(head, epoch, _)| (head, epoch))
+    }
+
+    /// Stops tracking all safe blocks, keeping the finalized L1 block number
+    pub fn clear(&mut self) {
+        self.unfinalized.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::H256;
+
+    use crate::common::{BlockInfo, Epoch};
+
+    use super::FinalityTracker;
+
+    fn block(number: u64) -> BlockInfo {
+        BlockInfo {
+            hash: H256::from_low_u64_be(number),
+            number,
+            ..Default::default()
+        }
+    }
+
+    fn epoch(number: u64) -> Epoch {
+        Epoch {
+            number,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_finalizes_mid_epoch_blocks() {
+        let mut tracker = FinalityTracker::default();
+
+        // Blocks 1 and 2 are in epoch 10, 3 and 4 in epoch 11, all derived at L1 block 12 or 13
+        tracker.add_safe_block(block(1), epoch(10), 12);
+        tracker.add_safe_block(block(2), epoch(10), 12);
+        tracker.add_safe_block(block(3), epoch(11), 12);
+        tracker.add_safe_block(block(4), epoch(11), 13);
+
+        tracker.update_l1_finalized(11);
+        assert_eq!(tracker.pop_finalized(), None);
+
+        tracker.update_l1_finalized(12);
+        assert_eq!(tracker.pop_finalized(), Some((block(3), epoch(11))));
+        assert_eq!(tracker.pop_finalized(), None);
+
+        // Finality never goes backwards
+        tracker.update_l1_finalized(5);
+        assert_eq!(tracker.l1_finalized(), 12);
+
+        tracker.update_l1_finalized(20);
+        assert_eq!(tracker.pop_finalized(), Some((block(4), epoch(11))));
+    }
+}
@@ -149,6 +149,7 @@ mod test_utils {
         Config {
             l1_rpc_url: Default::default(),
             l1_beacon_url: Default::default(),
+            l1_beacon_fallback_urls: Default::default(),
             l2_rpc_url: Default::default(),
             l2_engine_url: Default::default(),
             chain: ChainConfig::optimism(),
@@ -157,6 +158,9 @@ mod test_utils {
             rpc_port: Default::default(),
             rpc_addr: Default::default(),
             devnet: false,
+            data_dir: None,
+            l1_confs: 0,
+            p2p: Default::default(),
         }
     }
 
@@ -1,27 +1,35 @@
 use std::{
+    collections::{HashMap, HashSet},
     process,
-    sync::{mpsc::Receiver, Arc, RwLock},
-    time::Duration,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
 };
 
 use ethers::{
-    providers::{Http, Provider},
-    types::Address,
+    providers::{Http, Middleware, Provider},
+    types::{Address, H256},
 };
-use eyre::Result;
+use eyre::{Result, WrapErr};
 use reqwest::Url;
 use tokio::{
-    sync::watch::{self, Sender},
-    time::sleep,
+    sync::{
+        mpsc,
+        watch::{self, Sender},
+    },
+    time::{interval, sleep, sleep_until, MissedTickBehavior},
 };
 
 use crate::{
-    common::{BlockInfo, Epoch},
+    common::BlockInfo,
     config::Config,
     derive::{state::State, Pipeline},
-    engine::{Engine, EngineApi, ExecutionPayload},
+    engine::{Engine, EngineApi, ExecutionPayload, PayloadAttributes},
     l1::{BlockUpdate, ChainWatcher},
-    network::{handlers::block_handler::BlockHandler, service::Service},
+    network::{
+        handlers::block_handler::BlockHandler,
+        recorder::{self, BlockRecorder, BlockReplay, RecordFormat},
+        service::{NetworkHandle, PayloadSync, Service},
+    },
     rpc,
     telemetry::metrics,
 };
@@ -31,9 +39,21 @@ use self::engine_driver::EngineDriver;
 /// A module to handle block production & validation
 mod engine_driver;
 
+/// Driver error classification
+mod error;
+pub use error::*;
+
+/// A module to track the finality of safe blocks
+mod finality;
+pub use finality::FinalityTracker;
+
 /// A module to handle fetching blocks
 mod info;
 
+/// A module to find the L2 heads to resume from
+mod sync_start;
+pub use sync_start::{SyncStart, SyncStartFetcher, SyncStartProvider};
+
 /// A module to handle conversions to a [HeadInfo] struct
 mod types;
 pub use types::*;
@@ -45,12 +65,18 @@ pub struct Driver<E: Engine> {
     pipeline: Pipeline,
     /// The engine driver
     engine_driver: EngineDriver<E>,
-    /// List of unfinalized L2 blocks with their epochs, L1 inclusions, and sequence numbers
-    unfinalized_blocks: Vec<(BlockInfo, Epoch, u64, u64)>,
-    /// Current finalized L1 block number
-    finalized_l1_block_number: u64,
+    /// Safe L2 blocks waiting for the L1 data they were derived from to be finalized
+    finality: FinalityTracker,
     /// List of unsafe blocks that have not been applied yet
     future_unsafe_blocks: Vec<ExecutionPayload>,
+    /// Unsafe blocks received from peers by number, keyed by hash. These are not signed, so they
+    /// are only moved to `future_unsafe_blocks` once a trusted block names them as its parent.
+    requested_unsafe_blocks: HashMap<H256, ExecutionPayload>,
+    /// Hashes of the blocks in `future_unsafe_blocks` that were requested from peers rather
+    /// than received via gossip
+    verified_requested_blocks: HashSet<H256>,
+    /// When each missing unsafe block number was last requested from peers
+    unsafe_block_requests: HashMap<u64, Instant>,
     /// State struct to keep track of global state
     state: Arc<RwLock<State>>,
     /// L1 chain watcher
@@ -58,13 +84,45 @@ pub struct Driver<E: Engine> {
     /// Channel to receive the shutdown signal from
     shutdown_recv: watch::Receiver<bool>,
     /// Channel to receive unsafe blocks from
-    unsafe_block_recv: Receiver<ExecutionPayload>,
+    unsafe_block_recv: mpsc::UnboundedReceiver<ExecutionPayload>,
+    /// Channel to request missing unsafe blocks from peers by number
+    unsafe_block_request_sender: mpsc::UnboundedSender<u64>,
+    /// Channel to receive requested unsafe blocks from
+    requested_unsafe_block_recv: mpsc::UnboundedReceiver<ExecutionPayload>,
     /// Channel to send unsafe signer updates to block handler
     unsafe_block_signer_sender: Sender<Address>,
+    /// Attributes taken from the pipeline that failed to apply, retried before new ones
+    pending_attributes: Option<PayloadAttributes>,
     /// Networking service
     network_service: Option<Service>,
+    /// Replay of recorded unsafe blocks, used in place of the networking service
+    block_replay: Option<BlockReplay>,
     /// Channel timeout length
     channel_timeout: u64,
+    /// Global config
+    config: Arc<Config>,
+    /// Provider for the local L2 execution RPC
+    l2_provider: Provider<Http>,
+    /// Provider for the L1 RPC
+    l1_provider: Provider<Http>,
+    /// Whether the engine is syncing itself from unsafe blocks before derivation starts
+    el_syncing: bool,
+    /// Whether the pipeline must be reset before advancing again
+    pending_reset: bool,
+    /// Number of consecutive temporary errors, used to back off retries
+    temporary_errors: u32,
+    /// When to advance again after a temporary error, if backing off
+    retry_at: Option<Instant>,
+    /// The most recent L1 block ingested by the pipeline
+    current_l1: BlockInfo,
+    /// The most recent block at the L1 head
+    head_l1: BlockInfo,
+    /// The most recent safe L1 block
+    safe_l1: BlockInfo,
+    /// The most recent finalized L1 block
+    finalized_l1: BlockInfo,
+    /// Channel to publish [SyncStatus] changes to
+    sync_status_sender: Sender<SyncStatus>,
 }
 
 impl Driver<EngineApi> {
@@ -74,75 +132,222 @@ impl Driver<EngineApi> {
             .timeout(Duration::from_secs(5))
             .build()?;
 
-        let http = Http::new_with_client(Url::parse(&config.l2_rpc_url)?, client);
+        let http = Http::new_with_client(Url::parse(&config.l2_rpc_url)?, client.clone());
         let provider = Provider::new(http);
 
-        let head =
-            info::HeadInfoQuery::get_head_info(&info::HeadInfoFetcher::from(&provider), &config)
-                .await;
+        let http = Http::new_with_client(Url::parse(&config.l1_rpc_url)?, client);
+        let l1_provider = Provider::new(http);
+
+        let start = sync_start::find_sync_start(
+            &SyncStartFetcher::new(&l1_provider, &provider, &config),
+            &config,
+        )
+        .await?;
 
-        let finalized_head = head.l2_block_info;
-        let finalized_epoch = head.l1_epoch;
-        let finalized_seq = head.sequence_number;
+        let safe_head = start.safe_head.l2_block_info;
+        let safe_epoch = start.safe_head.l1_epoch;
 
-        tracing::info!("starting from head: {:?}", finalized_head.hash);
+        tracing::info!(
+            "starting from safe head {:?} with unsafe head {:?}",
+            safe_head.hash,
+            start.unsafe_head.l2_block_info.hash
+        );
 
-        let l1_start_block =
-            get_l1_start_block(finalized_epoch.number, config.chain.channel_timeout);
+        let l1_start_block = get_l1_start_block(safe_epoch.number, config.chain.channel_timeout);
 
         let config = Arc::new(config);
-        let chain_watcher =
-            ChainWatcher::new(l1_start_block, finalized_head.number, config.clone())?;
+        let chain_watcher = ChainWatcher::new(l1_start_block, safe_head.number, config.clone())?;
 
-        let state = State::new(finalized_head, finalized_epoch, &provider, config.clone()).await;
+        let state = State::new(safe_head, safe_epoch, &provider, config.clone()).await;
         let state = Arc::new(RwLock::new(state));
 
-        let engine_driver = EngineDriver::new(finalized_head, finalized_epoch, provider, &config)?;
-        let pipeline = Pipeline::new(state.clone(), config.clone(), finalized_seq)?;
+        let mut engine_driver = EngineDriver::new(
+            start.finalized_head.l2_block_info,
+            start.finalized_head.l1_epoch,
+            provider.clone(),
+            &config,
+        )?;
+        engine_driver.reset_to(&start);
+
+        let pipeline = Pipeline::new(
+            state.clone(),
+            config.clone(),
+            start.safe_head.sequence_number,
+        )?;
 
-        let _addr = rpc::run_server(config.clone()).await?;
+        let (sync_status_sender, sync_status_recv) = watch::channel(SyncStatus::default());
+        let (network_handle, network_commands) = NetworkHandle::new();
+        let _addr = rpc::run_server(config.clone(), sync_status_recv, network_handle).await?;
 
         let (unsafe_block_signer_sender, unsafe_block_signer_recv) = watch::channel(
             Address::from_slice(config.chain.system_config.unsafe_block_signer.as_slice()),
         );
 
-        let (block_handler, unsafe_block_recv) =
-            BlockHandler::new(config.chain.l2_chain_id, unsafe_block_signer_recv);
+        let (payload_sync, unsafe_block_request_sender, requested_unsafe_block_recv) =
+            PayloadSync::new(Some(provider.clone()));
+
+        let (network_service, block_replay, unsafe_block_recv) = match &config.p2p.replay_blocks {
+            Some(_) if config.p2p.record_blocks.is_some() => {
+                eyre::bail!("unsafe blocks cannot be recorded while replaying a recording")
+            }
+            Some(path) => {
+                let (replay, unsafe_block_recv) = BlockReplay::new(path, config.p2p.replay_speed)?;
+                (None, Some(replay), unsafe_block_recv)
+            }
+            None => {
+                let (block_handler, mut unsafe_block_recv) =
+                    BlockHandler::new(config.chain.l2_chain_id, unsafe_block_signer_recv);
+
+                if let Some(path) = &config.p2p.record_blocks {
+                    let recorder = BlockRecorder::create(path, RecordFormat::from_path(path))?;
+                    unsafe_block_recv = recorder::record_blocks(recorder, unsafe_block_recv);
+                }
+
+                let service = Service::from_config(&config)?
+                    .add_handler(Box::new(block_handler))
+                    .set_payload_sync(payload_sync)
+                    .set_commands(network_commands);
 
-        let service = Service::new("0.0.0.0:9876".parse()?, config.chain.l2_chain_id)
-            .add_handler(Box::new(block_handler));
+                (Some(service), None, unsafe_block_recv)
+            }
+        };
 
         Ok(Self {
             engine_driver,
             pipeline,
-            unfinalized_blocks: Vec::new(),
-            finalized_l1_block_number: 0,
+            finality: FinalityTracker::default(),
             future_unsafe_blocks: Vec::new(),
+            requested_unsafe_blocks: HashMap::new(),
+            verified_requested_blocks: HashSet::new(),
+            unsafe_block_requests: HashMap::new(),
             state,
             chain_watcher,
             shutdown_recv,
             unsafe_block_recv,
+            unsafe_block_request_sender,
+            requested_unsafe_block_recv,
             unsafe_block_signer_sender,
-            network_service: Some(service),
+            pending_attributes: None,
+            network_service,
+            block_replay,
             channel_timeout: config.chain.channel_timeout,
+            config,
+            l2_provider: provider,
+            l1_provider,
+            el_syncing: false,
+            pending_reset: false,
+            temporary_errors: 0,
+            retry_at: None,
+            current_l1: BlockInfo::default(),
+            head_l1: BlockInfo::default(),
+            safe_l1: BlockInfo::default(),
+            finalized_l1: BlockInfo::default(),
+            sync_status_sender,
         })
     }
 }
 
 impl<E: Engine> Driver<E> {
+    /// Enables execution layer sync: before deriving from L1, the engine syncs itself from its
+    /// own p2p network, using unsafe blocks received via gossip as sync targets. Only used if
+    /// the engine has not synced past genesis yet.
+    pub fn with_execution_layer_sync(mut self, enabled: bool) -> Self {
+        let at_genesis = self.engine_driver.finalized_head == self.config.chain.l2_genesis;
+        if enabled && !at_genesis {
+            tracing::info!("execution client is past genesis, skipping execution layer sync");
+        }
+
+        self.el_syncing = enabled && at_genesis;
+        self
+    }
+
     /// Runs the Driver
     pub async fn start(&mut self) -> Result<()> {
-        self.await_engine_ready().await;
-        self.chain_watcher.start()?;
+        self.await_engine_ready().await?;
+
+        if !self.el_syncing {
+            self.chain_watcher.start()?;
+        }
+
+        let mut step = interval(DRIVER_STEP_INTERVAL);
+        step.set_missed_tick_behavior(MissedTickBehavior::Delay);
 
         loop {
-            self.check_shutdown().await;
+            let res = tokio::select! {
+                changed = self.shutdown_recv.changed() => {
+                    // Nothing can signal a shutdown once the sender is dropped
+                    if changed.is_err() {
+                        self.shutdown().await;
+                    }
+                    self.check_shutdown().await;
+                    Ok(())
+                }
+                Some(update) = self.chain_watcher.recv_from_channel() => {
+                    self.handle_block_update(update)
+                }
+                Some(payload) = self.unsafe_block_recv.recv() => {
+                    self.future_unsafe_blocks.push(payload);
+                    Ok(())
+                }
+                Some(payload) = self.requested_unsafe_block_recv.recv() => {
+                    self.requested_unsafe_blocks.insert(payload.block_hash, payload);
+                    Ok(())
+                }
+                _ = sleep_until(self.retry_at.unwrap_or_else(Instant::now).into()),
+                    if self.retry_at.is_some() =>
+                {
+                    self.retry_at = None;
+                    Ok(())
+                }
+                _ = step.tick() => Ok(()),
+            };
+
+            // Updates are still received while backing off, but the driver does not advance
+            let backing_off = self.retry_at.is_some();
+            let res = match res {
+                Ok(()) if backing_off => Ok(()),
+                Ok(()) if self.pending_reset => self.reset().await,
+                Ok(()) => self.advance().await,
+                Err(err) => Err(err),
+            };
+
+            self.publish_sync_status();
+
+            match res {
+                Ok(()) if backing_off => {}
+                Ok(()) => self.temporary_errors = 0,
+                Err(err) => self.handle_error(err.into())?,
+            }
+        }
+    }
 
-            if let Err(err) = self.advance().await {
-                tracing::error!("fatal error: {:?}", err);
-                self.shutdown().await;
+    /// Recovers from an error returned while advancing the driver. Temporary errors are
+    /// retried after a backoff, reset errors schedule a pipeline reset, and critical errors
+    /// are returned to stop the driver.
+    fn handle_error(&mut self, err: DriverError) -> Result<()> {
+        match err {
+            DriverError::Temporary(err) => {
+                let backoff = TEMPORARY_ERROR_BACKOFF
+                    .saturating_mul(2u32.saturating_pow(self.temporary_errors))
+                    .min(MAX_TEMPORARY_ERROR_BACKOFF);
+                self.temporary_errors = self.temporary_errors.saturating_add(1);
+
+                tracing::warn!("temporary error, retrying in {:?}: {:?}", backoff, err);
+                metrics::DRIVER_RETRIES.inc();
+                self.retry_at = Some(Instant::now() + backoff);
+            }
+            DriverError::Reset(err) => {
+                tracing::warn!("reset error, resetting pipeline: {:?}", err);
+                metrics::DRIVER_RESETS.inc();
+                self.pending_reset = true;
+            }
+            DriverError::Critical(err) => {
+                tracing::error!("critical error: {:?}", err);
+                return Err(err);
             }
         }
+
+        Ok(())
     }
 
     /// Shuts down the driver
@@ -157,21 +362,28 @@ impl<E: Engine> Driver<E> {
         }
     }
 
-    /// Loops until the [EngineApi] is online and receives a response from the engine.
-    async fn await_engine_ready(&self) {
+    /// Loops until the [EngineApi] is online and receives a response from the engine,
+    /// then checks that the engine supports the methods Magi calls.
+    async fn await_engine_ready(&mut self) -> Result<()> {
         while !self.engine_driver.engine_ready().await {
             self.check_shutdown().await;
             sleep(Duration::from_secs(1)).await;
         }
+
+        self.engine_driver.exchange_capabilities().await
     }
 
     /// Attempts to advance the execution node forward using either L1 info or
     /// blocks received on the p2p network.
     async fn advance(&mut self) -> Result<()> {
+        if self.el_syncing {
+            return self.advance_el_sync().await;
+        }
+
         self.advance_safe_head().await?;
         self.advance_unsafe_head().await?;
 
-        self.update_finalized();
+        self.update_finalized().await?;
         self.update_metrics();
         self.try_start_networking()?;
 
@@ -182,23 +394,14 @@ impl<E: Engine> Driver<E> {
     /// L1 data. Errors if the most recent PayloadAttributes from the pipeline
     /// does not successfully advance the node
     async fn advance_safe_head(&mut self) -> Result<()> {
-        self.handle_next_block_update().await?;
         self.update_state_head()?;
 
-        for next_attributes in self.pipeline.by_ref() {
-            let l1_inclusion_block = next_attributes
-                .l1_inclusion_block
-                .ok_or(eyre::eyre!("attributes without inclusion block"))?;
-
-            let seq_number = next_attributes
-                .seq_number
-                .ok_or(eyre::eyre!("attributes without seq number"))?;
-
-            self.engine_driver
-                .handle_attributes(next_attributes)
-                .await
-                .map_err(|e| eyre::eyre!("failed to handle attributes: {}", e))?;
-
+        while let Some(l1_inclusion_block) = self
+            .engine_driver
+            .handle_next_attributes(&mut self.pending_attributes, &mut self.pipeline)
+            .await
+            .wrap_err("failed to handle attributes")?
+        {
             tracing::info!(
                 "safe head updated: {} {:?}",
                 self.engine_driver.safe_head.number,
@@ -210,44 +413,213 @@ impl<E: Engine> Driver<E> {
 
             self.state
                 .write()
-                .map_err(|_| eyre::eyre!("lock poisoned"))?
+                .map_err(|_| DriverError::critical(eyre::eyre!("lock poisoned")))?
                 .update_safe_head(new_safe_head, new_safe_epoch);
 
-            let unfinalized_entry = (
-                new_safe_head,
-                new_safe_epoch,
-                l1_inclusion_block,
-                seq_number,
-            );
-
-            self.unfinalized_blocks.push(unfinalized_entry);
+            self.finality
+                .add_safe_block(new_safe_head, new_safe_epoch, l1_inclusion_block);
         }
 
         Ok(())
     }
 
-    /// Collects unsafe blocks received via p2p gossip and updates the forkchoice with the first available unsafe block.
+    /// Updates the forkchoice with the unsafe blocks received via p2p gossip that extend the unsafe head.
+    /// Blocks missing between the unsafe head and the received blocks are requested from peers.
     async fn advance_unsafe_head(&mut self) -> Result<()> {
-        while let Ok(payload) = self.unsafe_block_recv.try_recv() {
-            self.future_unsafe_blocks.push(payload);
+        let synced_block_num = self.engine_driver.unsafe_head.number;
+        let in_range = |payload: &ExecutionPayload| {
+            let unsafe_block_num = payload.block_number.as_u64();
+            unsafe_block_num > synced_block_num && unsafe_block_num - synced_block_num < 1024
+        };
+
+        self.future_unsafe_blocks.retain(in_range);
+        self.requested_unsafe_blocks
+            .retain(|_, payload| in_range(payload));
+        let future_unsafe_blocks = &self.future_unsafe_blocks;
+        self.verified_requested_blocks
+            .retain(|hash| future_unsafe_blocks.iter().any(|p| p.block_hash == *hash));
+        self.unsafe_block_requests
+            .retain(|number, _| *number > synced_block_num);
+
+        self.verify_requested_unsafe_blocks();
+
+        while let Some(index) = self
+            .future_unsafe_blocks
+            .iter()
+            .position(|p| p.parent_hash == self.engine_driver.unsafe_head.hash)
+        {
+            let payload = &self.future_unsafe_blocks[index];
+            if let Err(err) = self.engine_driver.handle_unsafe_payload(payload).await {
+                // Requested blocks are only as good as the peer that sent them, so drop the
+                // block and request it again. Gossiped blocks are signed, so they are kept
+                // and retried.
+                if !self.verified_requested_blocks.remove(&payload.block_hash) {
+                    return Err(err.wrap_err("failed to apply unsafe block"));
+                }
+
+                tracing::warn!("failed to apply requested unsafe block: {}", err);
+                self.future_unsafe_blocks.swap_remove(index);
+                break;
+            }
         }
 
-        self.future_unsafe_blocks.retain(|payload| {
-            let unsafe_block_num = payload.block_number.as_u64();
-            let synced_block_num = self.engine_driver.unsafe_head.number;
+        self.request_missing_unsafe_blocks();
 
-            unsafe_block_num > synced_block_num && unsafe_block_num - synced_block_num < 1024
-        });
+        Ok(())
+    }
+
+    /// Moves requested unsafe blocks to `future_unsafe_blocks` once they are the parent of the
+    /// lowest block there. That block is trusted, so its parent hash commits to the requested
+    /// block, and the engine checks that the block matches its hash.
+    fn verify_requested_unsafe_blocks(&mut self) {
+        while let Some(parent_hash) = self
+            .future_unsafe_blocks
+            .iter()
+            .min_by_key(|p| p.block_number)
+            .map(|p| p.parent_hash)
+        {
+            let Some(payload) = self.requested_unsafe_blocks.remove(&parent_hash) else {
+                break;
+            };
+
+            self.verified_requested_blocks.insert(payload.block_hash);
+            self.future_unsafe_blocks.push(payload);
+        }
+    }
 
-        let next_unsafe_payload = self
+    /// Requests the unsafe blocks between the unsafe head and the lowest future unsafe block
+    /// from peers, starting from the highest so each response can be verified on arrival.
+    fn request_missing_unsafe_blocks(&mut self) {
+        let head = self.engine_driver.unsafe_head.number;
+        let Some(lowest) = self
             .future_unsafe_blocks
             .iter()
-            .find(|p| p.parent_hash == self.engine_driver.unsafe_head.hash);
+            .map(|p| p.block_number.as_u64())
+            .min()
+        else {
+            return;
+        };
+
+        let now = Instant::now();
+        let start = (head + 1).max(lowest.saturating_sub(MAX_UNSAFE_BLOCK_REQUESTS));
+
+        for number in (start..lowest).rev() {
+            let requested = self
+                .unsafe_block_requests
+                .get(&number)
+                .is_some_and(|at| now.duration_since(*at) < UNSAFE_BLOCK_REQUEST_TIMEOUT);
+
+            if !requested && self.unsafe_block_request_sender.send(number).is_ok() {
+                self.unsafe_block_requests.insert(number, now);
+            }
+        }
+    }
+
+    /// Forwards the newest unsafe block from the p2p network to the engine while it syncs itself,
+    /// and switches to derivation once the engine reports that it has synced.
+    async fn advance_el_sync(&mut self) -> Result<()> {
+        self.start_networking()?;
 
-        if let Some(payload) = next_unsafe_payload {
-            _ = self.engine_driver.handle_unsafe_payload(payload).await;
+        let latest = self
+            .future_unsafe_blocks
+            .drain(..)
+            .max_by_key(|payload| payload.block_number);
+
+        let Some(payload) = latest else {
+            return Ok(());
+        };
+
+        match self.engine_driver.sync_to_payload(&payload).await {
+            Ok(true) => self.finish_el_sync(&payload).await,
+            Ok(false) => {
+                tracing::info!(
+                    "execution layer syncing to {} {:?}",
+                    payload.block_number,
+                    payload.block_hash
+                );
+                Ok(())
+            }
+            Err(err) => {
+                tracing::warn!("failed to forward unsafe block: {}", err);
+                Ok(())
+            }
         }
+    }
+
+    /// Marks the block the engine synced to as finalized, then resets derivation to start
+    /// from it.
+    async fn finish_el_sync(&mut self, payload: &ExecutionPayload) -> Result<()> {
+        let block = self
+            .l2_provider
+            .get_block_with_txs(payload.block_hash)
+            .await?
+            .ok_or(eyre::eyre!("synced block not found"))?;
+
+        let head = HeadInfo::try_from_l2_block(&self.config, block)?;
+        self.engine_driver
+            .finish_sync(head.l2_block_info, head.l1_epoch)
+            .await?;
+
+        tracing::info!(
+            "execution layer sync finished at {} {:?}",
+            head.l2_block_info.number,
+            head.l2_block_info.hash
+        );
 
+        self.reset().await?;
+        self.el_syncing = false;
+        Ok(())
+    }
+
+    /// Finds a new [SyncStart] and rebuilds the [State], the [Pipeline] and the L1 chain
+    /// watcher from its safe head.
+    async fn reset(&mut self) -> Result<()> {
+        let start = sync_start::find_sync_start(
+            &SyncStartFetcher::new(&self.l1_provider, &self.l2_provider, &self.config),
+            &self.config,
+        )
+        .await?;
+        let head = &start.safe_head;
+
+        tracing::info!(
+            "resetting derivation to {} {:?}",
+            head.l2_block_info.number,
+            head.l2_block_info.hash
+        );
+
+        let state = State::new(
+            head.l2_block_info,
+            head.l1_epoch,
+            &self.l2_provider,
+            self.config.clone(),
+        )
+        .await;
+
+        *self
+            .state
+            .write()
+            .map_err(|_| DriverError::critical(eyre::eyre!("lock poisoned")))? = state;
+
+        self.pipeline = Pipeline::new(
+            self.state.clone(),
+            self.config.clone(),
+            head.sequence_number,
+        )?;
+
+        self.finality.clear();
+        self.pending_attributes = None;
+        self.future_unsafe_blocks.clear();
+        self.requested_unsafe_blocks.clear();
+        self.verified_requested_blocks.clear();
+        self.unsafe_block_requests.clear();
+
+        self.engine_driver.reset_to(&start);
+
+        let l1_start_block = get_l1_start_block(head.l1_epoch.number, self.channel_timeout);
+        self.chain_watcher
+            .restart(l1_start_block, head.l2_block_info.number)?;
+
+        self.pending_reset = false;
         Ok(())
     }
 
@@ -256,99 +628,119 @@ impl<E: Engine> Driver<E> {
         let mut state = self
             .state
             .write()
-            .map_err(|_| eyre::eyre!("lock poisoned"))?;
+            .map_err(|_| DriverError::critical(eyre::eyre!("lock poisoned")))?;
 
         state.update_safe_head(self.engine_driver.safe_head, self.engine_driver.safe_epoch);
 
         Ok(())
     }
 
-    /// Ingests the next update from the block update channel
-    async fn handle_next_block_update(&mut self) -> Result<()> {
-        let next = self.chain_watcher.try_recv_from_channel();
-
-        if let Ok(update) = next {
-            match update {
-                BlockUpdate::NewBlock(l1_info) => {
-                    let num = l1_info.block_info.number;
-
-                    self.unsafe_block_signer_sender.send(Address::from_slice(
-                        l1_info.system_config.unsafe_block_signer.as_slice(),
-                    ))?;
-
-                    self.pipeline.push_batcher_transactions(
+    /// Ingests an update from the L1 chain watcher
+    fn handle_block_update(&mut self, update: BlockUpdate) -> Result<()> {
+        match update {
+            BlockUpdate::NewBlock(l1_info) => {
+                let num = l1_info.block_info.number;
+                self.current_l1 = BlockInfo {
+                    hash: l1_info.block_info.hash,
+                    number: num,
+                    parent_hash: l1_info.block_info.parent_hash,
+                    timestamp: l1_info.block_info.timestamp,
+                };
+
+                // The block has been taken from the chain watcher, so derivation must restart
+                // if its batcher transactions are lost
+                self.pipeline
+                    .push_batcher_transactions(
                         // cloning `bytes::Bytes` is cheap
                         l1_info.batcher_transactions.clone(),
                         num,
-                    )?;
-
-                    self.state
-                        .write()
-                        .map_err(|_| eyre::eyre!("lock poisoned"))?
-                        .update_l1_info(*l1_info);
-                }
-                BlockUpdate::Reorg => {
-                    tracing::warn!("reorg detected, purging pipeline");
-
-                    self.unfinalized_blocks.clear();
+                    )
+                    .map_err(DriverError::reset)?;
 
-                    let l1_start_block = get_l1_start_block(
-                        self.engine_driver.finalized_epoch.number,
-                        self.channel_timeout,
-                    );
-
-                    self.chain_watcher
-                        .restart(l1_start_block, self.engine_driver.finalized_head.number)?;
-
-                    self.state
-                        .write()
-                        .map_err(|_| eyre::eyre!("lock poisoned"))?
-                        .purge(
-                            self.engine_driver.finalized_head,
-                            self.engine_driver.finalized_epoch,
-                        );
+                // There is no block handler listening while replaying a recording
+                self.unsafe_block_signer_sender
+                    .send_replace(Address::from_slice(
+                        l1_info.system_config.unsafe_block_signer.as_slice(),
+                    ));
 
-                    self.pipeline.purge()?;
-                    self.engine_driver.reorg();
-                }
-                BlockUpdate::FinalityUpdate(num) => {
-                    self.finalized_l1_block_number = num;
-                }
+                self.state
+                    .write()
+                    .map_err(|_| DriverError::critical(eyre::eyre!("lock poisoned")))?
+                    .update_l1_info(*l1_info);
+            }
+            BlockUpdate::Reorg => {
+                // The finalized head may be in the middle of an epoch, so restart derivation
+                // from a sync start instead of purging the pipeline back to it
+                tracing::warn!("reorg detected, resetting pipeline");
+                metrics::DRIVER_RESETS.inc();
+                self.pending_reset = true;
+            }
+            BlockUpdate::FinalityUpdate(block) => {
+                self.finality.update_l1_finalized(block.number);
+                self.finalized_l1 = block;
+            }
+            BlockUpdate::HeadUpdate(head) => {
+                self.head_l1 = head;
+            }
+            BlockUpdate::SafeUpdate(block) => {
+                self.safe_l1 = block;
             }
         }
 
         Ok(())
     }
 
-    /// Updates the current finalized L2 block in the [EngineDriver] based on their inclusion in finalized L1 blocks
-    fn update_finalized(&mut self) {
-        let new_finalized = self
-            .unfinalized_blocks
-            .iter()
-            .filter(|(_, _, inclusion, seq)| {
-                *inclusion <= self.finalized_l1_block_number && *seq == 0
-            })
-            .last();
-
-        if let Some((head, epoch, _, _)) = new_finalized {
-            self.engine_driver.update_finalized(*head, *epoch);
+    /// Finalizes the highest safe L2 block derived entirely from finalized L1 data
+    async fn update_finalized(&mut self) -> Result<()> {
+        if let Some((head, epoch)) = self.finality.pop_finalized() {
+            self.engine_driver.update_finalized(head, epoch).await?;
         }
 
-        self.unfinalized_blocks
-            .retain(|(_, _, inclusion, _)| *inclusion > self.finalized_l1_block_number);
+        Ok(())
     }
 
-    /// Begins p2p networking if fully synced with no unfinalized blocks
+    /// Begins p2p networking once derivation has caught up with the L1 head
     fn try_start_networking(&mut self) -> Result<()> {
         if self.synced() {
-            if let Some(service) = self.network_service.take() {
-                service.start()?;
-            }
+            self.start_networking()?;
+        }
+
+        Ok(())
+    }
+
+    /// Begins p2p networking if it has not started yet
+    fn start_networking(&mut self) -> Result<()> {
+        if let Some(service) = self.network_service.take() {
+            service.start()?;
+        }
+
+        if let Some(replay) = self.block_replay.take() {
+            replay.start();
         }
 
         Ok(())
     }
 
+    /// Publishes the current [SyncStatus] if it changed
+    fn publish_sync_status(&self) {
+        let status = SyncStatus {
+            current_l1: self.current_l1,
+            head_l1: self.head_l1,
+            safe_l1: self.safe_l1,
+            finalized_l1: self.finalized_l1,
+            unsafe_l2: self.engine_driver.unsafe_head,
+            safe_l2: self.engine_driver.safe_head,
+            finalized_l2: self.engine_driver.finalized_head,
+            engine_syncing: self.el_syncing,
+        };
+
+        self.sync_status_sender.send_if_modified(|current| {
+            let modified = *current != status;
+            *current = status;
+            modified
+        });
+    }
+
     /// Updates Prometheus metrics
     fn update_metrics(&self) {
         metrics::FINALIZED_HEAD.set(self.engine_driver.finalized_head.number as i64);
@@ -356,12 +748,28 @@ impl<E: Engine> Driver<E> {
         metrics::SYNCED.set(self.synced() as i64);
     }
 
-    /// True if there are no unfinalized blocks
+    /// True once the pipeline has derived from every L1 block up to the confirmed L1 head
     fn synced(&self) -> bool {
-        !self.unfinalized_blocks.is_empty()
+        !self.head_l1.hash.is_zero()
+            && self.current_l1.number + self.config.l1_confs >= self.head_l1.number
     }
 }
 
+/// How often the driver steps when no L1 updates or unsafe blocks arrive
+const DRIVER_STEP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The maximum number of missing unsafe blocks requested from peers at once
+const MAX_UNSAFE_BLOCK_REQUESTS: u64 = 64;
+
+/// How long to wait for a requested unsafe block before requesting it again
+const UNSAFE_BLOCK_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The delay before retrying after the first temporary error. Doubles on each consecutive error.
+const TEMPORARY_ERROR_BACKOFF: Duration = Duration::from_millis(500);
+
+/// The maximum delay before retrying after a temporary error
+const MAX_TEMPORARY_ERROR_BACKOFF: Duration = Duration::from_secs(30);
+
 /// Retrieves the L1 start block number.
 /// If an overflow occurs during subtraction, the function returns the genesis block #0.
 fn get_l1_start_block(epoch_number: u64, channel_timeout: u64) -> u64 {
@@ -392,6 +800,7 @@ mod tests {
             let cli_config = CliConfig {
                 l1_rpc_url: Some(rpc.to_owned()),
                 l1_beacon_url: None,
+                l1_beacon_fallback_urls: None,
                 l2_rpc_url: Some(l2_rpc.to_owned()),
                 l2_engine_url: None,
                 jwt_secret: Some(
@@ -401,6 +810,9 @@ mod tests {
                 rpc_port: None,
                 rpc_addr: None,
                 devnet: false,
+                data_dir: None,
+                l1_confs: None,
+                p2p: Default::default(),
             };
             let config = Config::new(&config_path, cli_config, ChainConfig::optimism_sepolia());
             let (_shutdown_sender, shutdown_recv) = channel(false);
@@ -0,0 +1,327 @@
+use ethers::{
+    providers::{JsonRpcClient, Middleware, Provider},
+    types::{BlockId, BlockNumber, H256},
+};
+use eyre::Result;
+
+use crate::config::Config;
+
+use super::{info, HeadInfo};
+
+/// The L2 heads the driver resumes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncStart {
+    /// The highest L2 block whose L1 origin has not been reorged out. The engine keeps
+    /// blocks up to here, so they do not need to be re-derived or re-gossiped.
+    pub unsafe_head: HeadInfo,
+    /// The L2 block derivation restarts from
+    pub safe_head: HeadInfo,
+    /// The engine's finalized L2 block
+    pub finalized_head: HeadInfo,
+}
+
+/// Fetches the L2 and L1 blocks needed to find the [SyncStart]
+#[async_trait::async_trait]
+pub trait SyncStartProvider {
+    /// Returns the [HeadInfo] of an L2 block, or `None` if the engine does not have it
+    async fn l2_head(&self, block_id: BlockId) -> Result<Option<HeadInfo>>;
+
+    /// Returns the hash of the canonical L1 block at the given number, or `None` if the
+    /// L1 chain has not reached it yet
+    async fn l1_block_hash(&self, number: u64) -> Result<Option<H256>>;
+
+    /// Returns the [HeadInfo] of the engine's finalized L2 block, falling back to genesis
+    async fn finalized_head(&self, config: &Config) -> HeadInfo;
+}
+
+/// Fetches [SyncStart] blocks from an L1 and an L2 [Provider]
+pub struct SyncStartFetcher<'a, L1: JsonRpcClient, L2: JsonRpcClient> {
+    /// L1 provider
+    l1: &'a Provider<L1>,
+    /// L2 provider
+    l2: &'a Provider<L2>,
+    /// Global config
+    config: &'a Config,
+}
+
+impl<'a, L1: JsonRpcClient, L2: JsonRpcClient> SyncStartFetcher<'a, L1, L2> {
+    /// Creates a new [SyncStartFetcher]
+    pub fn new(l1: &'a Provider<L1>, l2: &'a Provider<L2>, config: &'a Config) -> Self {
+        Self { l1, l2, config }
+    }
+}
+
+#[async_trait::async_trait]
+impl<'a, L1: JsonRpcClient, L2: JsonRpcClient> SyncStartProvider for SyncStartFetcher<'a, L1, L2> {
+    async fn l2_head(&self, block_id: BlockId) -> Result<Option<HeadInfo>> {
+        let Some(block) = self.l2.get_block_with_txs(block_id).await? else {
+            return Ok(None);
+        };
+
+        HeadInfo::try_from_l2_block(self.config, block).map(Some)
+    }
+
+    async fn l1_block_hash(&self, number: u64) -> Result<Option<H256>> {
+        let block = self.l1.get_block(number).await?;
+        Ok(block.and_then(|b| b.hash))
+    }
+
+    async fn finalized_head(&self, config: &Config) -> HeadInfo {
+        info::HeadInfoQuery::get_head_info(&info::HeadInfoFetcher::from(self.l2), config).await
+    }
+}
+
+/// Finds the L2 heads to resume from, following the op-node sync start search.
+///
+/// Walks back from the engine's unsafe head towards its finalized head. The unsafe head is
+/// the highest block whose L1 origin is still canonical (or not yet known to L1). Derivation
+/// restarts from the first block at or below the engine's safe head that starts an epoch,
+/// and whose L1 origin is more than a sequencing window behind the L1 origin of the unsafe
+/// head. Any batch for a later block must then have been included after that origin, so it
+/// is re-derived.
+pub async fn find_sync_start<P: SyncStartProvider + Sync>(
+    provider: &P,
+    config: &Config,
+) -> Result<SyncStart> {
+    let finalized_head = provider.finalized_head(config).await;
+
+    let latest = provider
+        .l2_head(BlockId::Number(BlockNumber::Latest))
+        .await?;
+    let safe_number = provider
+        .l2_head(BlockId::Number(BlockNumber::Safe))
+        .await?
+        .map(|head| head.l2_block_info.number)
+        .unwrap_or(finalized_head.l2_block_info.number);
+
+    let mut current = match latest {
+        Some(head) if head.l2_block_info.number > finalized_head.l2_block_info.number => head,
+        _ => {
+            return Ok(SyncStart {
+                unsafe_head: finalized_head.clone(),
+                safe_head: finalized_head.clone(),
+                finalized_head,
+            })
+        }
+    };
+
+    let mut unsafe_head: Option<HeadInfo> = None;
+    let mut highest_canonical: Option<HeadInfo> = None;
+    let mut checked_origin: Option<(u64, Option<H256>)> = None;
+
+    loop {
+        if current.l2_block_info.number <= finalized_head.l2_block_info.number {
+            if current.l2_block_info.hash != finalized_head.l2_block_info.hash {
+                eyre::bail!(
+                    "unsafe chain does not include the finalized block {:?}",
+                    finalized_head.l2_block_info.hash
+                );
+            }
+
+            return Ok(SyncStart {
+                unsafe_head: unsafe_head.unwrap_or_else(|| finalized_head.clone()),
+                safe_head: finalized_head.clone(),
+                finalized_head,
+            });
+        }
+
+        let origin = current.l1_epoch;
+        let l1_hash = match checked_origin {
+            Some((number, hash)) if number == origin.number => hash,
+            _ => {
+                let hash = provider.l1_block_hash(origin.number).await?;
+                checked_origin = Some((origin.number, hash));
+                hash
+            }
+        };
+
+        match l1_hash {
+            Some(hash) if hash == origin.hash => {
+                unsafe_head.get_or_insert_with(|| current.clone());
+                highest_canonical.get_or_insert_with(|| current.clone());
+            }
+            Some(_) => {
+                tracing::warn!(
+                    "L1 origin of L2 block {} was reorged out",
+                    current.l2_block_info.number
+                );
+                unsafe_head = None;
+                highest_canonical = None;
+            }
+            None => {
+                unsafe_head.get_or_insert_with(|| current.clone());
+            }
+        }
+
+        if let Some(highest) = &highest_canonical {
+            let window_end = origin.number + config.chain.seq_window_size;
+            if current.l2_block_info.number <= safe_number
+                && current.sequence_number == 0
+                && window_end < highest.l1_epoch.number
+            {
+                return Ok(SyncStart {
+                    unsafe_head: unsafe_head.unwrap_or_else(|| current.clone()),
+                    safe_head: current,
+                    finalized_head,
+                });
+            }
+        }
+
+        current = provider
+            .l2_head(BlockId::Hash(current.l2_block_info.parent_hash))
+            .await?
+            .ok_or(eyre::eyre!(
+                "missing parent of L2 block {}",
+                current.l2_block_info.number
+            ))?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use ethers::types::{BlockId, BlockNumber, H256};
+    use eyre::Result;
+
+    use crate::{
+        common::{BlockInfo, Epoch},
+        config::{ChainConfig, Config},
+        driver::HeadInfo,
+    };
+
+    use super::{find_sync_start, SyncStartProvider};
+
+    /// An L2 chain with one block per L1 block, and a fixed view of L1
+    struct MockChain {
+        l2_blocks: Vec<HeadInfo>,
+        l1_hashes: HashMap<u64, H256>,
+        safe: u64,
+        finalized: u64,
+    }
+
+    fn l1_hash(number: u64) -> H256 {
+        H256::from_low_u64_be(1_000_000 + number)
+    }
+
+    fn l2_hash(number: u64) -> H256 {
+        H256::from_low_u64_be(number + 1)
+    }
+
+    impl MockChain {
+        /// Builds `len` L2 blocks, with two blocks per epoch, and an L1 chain up to `l1_head`
+        fn new(len: u64, l1_head: u64) -> Self {
+            let l2_blocks = (0..len)
+                .map(|number| HeadInfo {
+                    l2_block_info: BlockInfo {
+                        hash: l2_hash(number),
+                        number,
+                        parent_hash: if number == 0 {
+                            H256::zero()
+                        } else {
+                            l2_hash(number - 1)
+                        },
+                        timestamp: number * 2,
+                    },
+                    l1_epoch: Epoch {
+                        number: number / 2,
+                        hash: l1_hash(number / 2),
+                        timestamp: number * 2,
+                    },
+                    sequence_number: number % 2,
+                })
+                .collect();
+
+            Self {
+                l2_blocks,
+                l1_hashes: (0..=l1_head).map(|n| (n, l1_hash(n))).collect(),
+                safe: len - 1,
+                finalized: 0,
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl SyncStartProvider for MockChain {
+        async fn l2_head(&self, block_id: BlockId) -> Result<Option<HeadInfo>> {
+            let head = match block_id {
+                BlockId::Number(BlockNumber::Latest) => self.l2_blocks.last(),
+                BlockId::Number(BlockNumber::Safe) => self.l2_blocks.get(self.safe as usize),
+                BlockId::Hash(hash) => self.l2_blocks.iter().find(|h| h.l2_block_info.hash == hash),
+                _ => None,
+            };
+
+            Ok(head.cloned())
+        }
+
+        async fn l1_block_hash(&self, number: u64) -> Result<Option<H256>> {
+            Ok(self.l1_hashes.get(&number).copied())
+        }
+
+        async fn finalized_head(&self, _config: &Config) -> HeadInfo {
+            self.l2_blocks[self.finalized as usize].clone()
+        }
+    }
+
+    fn config(seq_window_size: u64) -> Config {
+        let mut chain = ChainConfig::optimism();
+        chain.seq_window_size = seq_window_size;
+
+        Config {
+            l1_rpc_url: Default::default(),
+            l1_beacon_url: Default::default(),
+            l1_beacon_fallback_urls: Default::default(),
+            l2_rpc_url: Default::default(),
+            l2_engine_url: Default::default(),
+            chain,
+            jwt_secret: Default::default(),
+            checkpoint_sync_url: Default::default(),
+            rpc_port: Default::default(),
+            rpc_addr: Default::default(),
+            devnet: false,
+            data_dir: None,
+            l1_confs: 0,
+            p2p: Default::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sync_start_keeps_unsafe_blocks() {
+        // 40 L2 blocks with origins 0..=19
+        let chain = MockChain::new(40, 19);
+        let start = find_sync_start(&chain, &config(5)).await.unwrap();
+
+        assert_eq!(start.unsafe_head.l2_block_info.number, 39);
+        assert_eq!(start.finalized_head.l2_block_info.number, 0);
+
+        // The highest epoch start whose origin is more than 5 L1 blocks behind origin 19
+        assert_eq!(start.safe_head.l1_epoch.number, 13);
+        assert_eq!(start.safe_head.sequence_number, 0);
+        assert_eq!(start.safe_head.l2_block_info.number, 26);
+    }
+
+    #[tokio::test]
+    async fn test_sync_start_drops_reorged_blocks() {
+        // Origins 16 and up were reorged out on L1
+        let mut chain = MockChain::new(40, 19);
+        for number in 16..=19 {
+            chain.l1_hashes.insert(number, H256::random());
+        }
+        chain.safe = 20;
+
+        let start = find_sync_start(&chain, &config(5)).await.unwrap();
+        assert_eq!(start.unsafe_head.l2_block_info.number, 31);
+        assert_eq!(start.safe_head.l2_block_info.number, 18);
+    }
+
+    #[tokio::test]
+    async fn test_sync_start_stops_at_finalized() {
+        let mut chain = MockChain::new(40, 25);
+        chain.finalized = 30;
+
+        let start = find_sync_start(&chain, &config(100)).await.unwrap();
+        assert_eq!(start.unsafe_head.l2_block_info.number, 39);
+        assert_eq!(start.safe_head, chain.l2_blocks[30]);
+        assert_eq!(start.finalized_head, chain.l2_blocks[30]);
+    }
+}
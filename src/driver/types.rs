@@ -18,6 +18,28 @@ pub struct HeadInfo {
     pub sequence_number: u64,
 }
 
+/// The sync status of the node, published by the [Driver](super::Driver) whenever one of
+/// its heads changes
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SyncStatus {
+    /// The most recent L1 block ingested by the derivation pipeline
+    pub current_l1: BlockInfo,
+    /// The most recent block at the L1 head
+    pub head_l1: BlockInfo,
+    /// The most recent safe L1 block
+    pub safe_l1: BlockInfo,
+    /// The most recent finalized L1 block
+    pub finalized_l1: BlockInfo,
+    /// The unsafe L2 head
+    pub unsafe_l2: BlockInfo,
+    /// The safe L2 head
+    pub safe_l2: BlockInfo,
+    /// The finalized L2 head
+    pub finalized_l2: BlockInfo,
+    /// True while the execution client is syncing itself, before derivation starts
+    pub engine_syncing: bool,
+}
+
 impl HeadInfo {
     /// Returns the head info from the given L2 block and the system config.
     /// The config is used to check whether the block is subject to the Ecotone hardfork
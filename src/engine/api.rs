@@ -1,11 +1,8 @@
 use std::collections::HashMap;
-use std::time::{Duration, SystemTime};
+use std::time::Duration;
 
 use again::RetryPolicy;
 use eyre::Result;
-use futures::prelude::*;
-use futures_timer::TryFutureExt;
-use reqwest::{header, Client};
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
@@ -13,9 +10,12 @@ use serde_json::Value;
 use crate::engine::DEFAULT_AUTH_PORT;
 
 use super::{
-    Engine, ExecutionPayload, ForkChoiceUpdate, ForkchoiceState, JwtSecret, PayloadAttributes,
-    PayloadId, PayloadStatus, ENGINE_FORKCHOICE_UPDATED_V2, ENGINE_GET_PAYLOAD_V2,
-    ENGINE_NEW_PAYLOAD_V2,
+    Engine, EngineError, EngineTransport, ExecutionPayload, ForkChoiceUpdate, ForkchoiceState,
+    JwtSecret, PayloadAttributes, PayloadId, PayloadStatus, ENGINE_EXCHANGE_CAPABILITIES,
+    ENGINE_EXCHANGE_CAPABILITIES_TIMEOUT, ENGINE_FORKCHOICE_UPDATED_TIMEOUT,
+    ENGINE_FORKCHOICE_UPDATED_V2, ENGINE_GET_PAYLOAD_TIMEOUT, ENGINE_GET_PAYLOAD_V2,
+    ENGINE_MAX_RETRIES, ENGINE_NEW_PAYLOAD_TIMEOUT, ENGINE_NEW_PAYLOAD_V2, ENGINE_RETRY_BACKOFF,
+    IPC_SCHEME,
 };
 
 use super::{JSONRPC_VERSION, STATIC_ID};
@@ -27,15 +27,24 @@ pub struct EngineApi {
     pub base_url: String,
     /// The url port
     pub port: u16,
-    /// HTTP Client
-    pub client: Option<Client>,
-    /// A JWT secret used to authenticate with the engine api
-    secret: JwtSecret,
+    /// Transport used to send requests, chosen from the url scheme
+    transport: EngineTransport,
 }
 
 impl EngineApi {
     /// Creates a new [`EngineApi`] with a base url and secret.
+    ///
+    /// An `ipc://<path>` url connects to the engine over a Unix domain socket, in which case
+    /// the secret is not used. Any other url is treated as an HTTP(S) endpoint.
     pub fn new(base_url: &str, secret_str: &str) -> Self {
+        if let Some(path) = base_url.strip_prefix(IPC_SCHEME) {
+            return Self {
+                base_url: base_url.to_string(),
+                port: 0,
+                transport: EngineTransport::ipc(path),
+            };
+        }
+
         let secret = JwtSecret::from_hex(secret_str).unwrap();
 
         // Gracefully parse the port from the base url
@@ -49,22 +58,10 @@ impl EngineApi {
             parts.join(":")
         };
 
-        let client = reqwest::Client::builder()
-            .default_headers({
-                header::HeaderMap::from_iter([(
-                    header::CONTENT_TYPE,
-                    header::HeaderValue::from_static("application/json"),
-                )])
-            })
-            .timeout(Duration::from_secs(5))
-            .build()
-            .expect("reqwest::Client could not be built, TLS backend could not be initialized");
-
         Self {
+            transport: EngineTransport::http(&base_url, secret),
             base_url,
             port,
-            client: Some(client),
-            secret,
         }
     }
 
@@ -77,8 +74,12 @@ impl EngineApi {
     }
 
     /// Returns if the provided secret matches the secret used to authenticate with the engine api.
+    /// Always false for IPC, which is not authenticated.
     pub fn check_secret(&self, secret: &str) -> bool {
-        self.secret.equal(secret)
+        match &self.transport {
+            EngineTransport::Http { secret: s, .. } => s.equal(secret),
+            EngineTransport::Ipc { .. } => false,
+        }
     }
 
     /// Creates an engine api from environment variables
@@ -111,8 +112,18 @@ impl EngineApi {
         map
     }
 
-    /// Helper to construct a post request through the client
-    async fn post<P>(&self, method: &str, params: Vec<Value>) -> Result<P>
+    /// Helper to construct a post request through the client.
+    ///
+    /// Each attempt is bounded by `timeout`. Requests that fail before reaching the engine,
+    /// or time out, are resent up to `retries` times with exponential backoff, so only
+    /// idempotent requests should be retried. JSON-RPC errors are returned as an [EngineError].
+    async fn post<P>(
+        &self,
+        method: &str,
+        params: Vec<Value>,
+        timeout: Duration,
+        retries: usize,
+    ) -> Result<P>
     where
         P: DeserializeOwned,
     {
@@ -124,49 +135,24 @@ impl EngineApi {
         tracing::trace!("Sending request to url: {:?}", self.base_url);
         tracing::trace!("Sending request: {:?}", serde_json::to_string(&body));
 
-        // Send the client request
-        let client = self
-            .client
-            .as_ref()
-            .ok_or(eyre::eyre!("Driver missing http client"))?;
-
-        // Clone the secret so we can use it in the retry policy.
-        let secret_clone = self.secret.clone();
-
-        let policy = RetryPolicy::fixed(Duration::ZERO).with_max_retries(5);
+        let policy = RetryPolicy::exponential(ENGINE_RETRY_BACKOFF).with_max_retries(retries);
 
         // Send the request
         let res = policy
-            .retry(|| async {
-                // Construct the JWT Authorization Token
-                let claims = secret_clone.generate_claims(Some(SystemTime::now()));
-                let jwt = secret_clone
-                    .encode(&claims)
-                    .map_err(|_| eyre::eyre!("EngineApi failed to encode jwt with claims!"))?;
-
-                // Send the request
-                client
-                    .post(&self.base_url)
-                    .header(header::AUTHORIZATION, format!("Bearer {}", jwt))
-                    .json(&body)
-                    .send()
-                    .map_err(|e| eyre::eyre!(e))
-                    .timeout(Duration::from_secs(2))
-                    .await?
-                    .json::<EngineApiResponse<P>>()
-                    .map_err(|e| eyre::eyre!(e))
-                    .timeout(Duration::from_secs(2))
-                    .map_err(|e| eyre::eyre!(e))
-                    .await
-            })
+            .retry_if(
+                || self.transport.send(&body, timeout),
+                |err: &EngineError| err.is_transport(),
+            )
             .await?;
+        let res = serde_json::from_value::<EngineApiResponse<P>>(res)?;
 
         if let Some(res) = res.result {
             return Ok(res);
         }
 
         if let Some(err) = res.error {
-            eyre::bail!("Engine API POST error: {}", err.message);
+            tracing::debug!("{} failed: {} {}", method, err.code, err.message);
+            return Err(EngineError::from_rpc(err.code, err.message).into());
         }
 
         // This scenario shouldn't occur as the response should always have either data or an error
@@ -175,7 +161,14 @@ impl EngineApi {
 
     /// Calls the engine to verify it's available to receive requests
     pub async fn is_available(&self) -> bool {
-        self.post::<Value>("eth_chainId", vec![]).await.is_ok()
+        self.post::<Value>(
+            "eth_chainId",
+            vec![],
+            ENGINE_EXCHANGE_CAPABILITIES_TIMEOUT,
+            0,
+        )
+        .await
+        .is_ok()
     }
 }
 
@@ -206,52 +199,95 @@ pub struct EngineApiErrorPayload {
 
 #[async_trait::async_trait]
 impl Engine for EngineApi {
-    /// Sends an `engine_forkchoiceUpdatedV2` (V3 post Ecotone) message to the engine.
+    /// Sends an `engine_forkchoiceUpdatedV2` message to the engine.
+    /// Only retried without attributes, since each request with attributes starts a new build.
     async fn forkchoice_updated(
         &self,
         forkchoice_state: ForkchoiceState,
         payload_attributes: Option<PayloadAttributes>,
     ) -> Result<ForkChoiceUpdate> {
+        let retries = match payload_attributes {
+            Some(_) => 0,
+            None => ENGINE_MAX_RETRIES,
+        };
         let payload_attributes_param = match payload_attributes {
             Some(payload_attributes) => serde_json::to_value(payload_attributes)?,
             None => Value::Null,
         };
         let forkchoice_state_param = serde_json::to_value(forkchoice_state)?;
         let params = vec![forkchoice_state_param, payload_attributes_param];
-        let res = self.post(ENGINE_FORKCHOICE_UPDATED_V2, params).await?;
+        let res = self
+            .post(
+                ENGINE_FORKCHOICE_UPDATED_V2,
+                params,
+                ENGINE_FORKCHOICE_UPDATED_TIMEOUT,
+                retries,
+            )
+            .await?;
         Ok(res)
     }
 
-    /// Sends an `engine_newPayloadV2` (V3 post Ecotone) message to the engine.
+    /// Sends an `engine_newPayloadV2` message to the engine.
     async fn new_payload(&self, execution_payload: ExecutionPayload) -> Result<PayloadStatus> {
         let params = vec![serde_json::to_value(execution_payload)?];
-        let res = self.post(ENGINE_NEW_PAYLOAD_V2, params).await?;
+        let res = self
+            .post(
+                ENGINE_NEW_PAYLOAD_V2,
+                params,
+                ENGINE_NEW_PAYLOAD_TIMEOUT,
+                ENGINE_MAX_RETRIES,
+            )
+            .await?;
         Ok(res)
     }
 
-    /// Sends an `engine_getPayloadV2` (V3 post Ecotone) message to the engine.
+    /// Sends an `engine_getPayloadV2` message to the engine.
     async fn get_payload(&self, payload_id: PayloadId) -> Result<ExecutionPayload> {
         let encoded = format!("{:x}", payload_id);
         let padded = format!("0x{:0>16}", encoded);
         let params = vec![Value::String(padded)];
         let res = self
-            .post::<GetPayloadResponse>(ENGINE_GET_PAYLOAD_V2, params)
+            .post::<GetPayloadResponse>(
+                ENGINE_GET_PAYLOAD_V2,
+                params,
+                ENGINE_GET_PAYLOAD_TIMEOUT,
+                ENGINE_MAX_RETRIES,
+            )
             .await?;
         Ok(res.execution_payload)
     }
+
+    /// Sends an `engine_exchangeCapabilities` message to the engine.
+    async fn exchange_capabilities(&self, capabilities: Vec<String>) -> Result<Vec<String>> {
+        let params = vec![serde_json::to_value(capabilities)?];
+        let res = self
+            .post(
+                ENGINE_EXCHANGE_CAPABILITIES,
+                params,
+                ENGINE_EXCHANGE_CAPABILITIES_TIMEOUT,
+                ENGINE_MAX_RETRIES,
+            )
+            .await?;
+        Ok(res)
+    }
 }
 
 /// Wrapper around an [ExecutionPayload]
 #[derive(Debug, Serialize, Deserialize, Default)]
 #[serde(rename_all = "camelCase")]
 struct GetPayloadResponse {
-    /// The execution payload returned by the engine via `engine_getPayloadV2` (`engine_getPayloadV3` post Ecotone)
+    /// The execution payload returned by the engine via `engine_getPayloadV2`
     execution_payload: ExecutionPayload,
 }
 
 #[cfg(test)]
 mod tests {
-    use std::time::SystemTime;
+    use std::{path::Path, time::SystemTime};
+
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::UnixListener,
+    };
 
     // use std::str::FromStr;
     // use ethers_core::types::H256;
@@ -304,4 +340,138 @@ mod tests {
         // server.stop().unwrap();
         // server.stopped().await;
     }
+
+    /// Serves engine api requests on a Unix socket, answering each method with a canned
+    /// result. Each response is split across two writes to exercise partial reads.
+    fn serve_ipc(path: &Path, results: HashMap<&'static str, Result<Value, i64>>) {
+        let listener = UnixListener::bind(path).unwrap();
+
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let results = results.clone();
+                tokio::spawn(async move {
+                    let mut buf = Vec::new();
+                    let mut chunk = [0u8; 1024];
+                    while let Ok(read) = stream.read(&mut chunk).await {
+                        if read == 0 {
+                            return;
+                        }
+                        buf.extend_from_slice(&chunk[..read]);
+
+                        let Ok(req) = serde_json::from_slice::<Value>(&buf) else {
+                            continue;
+                        };
+                        buf.clear();
+
+                        let method = req["method"].as_str().unwrap_or_default();
+                        let res = match results.get(method) {
+                            Some(Ok(result)) => serde_json::json!({
+                                "jsonrpc": "2.0", "id": req["id"], "result": result
+                            }),
+                            Some(Err(code)) => serde_json::json!({
+                                "jsonrpc": "2.0",
+                                "id": req["id"],
+                                "error": { "code": code, "message": "engine error" }
+                            }),
+                            None => serde_json::json!({
+                                "jsonrpc": "2.0",
+                                "id": req["id"],
+                                "error": { "code": -32601, "message": "method not found" }
+                            }),
+                        };
+
+                        let res = format!("{}\n", res).into_bytes();
+                        let (head, tail) = res.split_at(res.len() / 2);
+                        stream.write_all(head).await.unwrap();
+                        stream.flush().await.unwrap();
+                        stream.write_all(tail).await.unwrap();
+                    }
+                });
+            }
+        });
+    }
+
+    #[tokio::test]
+    async fn test_engine_api_over_ipc() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("engine.ipc");
+
+        serve_ipc(
+            &path,
+            HashMap::from([
+                ("eth_chainId", Ok(Value::String("0xa".to_string()))),
+                (
+                    ENGINE_FORKCHOICE_UPDATED_V2,
+                    Ok(serde_json::json!({
+                        "payloadStatus": { "status": "VALID", "latestValidHash": null },
+                        "payloadId": "0x0000000000000001"
+                    })),
+                ),
+            ]),
+        );
+
+        // The secret is not used over IPC
+        let engine_api = EngineApi::new(&format!("ipc://{}", path.display()), "");
+        assert!(engine_api.is_available().await);
+        assert!(!engine_api.check_secret(SECRET));
+
+        // Requests reuse the connection
+        for _ in 0..2 {
+            let update = engine_api
+                .forkchoice_updated(ForkchoiceState::from_single_head(Default::default()), None)
+                .await
+                .unwrap();
+            assert_eq!(update.payload_status.status, crate::engine::Status::Valid);
+            assert_eq!(update.payload_id, Some(PayloadId::from(1)));
+        }
+
+        let err = engine_api
+            .get_payload(PayloadId::from(1))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("method not found"));
+    }
+
+    #[tokio::test]
+    async fn test_engine_api_typed_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("engine.ipc");
+
+        serve_ipc(
+            &path,
+            HashMap::from([
+                (ENGINE_GET_PAYLOAD_V2, Err(-38001)),
+                (ENGINE_FORKCHOICE_UPDATED_V2, Err(-38002)),
+            ]),
+        );
+
+        let engine_api = EngineApi::new(&format!("ipc://{}", path.display()), "");
+
+        let err = engine_api
+            .get_payload(PayloadId::from(1))
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<EngineError>(),
+            Some(&EngineError::UnknownPayload)
+        );
+
+        let err = engine_api
+            .forkchoice_updated(ForkchoiceState::from_single_head(Default::default()), None)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<EngineError>(),
+            Some(&EngineError::InvalidForkchoiceState)
+        );
+
+        let err = engine_api
+            .new_payload(Default::default())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<EngineError>(),
+            Some(EngineError::Rpc { code: -32601, .. })
+        ));
+    }
 }
@@ -0,0 +1,119 @@
+use std::fmt;
+
+/// JSON-RPC error code returned for an unknown payload id
+pub const UNKNOWN_PAYLOAD_CODE: i64 = -38001;
+
+/// JSON-RPC error code returned for an invalid forkchoice state
+pub const INVALID_FORKCHOICE_STATE_CODE: i64 = -38002;
+
+/// JSON-RPC error code returned for invalid payload attributes
+pub const INVALID_PAYLOAD_ATTRIBUTES_CODE: i64 = -38003;
+
+/// JSON-RPC error code returned when a request is too large
+pub const TOO_LARGE_REQUEST_CODE: i64 = -38004;
+
+/// An error returned by an Engine API request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EngineError {
+    /// The engine does not know the requested payload id
+    UnknownPayload,
+    /// The forkchoice state is invalid or inconsistent
+    InvalidForkchoiceState,
+    /// The payload attributes are invalid or inconsistent
+    InvalidPayloadAttributes,
+    /// The request exceeds the engine's size limits
+    TooLargeRequest,
+    /// Any other JSON-RPC error
+    Rpc {
+        /// The error code
+        code: i64,
+        /// The error message
+        message: String,
+    },
+    /// The request did not complete within its timeout
+    Timeout,
+    /// The request could not be sent, or the response could not be read
+    Transport(String),
+}
+
+/// How the caller of a failed Engine API request should react
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EngineErrorKind {
+    /// The request may succeed if retried later
+    Temporary,
+    /// The engine and driver disagree on the chain, so the driver should reset to its
+    /// finalized head
+    Reset,
+    /// The request can never succeed
+    Critical,
+}
+
+impl EngineError {
+    /// Classifies a JSON-RPC error returned by the engine
+    pub fn from_rpc(code: i64, message: String) -> Self {
+        match code {
+            UNKNOWN_PAYLOAD_CODE => Self::UnknownPayload,
+            INVALID_FORKCHOICE_STATE_CODE => Self::InvalidForkchoiceState,
+            INVALID_PAYLOAD_ATTRIBUTES_CODE => Self::InvalidPayloadAttributes,
+            TOO_LARGE_REQUEST_CODE => Self::TooLargeRequest,
+            _ => Self::Rpc { code, message },
+        }
+    }
+
+    /// Returns how the caller should react to this error
+    pub fn kind(&self) -> EngineErrorKind {
+        match self {
+            Self::Timeout | Self::Transport(_) | Self::Rpc { .. } => EngineErrorKind::Temporary,
+            Self::UnknownPayload
+            | Self::InvalidForkchoiceState
+            | Self::InvalidPayloadAttributes => EngineErrorKind::Reset,
+            Self::TooLargeRequest => EngineErrorKind::Critical,
+        }
+    }
+
+    /// Returns true if the request never reached the engine or timed out, in which case it
+    /// is safe to resend an idempotent request
+    pub fn is_transport(&self) -> bool {
+        matches!(self, Self::Timeout | Self::Transport(_))
+    }
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownPayload => write!(f, "unknown payload"),
+            Self::InvalidForkchoiceState => write!(f, "invalid forkchoice state"),
+            Self::InvalidPayloadAttributes => write!(f, "invalid payload attributes"),
+            Self::TooLargeRequest => write!(f, "too large request"),
+            Self::Rpc { code, message } => write!(f, "engine api error {}: {}", code, message),
+            Self::Timeout => write!(f, "engine api request timed out"),
+            Self::Transport(err) => write!(f, "engine api transport error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for EngineError {}
+
+#[cfg(test)]
+mod tests {
+    use super::{EngineError, EngineErrorKind};
+
+    #[test]
+    fn test_classify_rpc_errors() {
+        let err = EngineError::from_rpc(-38001, "Unknown payload".to_string());
+        assert_eq!(err, EngineError::UnknownPayload);
+        assert_eq!(err.kind(), EngineErrorKind::Reset);
+
+        let err = EngineError::from_rpc(-38002, "Invalid forkchoice state".to_string());
+        assert_eq!(err.kind(), EngineErrorKind::Reset);
+
+        let err = EngineError::from_rpc(-38004, "Too large request".to_string());
+        assert_eq!(err, EngineError::TooLargeRequest);
+        assert_eq!(err.kind(), EngineErrorKind::Critical);
+
+        let err = EngineError::from_rpc(-32000, "header not found".to_string());
+        assert_eq!(err.kind(), EngineErrorKind::Temporary);
+        assert!(!err.is_transport());
+        assert!(EngineError::Timeout.is_transport());
+    }
+}
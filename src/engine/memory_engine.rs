@@ -0,0 +1,371 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use async_trait::async_trait;
+use ethers::{types::H256, utils::keccak256};
+use eyre::Result;
+
+use crate::common::BlockInfo;
+
+use super::{
+    Engine, EngineError, ExecutionPayload, ForkChoiceUpdate, ForkchoiceState, PayloadAttributes,
+    PayloadId, PayloadStatus, Status, ENGINE_CAPABILITIES,
+};
+
+/// Stateful in-memory L2 Engine API for tests.
+///
+/// Builds deterministic payloads from [PayloadAttributes], with block hashes committing to
+/// the parent hash and the attributes. Payloads passed to `new_payload` must link to a known
+/// parent, forkchoice updates to unknown heads return `SYNCING`, safe or finalized blocks
+/// off the head's chain are rejected as an invalid forkchoice state, and attributes that do
+/// not advance the timestamp are rejected as invalid payload attributes after the forkchoice
+/// is applied, so chains of blocks can be driven through it much like a real execution
+/// client.
+#[derive(Debug)]
+pub struct MemoryEngine {
+    /// Engine state
+    inner: Mutex<Inner>,
+}
+
+/// Mutable [MemoryEngine] state
+#[derive(Debug)]
+struct Inner {
+    /// Known blocks by hash
+    blocks: HashMap<H256, ExecutionPayload>,
+    /// The current forkchoice
+    forkchoice: ForkchoiceState,
+    /// Built payloads by id
+    payloads: HashMap<PayloadId, ExecutionPayload>,
+    /// The id of the next built payload
+    next_payload_id: u64,
+    /// The number of upcoming calls to fail, as if the engine were unreachable
+    failures: u32,
+}
+
+impl MemoryEngine {
+    /// Creates a new [MemoryEngine] whose chain starts at the given genesis block
+    pub fn new(genesis: BlockInfo) -> Self {
+        let genesis_payload = ExecutionPayload {
+            parent_hash: genesis.parent_hash,
+            block_number: genesis.number.into(),
+            timestamp: genesis.timestamp.into(),
+            block_hash: genesis.hash,
+            ..Default::default()
+        };
+
+        Self {
+            inner: Mutex::new(Inner {
+                blocks: HashMap::from([(genesis.hash, genesis_payload)]),
+                forkchoice: ForkchoiceState::from_single_head(genesis.hash),
+                payloads: HashMap::new(),
+                next_payload_id: 1,
+                failures: 0,
+            }),
+        }
+    }
+
+    /// Returns the current forkchoice
+    pub fn forkchoice(&self) -> ForkchoiceState {
+        self.inner.lock().unwrap().forkchoice
+    }
+
+    /// Returns the block with the given hash, if known
+    pub fn block(&self, hash: H256) -> Option<ExecutionPayload> {
+        self.inner.lock().unwrap().blocks.get(&hash).cloned()
+    }
+
+    /// Returns the block at the head of the current forkchoice
+    pub fn head(&self) -> ExecutionPayload {
+        let inner = self.inner.lock().unwrap();
+        inner.blocks[&inner.forkchoice.head_block_hash].clone()
+    }
+
+    /// Fails the next `count` calls to `forkchoice_updated`, `new_payload` and `get_payload`
+    /// without changing the engine state
+    pub fn fail_next_calls(&self, count: u32) {
+        self.inner.lock().unwrap().failures = count;
+    }
+
+    /// Computes the block hash of a payload built on `parent_hash` from the given attributes
+    pub fn block_hash(parent_hash: H256, attributes: &PayloadAttributes) -> H256 {
+        let mut data = parent_hash.as_bytes().to_vec();
+        data.extend_from_slice(&attributes.timestamp.as_u64().to_be_bytes());
+        data.extend_from_slice(attributes.prev_randao.as_bytes());
+        data.extend_from_slice(attributes.suggested_fee_recipient.as_bytes());
+        data.extend_from_slice(&attributes.gas_limit.as_u64().to_be_bytes());
+        for tx in attributes.transactions.iter().flatten() {
+            data.extend_from_slice(&keccak256(&tx.0));
+        }
+
+        H256::from(keccak256(data))
+    }
+}
+
+impl Inner {
+    /// Errors if the current call should fail
+    fn check_failure(&mut self) -> Result<()> {
+        if self.failures > 0 {
+            self.failures -= 1;
+            eyre::bail!("engine unavailable");
+        }
+
+        Ok(())
+    }
+
+    /// Returns true if `ancestor` is the zero hash, or a known block on the chain ending at `head`
+    fn is_ancestor(&self, ancestor: H256, head: H256) -> bool {
+        if ancestor.is_zero() {
+            return true;
+        }
+
+        let mut current = self.blocks.get(&head);
+        while let Some(block) = current {
+            if block.block_hash == ancestor {
+                return true;
+            }
+            current = self.blocks.get(&block.parent_hash);
+        }
+
+        false
+    }
+}
+
+/// Creates a [PayloadStatus] without a validation error
+fn payload_status(status: Status, latest_valid_hash: Option<H256>) -> PayloadStatus {
+    PayloadStatus {
+        status,
+        latest_valid_hash,
+        validation_error: None,
+    }
+}
+
+#[async_trait]
+impl Engine for MemoryEngine {
+    async fn forkchoice_updated(
+        &self,
+        forkchoice_state: ForkchoiceState,
+        payload_attributes: Option<PayloadAttributes>,
+    ) -> Result<ForkChoiceUpdate> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.check_failure()?;
+        let head_hash = forkchoice_state.head_block_hash;
+
+        let Some(head) = inner.blocks.get(&head_hash).cloned() else {
+            return Ok(ForkChoiceUpdate {
+                payload_status: payload_status(Status::Syncing, None),
+                payload_id: None,
+            });
+        };
+
+        if !inner.is_ancestor(forkchoice_state.safe_block_hash, head_hash)
+            || !inner.is_ancestor(forkchoice_state.finalized_block_hash, head_hash)
+        {
+            return Err(EngineError::InvalidForkchoiceState.into());
+        }
+
+        inner.forkchoice = forkchoice_state;
+
+        // Invalid attributes are rejected after the forkchoice is applied
+        if matches!(&payload_attributes, Some(attributes) if attributes.timestamp <= head.timestamp)
+        {
+            return Err(EngineError::InvalidPayloadAttributes.into());
+        }
+
+        let payload_id = match payload_attributes {
+            Some(attributes) => {
+                let payload = ExecutionPayload {
+                    parent_hash: head_hash,
+                    fee_recipient: attributes.suggested_fee_recipient,
+                    prev_randao: attributes.prev_randao,
+                    block_number: head.block_number + 1,
+                    gas_limit: attributes.gas_limit,
+                    timestamp: attributes.timestamp,
+                    block_hash: Self::block_hash(head_hash, &attributes),
+                    transactions: attributes.transactions.unwrap_or_default(),
+                    withdrawals: attributes.withdrawals,
+                    ..Default::default()
+                };
+
+                let id = PayloadId::from(inner.next_payload_id);
+                inner.next_payload_id += 1;
+                inner.payloads.insert(id, payload);
+                Some(id)
+            }
+            None => None,
+        };
+
+        Ok(ForkChoiceUpdate {
+            payload_status: payload_status(Status::Valid, Some(head_hash)),
+            payload_id,
+        })
+    }
+
+    async fn new_payload(&self, execution_payload: ExecutionPayload) -> Result<PayloadStatus> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.check_failure()?;
+        let hash = execution_payload.block_hash;
+
+        if inner.blocks.contains_key(&hash) {
+            return Ok(payload_status(Status::Valid, Some(hash)));
+        }
+
+        let Some(parent) = inner.blocks.get(&execution_payload.parent_hash) else {
+            return Ok(payload_status(Status::Syncing, None));
+        };
+
+        if execution_payload.block_number != parent.block_number + 1
+            || execution_payload.timestamp <= parent.timestamp
+        {
+            return Ok(payload_status(Status::Invalid, Some(parent.block_hash)));
+        }
+
+        inner.blocks.insert(hash, execution_payload);
+        Ok(payload_status(Status::Valid, Some(hash)))
+    }
+
+    async fn get_payload(&self, payload_id: PayloadId) -> Result<ExecutionPayload> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.check_failure()?;
+        inner
+            .payloads
+            .get(&payload_id)
+            .cloned()
+            .ok_or(eyre::eyre!("unknown payload id {}", payload_id))
+    }
+
+    async fn exchange_capabilities(&self, _capabilities: Vec<String>) -> Result<Vec<String>> {
+        Ok(ENGINE_CAPABILITIES.iter().map(|m| m.to_string()).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::H256;
+
+    use crate::{
+        common::BlockInfo,
+        engine::{
+            Engine, EngineError, ExecutionPayload, ForkchoiceState, PayloadAttributes, Status,
+        },
+    };
+
+    use super::MemoryEngine;
+
+    fn genesis() -> BlockInfo {
+        BlockInfo {
+            hash: H256::from_low_u64_be(1),
+            number: 0,
+            parent_hash: H256::zero(),
+            timestamp: 100,
+        }
+    }
+
+    fn attributes(timestamp: u64) -> PayloadAttributes {
+        PayloadAttributes {
+            timestamp: timestamp.into(),
+            gas_limit: 30_000_000.into(),
+            ..Default::default()
+        }
+    }
+
+    async fn build_block(engine: &MemoryEngine, timestamp: u64) -> ExecutionPayload {
+        let forkchoice = engine.forkchoice();
+        let update = engine
+            .forkchoice_updated(forkchoice, Some(attributes(timestamp)))
+            .await
+            .unwrap();
+        assert_eq!(update.payload_status.status, Status::Valid);
+
+        let payload = engine
+            .get_payload(update.payload_id.unwrap())
+            .await
+            .unwrap();
+        let status = engine.new_payload(payload.clone()).await.unwrap();
+        assert_eq!(status.status, Status::Valid);
+
+        let forkchoice = ForkchoiceState {
+            head_block_hash: payload.block_hash,
+            ..forkchoice
+        };
+        let update = engine.forkchoice_updated(forkchoice, None).await.unwrap();
+        assert_eq!(update.payload_status.status, Status::Valid);
+
+        payload
+    }
+
+    #[tokio::test]
+    async fn test_build_chain() {
+        let engine = MemoryEngine::new(genesis());
+
+        let first = build_block(&engine, 102).await;
+        let second = build_block(&engine, 104).await;
+
+        assert_eq!(first.parent_hash, genesis().hash);
+        assert_eq!(second.parent_hash, first.block_hash);
+        assert_eq!(second.block_number.as_u64(), 2);
+        assert_eq!(engine.head(), second);
+
+        // Payloads are deterministic
+        let other = MemoryEngine::new(genesis());
+        assert_eq!(build_block(&other, 102).await, first);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_and_invalid_heads() {
+        let engine = MemoryEngine::new(genesis());
+        let first = build_block(&engine, 102).await;
+
+        // Unknown heads are synced to
+        let unknown = ForkchoiceState::from_single_head(H256::random());
+        let update = engine.forkchoice_updated(unknown, None).await.unwrap();
+        assert_eq!(update.payload_status.status, Status::Syncing);
+
+        let orphan = ExecutionPayload {
+            parent_hash: H256::random(),
+            block_hash: H256::random(),
+            ..first.clone()
+        };
+        let status = engine.new_payload(orphan).await.unwrap();
+        assert_eq!(status.status, Status::Syncing);
+
+        // Payloads must extend their parent
+        let bad_link = ExecutionPayload {
+            block_hash: H256::random(),
+            block_number: 5.into(),
+            ..first.clone()
+        };
+        let status = engine.new_payload(bad_link).await.unwrap();
+        assert_eq!(status.status, Status::Invalid);
+
+        // The safe head must be an ancestor of the head
+        let forkchoice = ForkchoiceState {
+            head_block_hash: genesis().hash,
+            safe_block_hash: first.block_hash,
+            finalized_block_hash: genesis().hash,
+        };
+        let err = engine
+            .forkchoice_updated(forkchoice, None)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<EngineError>(),
+            Some(&EngineError::InvalidForkchoiceState)
+        );
+        assert_eq!(engine.head(), first);
+
+        // Invalid attributes are rejected, but the forkchoice is still applied
+        let forkchoice = ForkchoiceState::from_single_head(genesis().hash);
+        let attributes = PayloadAttributes {
+            timestamp: 100.into(),
+            ..Default::default()
+        };
+        let err = engine
+            .forkchoice_updated(forkchoice, Some(attributes))
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<EngineError>(),
+            Some(&EngineError::InvalidPayloadAttributes)
+        );
+        assert_eq!(engine.head().block_hash, genesis().hash);
+    }
+}
@@ -17,6 +17,8 @@ pub struct MockEngine {
     pub new_payload_res: PayloadStatus,
     /// Get payload call response
     pub get_payload_res: ExecutionPayload,
+    /// Exchange capabilities call response
+    pub capabilities_res: Vec<String>,
 }
 
 #[async_trait]
@@ -40,4 +42,8 @@ impl Engine for MockEngine {
     async fn get_payload(&self, _payload_id: PayloadId) -> Result<ExecutionPayload> {
         Ok(self.get_payload_res.clone())
     }
+
+    async fn exchange_capabilities(&self, _capabilities: Vec<String>) -> Result<Vec<String>> {
+        Ok(self.capabilities_res.clone())
+    }
 }
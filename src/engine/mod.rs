@@ -13,6 +13,14 @@ pub use fork::*;
 mod api;
 pub use api::*;
 
+/// Engine API transports
+mod transport;
+pub use transport::*;
+
+/// Engine API errors
+mod error;
+pub use error::*;
+
 /// Auth module
 mod auth;
 pub use auth::*;
@@ -29,6 +37,12 @@ pub use traits::*;
 mod mock_engine;
 pub use mock_engine::*;
 
+/// Stateful in-memory engine for tests
+#[cfg(feature = "test-utils")]
+mod memory_engine;
+#[cfg(feature = "test-utils")]
+pub use memory_engine::*;
+
 #[cfg(test)]
 mod tests {
     use crate::engine::EngineApi;
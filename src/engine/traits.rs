@@ -94,4 +94,23 @@ pub trait Engine: Send + Sync + 'static {
     ///
     /// See more details in the [Optimism Specs](https://github.com/ethereum-optimism/specs/blob/main/specs/protocol/exec-engine.md#engine_getPayloadv1).
     async fn get_payload(&self, payload_id: PayloadId) -> Result<ExecutionPayload>;
+
+    /// ## exchange_capabilities
+    ///
+    /// No modifications to [`engine_exchangeCapabilities`](https://github.com/ethereum/execution-apis/blob/main/src/engine/common.md#engine_exchangecapabilities)
+    /// were made for L2. Exchanges the lists of engine methods supported by the consensus
+    /// and execution clients.
+    ///
+    /// ### Specification
+    ///
+    /// method: engine_exchangeCapabilities
+    ///
+    /// params:
+    /// - Array of strings: the engine methods supported by the consensus client
+    ///
+    /// timeout: 1s
+    ///
+    /// returns:
+    /// - Array of strings: the engine methods supported by the execution client
+    async fn exchange_capabilities(&self, capabilities: Vec<String>) -> Result<Vec<String>>;
 }
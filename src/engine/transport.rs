@@ -0,0 +1,176 @@
+use std::{path::PathBuf, sync::Arc, time::Duration, time::SystemTime};
+
+use eyre::Result;
+use reqwest::{header, Client};
+use serde::Serialize;
+use serde_json::Value;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::UnixStream,
+    sync::Mutex,
+    time::timeout,
+};
+
+use super::{EngineError, JwtSecret};
+
+/// The url scheme that selects the IPC transport
+pub const IPC_SCHEME: &str = "ipc://";
+
+/// Transport used to send JSON-RPC requests to the engine api
+#[derive(Debug, Clone)]
+pub enum EngineTransport {
+    /// HTTP(S) transport, authenticated with a JWT
+    Http {
+        /// The request url
+        url: String,
+        /// HTTP Client
+        client: Client,
+        /// A JWT secret used to authenticate with the engine api
+        secret: JwtSecret,
+    },
+    /// Unix domain socket transport. The socket is only reachable from the local
+    /// host, so requests are not authenticated.
+    Ipc {
+        /// Path of the socket
+        path: PathBuf,
+        /// The open connection, if any. Requests are sent one at a time.
+        conn: Arc<Mutex<Option<UnixStream>>>,
+    },
+}
+
+impl EngineTransport {
+    /// Creates a new HTTP transport for the given url and secret
+    pub fn http(url: &str, secret: JwtSecret) -> Self {
+        let client = reqwest::Client::builder()
+            .default_headers({
+                header::HeaderMap::from_iter([(
+                    header::CONTENT_TYPE,
+                    header::HeaderValue::from_static("application/json"),
+                )])
+            })
+            .timeout(Duration::from_secs(5))
+            .build()
+            .expect("reqwest::Client could not be built, TLS backend could not be initialized");
+
+        Self::Http {
+            url: url.to_string(),
+            client,
+            secret,
+        }
+    }
+
+    /// Creates a new IPC transport for the socket at the given path. The socket is
+    /// connected lazily on the first request.
+    pub fn ipc(path: impl Into<PathBuf>) -> Self {
+        Self::Ipc {
+            path: path.into(),
+            conn: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Sends a JSON-RPC request body and returns the raw JSON response.
+    pub async fn send<B: Serialize>(
+        &self,
+        body: &B,
+        request_timeout: Duration,
+    ) -> Result<Value, EngineError> {
+        match self {
+            Self::Http {
+                url,
+                client,
+                secret,
+            } => {
+                // Construct the JWT Authorization Token
+                let claims = secret.generate_claims(Some(SystemTime::now()));
+                let jwt = secret.encode(&claims).map_err(|_| {
+                    EngineError::Transport("failed to encode jwt with claims".to_string())
+                })?;
+
+                let res = client
+                    .post(url)
+                    .header(header::AUTHORIZATION, format!("Bearer {}", jwt))
+                    .json(body)
+                    .timeout(request_timeout)
+                    .send()
+                    .await
+                    .map_err(http_error)?;
+
+                res.json().await.map_err(http_error)
+            }
+            Self::Ipc { path, conn } => {
+                let mut conn = conn.lock().await;
+                let res = timeout(request_timeout, ipc_request(path, &mut conn, body)).await;
+
+                match res {
+                    Ok(Ok(value)) => Ok(value),
+                    Ok(Err(err)) => {
+                        // Drop the connection so the next request reconnects
+                        conn.take();
+                        Err(EngineError::Transport(err.to_string()))
+                    }
+                    Err(_) => {
+                        // A late response would be read by the next request, so reconnect
+                        conn.take();
+                        Err(EngineError::Timeout)
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Converts a [reqwest::Error] into an [EngineError]
+fn http_error(err: reqwest::Error) -> EngineError {
+    if err.is_timeout() {
+        EngineError::Timeout
+    } else {
+        EngineError::Transport(err.to_string())
+    }
+}
+
+/// Writes a request to the socket, connecting first if needed, and reads back a single
+/// JSON value. Responses are not assumed to be newline delimited.
+async fn ipc_request<B: Serialize>(
+    path: &PathBuf,
+    conn: &mut Option<UnixStream>,
+    body: &B,
+) -> Result<Value> {
+    let stream = match conn {
+        Some(stream) => stream,
+        None => conn.insert(UnixStream::connect(path).await?),
+    };
+
+    stream.write_all(&serde_json::to_vec(body)?).await?;
+
+    let mut buf = Vec::new();
+    let mut chunk = vec![0u8; 64 * 1024];
+    loop {
+        let read = stream.read(&mut chunk).await?;
+        if read == 0 {
+            eyre::bail!("IPC connection to {:?} closed", path);
+        }
+        buf.extend_from_slice(&chunk[..read]);
+
+        // Only try to parse once the buffer could hold a complete response object
+        let data = trim_trailing_whitespace(&buf);
+        if data.last() != Some(&b'}') {
+            continue;
+        }
+
+        match serde_json::from_slice::<Value>(data) {
+            Ok(value) => return Ok(value),
+            Err(err) if err.is_eof() => continue,
+            Err(err) => return Err(err.into()),
+        }
+    }
+}
+
+/// Strips the trailing newline or other whitespace after a JSON value
+fn trim_trailing_whitespace(buf: &[u8]) -> &[u8] {
+    let end = buf
+        .iter()
+        .rposition(|b| !b.is_ascii_whitespace())
+        .map(|i| i + 1)
+        .unwrap_or(0);
+    &buf[..end]
+}
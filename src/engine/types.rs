@@ -9,6 +9,12 @@ pub const STATIC_ID: u32 = 1;
 /// The json rpc version string
 pub const JSONRPC_VERSION: &str = "2.0";
 
+/// The delay before the first retry of a failed idempotent request. Doubles on each retry.
+pub const ENGINE_RETRY_BACKOFF: Duration = Duration::from_millis(100);
+
+/// The maximum number of times an idempotent request is retried after a transport error
+pub const ENGINE_MAX_RETRIES: usize = 3;
+
 /// The new payload method string
 pub const ENGINE_NEW_PAYLOAD_V2: &str = "engine_newPayloadV2";
 
@@ -26,3 +32,17 @@ pub const ENGINE_FORKCHOICE_UPDATED_V2: &str = "engine_forkchoiceUpdatedV2";
 
 /// The forkchoice updated timeout
 pub const ENGINE_FORKCHOICE_UPDATED_TIMEOUT: Duration = Duration::from_secs(8);
+
+/// The exchange capabilities method string
+pub const ENGINE_EXCHANGE_CAPABILITIES: &str = "engine_exchangeCapabilities";
+
+/// The exchange capabilities timeout
+pub const ENGINE_EXCHANGE_CAPABILITIES_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The engine methods [EngineApi](super::EngineApi) sends, which are the same on every fork.
+/// The execution client must support all of them.
+pub const ENGINE_CAPABILITIES: [&str; 3] = [
+    ENGINE_FORKCHOICE_UPDATED_V2,
+    ENGINE_NEW_PAYLOAD_V2,
+    ENGINE_GET_PAYLOAD_V2,
+];
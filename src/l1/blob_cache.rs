@@ -0,0 +1,203 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fs,
+    path::PathBuf,
+    sync::Mutex,
+};
+
+use ethers::types::H256;
+
+use super::BlobSidecar;
+
+/// The maximum number of L1 blocks whose blobs are kept in memory.
+const MAX_CACHED_BLOCKS: usize = 128;
+/// The number of L1 blocks below the newest cached block for which entries are kept on disk.
+/// This is well beyond the channel timeout, so a restarted node still finds the blobs it
+/// needs to resume derivation.
+const RETENTION_BLOCKS: u64 = 7200;
+/// The number of L1 blocks between scans of the on-disk cache for expired entries.
+const PRUNE_INTERVAL: u64 = 256;
+
+/// Caches verified blob sidecars by the hash of the L1 block that carries them.
+///
+/// Entries are kept in memory for the most recent blocks so that re-ingesting L1 blocks
+/// after a reorg does not hit the beacon node again. If a directory is set, entries are
+/// also written to disk so they survive restarts, and pruned once they fall more than
+/// [RETENTION_BLOCKS] behind the newest cached block. Since L1 block hashes commit to the
+/// blob versioned hashes, an entry can never become stale.
+#[derive(Debug, Default)]
+pub struct BlobCache {
+    /// Directory for the on-disk cache, if enabled
+    dir: Option<PathBuf>,
+    /// In-memory entries
+    entries: Mutex<Entries>,
+}
+
+/// In-memory cache entries keyed by L1 block hash
+#[derive(Debug, Default)]
+struct Entries {
+    /// Sidecars by L1 block hash
+    sidecars: HashMap<H256, Vec<BlobSidecar>>,
+    /// L1 block hashes in insertion order, oldest first
+    order: VecDeque<H256>,
+    /// The newest L1 block number the on-disk cache was pruned at
+    pruned_at: Option<u64>,
+}
+
+impl BlobCache {
+    /// Creates a new [BlobCache], persisting entries to `dir` if set.
+    pub fn new(dir: Option<PathBuf>) -> Self {
+        Self {
+            dir,
+            entries: Mutex::new(Entries::default()),
+        }
+    }
+
+    /// Returns the cached sidecars for the given L1 block, checking memory first
+    /// and then the on-disk cache.
+    pub fn get(&self, block_number: u64, block_hash: H256) -> Option<Vec<BlobSidecar>> {
+        if let Some(sidecars) = self.entries.lock().ok()?.sidecars.get(&block_hash) {
+            return Some(sidecars.clone());
+        }
+
+        let path = self.path(block_number, block_hash)?;
+        let data = fs::read(path).ok()?;
+        let sidecars = serde_json::from_slice::<Vec<BlobSidecar>>(&data).ok()?;
+
+        self.insert_in_memory(block_hash, sidecars.clone());
+        Some(sidecars)
+    }
+
+    /// Adds sidecars for the given L1 block, merging them with any already cached.
+    pub fn insert(&self, block_number: u64, block_hash: H256, sidecars: Vec<BlobSidecar>) {
+        let mut merged = self.get(block_number, block_hash).unwrap_or_default();
+        for sidecar in sidecars {
+            if !merged.iter().any(|s| s.index == sidecar.index) {
+                merged.push(sidecar);
+            }
+        }
+
+        if let Some(path) = self.path(block_number, block_hash) {
+            let res = path
+                .parent()
+                .map(fs::create_dir_all)
+                .transpose()
+                .map_err(eyre::Report::from)
+                .and_then(|_| Ok(serde_json::to_vec(&merged)?))
+                .and_then(|data| Ok(fs::write(&path, data)?));
+
+            if let Err(err) = res {
+                tracing::warn!("failed to write blob cache entry {:?}: {}", path, err);
+            }
+        }
+
+        self.insert_in_memory(block_hash, merged);
+        self.prune(block_number);
+    }
+
+    /// Removes on-disk entries more than [RETENTION_BLOCKS] behind the given block, scanning
+    /// the cache directory at most once every [PRUNE_INTERVAL] blocks.
+    fn prune(&self, block_number: u64) {
+        let Some(dir) = &self.dir else {
+            return;
+        };
+
+        {
+            let Ok(mut entries) = self.entries.lock() else {
+                return;
+            };
+            if matches!(entries.pruned_at, Some(n) if block_number < n + PRUNE_INTERVAL) {
+                return;
+            }
+            entries.pruned_at = Some(block_number);
+        }
+
+        let Ok(files) = fs::read_dir(dir) else {
+            return;
+        };
+
+        let cutoff = block_number.saturating_sub(RETENTION_BLOCKS);
+        for path in files.filter_map(|file| Some(file.ok()?.path())) {
+            let expired = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.split_once('-'))
+                .and_then(|(number, _)| number.parse::<u64>().ok())
+                .is_some_and(|number| number < cutoff);
+
+            if expired {
+                if let Err(err) = fs::remove_file(&path) {
+                    tracing::warn!("failed to prune blob cache entry {:?}: {}", path, err);
+                }
+            }
+        }
+    }
+
+    /// Inserts an entry in memory, evicting the oldest entries over the size limit.
+    fn insert_in_memory(&self, block_hash: H256, sidecars: Vec<BlobSidecar>) {
+        let Ok(mut entries) = self.entries.lock() else {
+            return;
+        };
+
+        if entries.sidecars.insert(block_hash, sidecars).is_none() {
+            entries.order.push_back(block_hash);
+        }
+
+        while entries.order.len() > MAX_CACHED_BLOCKS {
+            if let Some(oldest) = entries.order.pop_front() {
+                entries.sidecars.remove(&oldest);
+            }
+        }
+    }
+
+    /// Returns the on-disk cache path for the given L1 block, if enabled.
+    fn path(&self, block_number: u64, block_hash: H256) -> Option<PathBuf> {
+        self.dir
+            .as_ref()
+            .map(|dir| dir.join(format!("{}-{:x}.json", block_number, block_hash)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::H256;
+
+    use super::{BlobCache, PRUNE_INTERVAL, RETENTION_BLOCKS};
+    use crate::l1::BlobSidecar;
+
+    fn sidecar(index: u64) -> BlobSidecar {
+        BlobSidecar {
+            index,
+            blob: vec![index as u8],
+            kzg_commitment: vec![0; 48],
+        }
+    }
+
+    #[test]
+    fn test_prune_expired_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let on_disk =
+            |number, hash| BlobCache::new(Some(dir.path().to_path_buf())).get(number, hash);
+        let old_hash = H256::from_low_u64_be(1);
+        let recent_hash = H256::from_low_u64_be(2);
+        let new_hash = H256::from_low_u64_be(3);
+
+        let cache = BlobCache::new(Some(dir.path().to_path_buf()));
+        cache.insert(100, old_hash, vec![sidecar(0)]);
+        cache.insert(200, recent_hash, vec![sidecar(0)]);
+        assert!(on_disk(100, old_hash).is_some());
+
+        let newest = 101 + RETENTION_BLOCKS;
+        cache.insert(newest, new_hash, vec![sidecar(0)]);
+        assert!(on_disk(100, old_hash).is_none());
+        assert!(on_disk(200, recent_hash).is_some());
+        assert!(on_disk(newest, new_hash).is_some());
+
+        // the directory is only rescanned every few blocks
+        cache.insert(200 + RETENTION_BLOCKS, new_hash, vec![sidecar(0)]);
+        assert!(on_disk(200, recent_hash).is_some());
+
+        cache.insert(newest + PRUNE_INTERVAL, new_hash, vec![sidecar(0)]);
+        assert!(on_disk(200, recent_hash).is_none());
+    }
+}
@@ -1,32 +1,103 @@
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::{
+    path::PathBuf,
+    sync::atomic::{AtomicU64, Ordering},
+};
 
+use ethers::types::H256;
 use eyre::Result;
-use serde::Deserialize;
+use openssl::sha::sha256;
+use serde::{Deserialize, Serialize, Serializer};
 use serde_json::Value;
 
+use super::{l1_info::L1BlockInfo, BlobCache};
+
+/// The version byte prepended to the hash of a KZG commitment to form a
+/// blob versioned hash, as defined in EIP-4844.
+const VERSIONED_HASH_VERSION_KZG: u8 = 0x01;
+
 /// The blob fetcher is responsible for fetching blob data from the L1 beacon chain,
 /// along with relevant parsing and validation.
 ///
 /// Consensus layer info required for deriving the slot at which a specific blob was
 /// included in the beacon chain is fetched on the first call to [`Self::get_slot_from_time`]
 /// and cached for all subsequent calls.
+///
+/// Post Dencun, blobs are requested by the root of the beacon block which carries the L1
+/// block, resolved from the L1 block's `parent_beacon_block_root`. This pins the exact
+/// beacon block and avoids ambiguity around missed slots. Verified blobs are kept in a
+/// [BlobCache] so re-ingesting L1 blocks after a reorg or restart does not download them again.
+///
+/// Blobs are pruned by beacon nodes after the retention window (~18 days) expires.
+/// To sync past that window, a list of fallback sources exposing the same beacon API
+/// (such as a blob archiver) can be set with [`Self::with_fallback_urls`]. They are
+/// tried in order whenever the beacon node cannot serve the requested blobs.
 pub struct BlobFetcher {
     l1_beacon_url: String,
+    fallback_urls: Vec<String>,
     client: reqwest::Client,
+    cache: BlobCache,
     genesis_timestamp: AtomicU64,
     seconds_per_slot: AtomicU64,
 }
 
 /// A beacon chain blob sidecar object.
-/// KZG commitment and proof fields are not used in the current implementation.
-#[derive(Debug, Deserialize)]
+/// The KZG proof field is not used in the current implementation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BlobSidecar {
     /// Blob index (transactions can have more than one blob)
-    #[serde(deserialize_with = "deserialize_string_to_u64")]
+    #[serde(
+        serialize_with = "serialize_u64_to_string",
+        deserialize_with = "deserialize_string_to_u64"
+    )]
     pub index: u64,
     /// Blob data (not decoded)
-    #[serde(deserialize_with = "deserialize_blob_bytes")]
+    #[serde(
+        serialize_with = "serialize_blob_bytes",
+        deserialize_with = "deserialize_blob_bytes"
+    )]
     pub blob: Vec<u8>,
+    /// KZG commitment to the blob data
+    #[serde(
+        serialize_with = "serialize_blob_bytes",
+        deserialize_with = "deserialize_blob_bytes"
+    )]
+    pub kzg_commitment: Vec<u8>,
+}
+
+/// A beacon chain block header, as returned by `/eth/v1/beacon/headers`.
+#[derive(Debug, Deserialize)]
+struct BeaconBlockHeader {
+    /// The beacon block root
+    root: H256,
+    /// Whether the block is on the canonical beacon chain
+    canonical: bool,
+    /// The signed header
+    header: SignedBeaconBlockHeader,
+}
+
+/// A signed beacon block header. Only the slot is used.
+#[derive(Debug, Deserialize)]
+struct SignedBeaconBlockHeader {
+    /// The header message
+    message: BeaconBlockHeaderMessage,
+}
+
+/// The beacon block header message. Only the slot is used.
+#[derive(Debug, Deserialize)]
+struct BeaconBlockHeaderMessage {
+    /// The slot of the block
+    #[serde(deserialize_with = "deserialize_string_to_u64")]
+    slot: u64,
+}
+
+impl BlobSidecar {
+    /// Returns the versioned hash of the sidecar's KZG commitment, which is
+    /// what blob-carrying transactions reference in `blobVersionedHashes`.
+    pub fn versioned_hash(&self) -> H256 {
+        let mut hash = sha256(&self.kzg_commitment);
+        hash[0] = VERSIONED_HASH_VERSION_KZG;
+        H256::from(hash)
+    }
 }
 
 impl BlobFetcher {
@@ -34,12 +105,88 @@ impl BlobFetcher {
     pub fn new(l1_beacon_url: String) -> Self {
         Self {
             l1_beacon_url,
+            fallback_urls: Vec::new(),
             client: reqwest::Client::new(),
+            cache: BlobCache::default(),
             genesis_timestamp: AtomicU64::new(0),
             seconds_per_slot: AtomicU64::new(0),
         }
     }
 
+    /// Sets the fallback blob sources, tried in order when the beacon node
+    /// cannot serve the requested blobs.
+    pub fn with_fallback_urls(mut self, fallback_urls: Vec<String>) -> Self {
+        self.fallback_urls = fallback_urls;
+        self
+    }
+
+    /// Sets the directory used to persist fetched blobs across restarts.
+    pub fn with_cache_dir(mut self, cache_dir: Option<PathBuf>) -> Self {
+        self.cache = BlobCache::new(cache_dir);
+        self
+    }
+
+    /// Returns the verified blobs with the given indices and versioned hashes
+    /// carried by the given L1 block, in the same order as `indexed_hashes`.
+    ///
+    /// Blobs are served from the cache when possible. Otherwise the beacon block
+    /// carrying the L1 block is resolved and only the missing indices are requested.
+    pub async fn get_blobs(
+        &self,
+        block: &L1BlockInfo,
+        indexed_hashes: &[(u64, H256)],
+    ) -> Result<Vec<BlobSidecar>> {
+        let cached = self.cache.get(block.number, block.hash).unwrap_or_default();
+        let missing = indexed_hashes
+            .iter()
+            .filter(|(index, _)| !cached.iter().any(|b| b.index == *index))
+            .copied()
+            .collect::<Vec<_>>();
+
+        if !missing.is_empty() {
+            let block_id = self.get_block_id(block).await?;
+            let fetched = self.fetch_blobs(&block_id, &missing).await?;
+            tracing::debug!(
+                "fetched {} blobs for beacon block {}",
+                fetched.len(),
+                block_id
+            );
+            self.cache.insert(block.number, block.hash, fetched);
+        }
+
+        let sidecars = self.cache.get(block.number, block.hash).unwrap_or_default();
+        select_blobs(sidecars, indexed_hashes)
+    }
+
+    /// Returns the beacon API block id of the beacon block which carries the given L1 block.
+    ///
+    /// Post Dencun this is the root of the child of the L1 block's parent beacon block at the
+    /// L1 block's slot. Older blocks without a parent beacon block root fall back to the slot,
+    /// as do blocks whose headers no source can serve. The slot is then checked against the
+    /// blobs' versioned hashes like any other block id.
+    pub async fn get_block_id(&self, block: &L1BlockInfo) -> Result<String> {
+        let slot = self.get_slot_from_time(block.timestamp).await?;
+
+        let Some(parent_root) = block.parent_beacon_block_root else {
+            return Ok(slot.to_string());
+        };
+
+        match self.fetch_beacon_headers(parent_root).await {
+            Ok(headers) => {
+                let root = select_block_root(headers, parent_root, slot)?;
+                Ok(format!("{:?}", root))
+            }
+            Err(err) => {
+                tracing::warn!(
+                    "failed to resolve beacon block root at slot {}, using the slot: {}",
+                    slot,
+                    err
+                );
+                Ok(slot.to_string())
+            }
+        }
+    }
+
     /// Given a timestamp, return the slot number at which the timestamp
     /// was included in the beacon chain.
     ///
@@ -80,12 +227,85 @@ impl BlobFetcher {
         Ok((time - genesis_timestamp) / seconds_per_slot)
     }
 
-    /// Fetch the blob sidecars for a given slot.
-    pub async fn fetch_blob_sidecars(&self, slot: u64) -> Result<Vec<BlobSidecar>> {
-        let base_url = format!("{}/eth/v1/beacon/blob_sidecars", self.l1_beacon_url);
-        let full_url = format!("{}/{}", base_url, slot);
+    /// Fetch the blobs with the given indices and versioned hashes for a given beacon block id.
+    ///
+    /// The beacon node is queried first, followed by each fallback source in order.
+    /// A source is only used if it returns every requested blob and each blob's
+    /// KZG commitment matches its expected versioned hash. The returned sidecars
+    /// are in the same order as `indexed_hashes`.
+    pub async fn fetch_blobs(
+        &self,
+        block_id: &str,
+        indexed_hashes: &[(u64, H256)],
+    ) -> Result<Vec<BlobSidecar>> {
+        let indices = indexed_hashes
+            .iter()
+            .map(|(index, _)| *index)
+            .collect::<Vec<_>>();
+
+        let sources = std::iter::once(&self.l1_beacon_url).chain(self.fallback_urls.iter());
 
-        let res = self.client.get(full_url).send().await?.error_for_status()?;
+        for source in sources {
+            let sidecars = match self
+                .fetch_blob_sidecars_from(source, block_id, &indices)
+                .await
+            {
+                Ok(sidecars) => sidecars,
+                Err(err) => {
+                    tracing::warn!(
+                        "failed to fetch blobs for block {} from {}: {}",
+                        block_id,
+                        source,
+                        err
+                    );
+                    continue;
+                }
+            };
+
+            match select_blobs(sidecars, indexed_hashes) {
+                Ok(blobs) => return Ok(blobs),
+                Err(err) => {
+                    tracing::warn!(
+                        "invalid blobs for block {} from {}: {}",
+                        block_id,
+                        source,
+                        err
+                    );
+                }
+            }
+        }
+
+        eyre::bail!("blobs for block {} not available from any source", block_id)
+    }
+
+    /// Fetch all blob sidecars for a given beacon block id (slot or block root).
+    pub async fn fetch_blob_sidecars(&self, block_id: &str) -> Result<Vec<BlobSidecar>> {
+        self.fetch_blob_sidecars_from(&self.l1_beacon_url, block_id, &[])
+            .await
+    }
+
+    /// Fetch the blob sidecars for a given beacon block id from a beacon API compatible
+    /// source. Only the given indices are requested, or all sidecars if empty.
+    async fn fetch_blob_sidecars_from(
+        &self,
+        source: &str,
+        block_id: &str,
+        indices: &[u64],
+    ) -> Result<Vec<BlobSidecar>> {
+        let base_url = format!("{}/eth/v1/beacon/blob_sidecars", source);
+        let full_url = format!("{}/{}", base_url, block_id);
+        let query = indices
+            .iter()
+            .map(|index| ("indices", index.to_string()))
+            .collect::<Vec<_>>();
+
+        let res = self
+            .client
+            .get(full_url)
+            .query(&query)
+            .send()
+            .await?
+            .error_for_status()?;
         let res = serde_json::from_slice::<Value>(&res.bytes().await?)?;
         let res = res.get("data").ok_or(eyre::eyre!("No data in response"))?;
 
@@ -94,6 +314,62 @@ impl BlobFetcher {
         Ok(blobs)
     }
 
+    /// Fetch the root of the beacon block at `slot` whose parent is `parent_root`.
+    ///
+    /// Errors if no such block is known to the first source that serves the headers,
+    /// which happens if the slot was missed or the block has not been imported yet.
+    pub async fn fetch_beacon_block_root(&self, parent_root: H256, slot: u64) -> Result<H256> {
+        let headers = self.fetch_beacon_headers(parent_root).await?;
+        select_block_root(headers, parent_root, slot)
+    }
+
+    /// Fetch the headers of the beacon blocks whose parent is `parent_root`, from the beacon
+    /// node or else the first fallback source that serves them.
+    async fn fetch_beacon_headers(&self, parent_root: H256) -> Result<Vec<BeaconBlockHeader>> {
+        let sources = std::iter::once(&self.l1_beacon_url).chain(self.fallback_urls.iter());
+
+        for source in sources {
+            match self.fetch_beacon_headers_from(source, parent_root).await {
+                Ok(headers) => return Ok(headers),
+                Err(err) => tracing::warn!(
+                    "failed to fetch beacon headers with parent {:?} from {}: {}",
+                    parent_root,
+                    source,
+                    err
+                ),
+            }
+        }
+
+        eyre::bail!(
+            "beacon headers with parent {:?} not available from any source",
+            parent_root
+        )
+    }
+
+    /// Fetch the headers of the beacon blocks whose parent is `parent_root` from a
+    /// beacon API compatible source.
+    async fn fetch_beacon_headers_from(
+        &self,
+        source: &str,
+        parent_root: H256,
+    ) -> Result<Vec<BeaconBlockHeader>> {
+        let base_url = format!("{}/eth/v1/beacon/headers", source);
+
+        let res = self
+            .client
+            .get(base_url)
+            .query(&[("parent_root", format!("{:?}", parent_root))])
+            .send()
+            .await?
+            .error_for_status()?;
+        let res = serde_json::from_slice::<Value>(&res.bytes().await?)?;
+        let res = res.get("data").ok_or(eyre::eyre!("No data in response"))?;
+
+        let headers = serde_json::from_value::<Vec<BeaconBlockHeader>>(res.clone())?;
+
+        Ok(headers)
+    }
+
     /// Fetch the genesis timestamp from the beacon chain.
     pub async fn fetch_beacon_genesis_timestamp(&self) -> Result<u64> {
         let base_url = format!("{}/eth/v1/beacon/genesis", self.l1_beacon_url);
@@ -121,6 +397,68 @@ impl BlobFetcher {
     }
 }
 
+/// Returns the root of the block at `slot` among the children of `parent_root`,
+/// preferring the canonical one.
+fn select_block_root(
+    headers: Vec<BeaconBlockHeader>,
+    parent_root: H256,
+    slot: u64,
+) -> Result<H256> {
+    let mut headers = headers
+        .into_iter()
+        .filter(|h| h.header.message.slot == slot)
+        .collect::<Vec<_>>();
+    headers.sort_by_key(|h| !h.canonical);
+
+    headers.first().map(|h| h.root).ok_or(eyre::eyre!(
+        "no beacon block at slot {} with parent {:?}",
+        slot,
+        parent_root
+    ))
+}
+
+/// Picks the sidecars with the requested indices, checking each against its expected
+/// versioned hash. Errors if any of them is missing or does not match.
+fn select_blobs(
+    mut sidecars: Vec<BlobSidecar>,
+    indexed_hashes: &[(u64, H256)],
+) -> Result<Vec<BlobSidecar>> {
+    let mut blobs = Vec::with_capacity(indexed_hashes.len());
+
+    for (index, hash) in indexed_hashes {
+        let Some(pos) = sidecars.iter().position(|b| b.index == *index) else {
+            eyre::bail!("blob index {} not found in fetched sidecars", index);
+        };
+
+        let sidecar = sidecars.swap_remove(pos);
+        if sidecar.versioned_hash() != *hash {
+            eyre::bail!(
+                "blob index {} does not match versioned hash {:?}",
+                index,
+                hash
+            );
+        }
+
+        blobs.push(sidecar);
+    }
+
+    Ok(blobs)
+}
+
+fn serialize_u64_to_string<S>(value: &u64, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(&value.to_string())
+}
+
+fn serialize_blob_bytes<S>(value: &[u8], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(&format!("0x{}", hex::encode(value)))
+}
+
 fn deserialize_string_to_u64<'de, D>(deserializer: D) -> Result<u64, D::Error>
 where
     D: serde::Deserializer<'de>,
@@ -138,3 +476,347 @@ where
     let bytes = hex::decode(s).map_err(serde::de::Error::custom)?;
     Ok(bytes)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+
+    use ethers::types::{H256, U256};
+    use openssl::sha::sha256;
+    use serde_json::json;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::BlobFetcher;
+    use crate::l1::l1_info::L1BlockInfo;
+
+    /// Starts a minimal HTTP server on a random local port that answers GET
+    /// requests from a fixed table of paths, ignoring the query string.
+    /// Unknown paths get a 404.
+    async fn serve(routes: HashMap<String, String>) -> String {
+        serve_recorded(routes).await.0
+    }
+
+    /// Like [serve], but also returns the list of requested paths, including the query.
+    async fn serve_recorded(routes: HashMap<String, String>) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+
+        tokio::spawn(async move {
+            loop {
+                let Ok((mut stream, _)) = listener.accept().await else {
+                    return;
+                };
+
+                let mut buf = vec![0u8; 4096];
+                let n = stream.read(&mut buf).await.unwrap_or(0);
+                let request = String::from_utf8_lossy(&buf[..n]);
+                let path = request.split_whitespace().nth(1).unwrap_or("/");
+                recorded.lock().unwrap().push(path.to_string());
+                let path = path.split('?').next().unwrap_or_default();
+
+                let (status, body) = match routes.get(path) {
+                    Some(body) => ("200 OK", body.clone()),
+                    None => ("404 Not Found", String::new()),
+                };
+
+                let response = format!(
+                    "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+
+                _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+
+        (format!("http://{}", addr), requests)
+    }
+
+    fn versioned_hash(commitment: &[u8]) -> H256 {
+        let mut hash = sha256(commitment);
+        hash[0] = 0x01;
+        H256::from(hash)
+    }
+
+    fn sidecars_body(sidecars: &[(u64, &[u8], &[u8])]) -> String {
+        let data = sidecars
+            .iter()
+            .map(|(index, blob, commitment)| {
+                json!({
+                    "index": index.to_string(),
+                    "blob": format!("0x{}", hex::encode(blob)),
+                    "kzg_commitment": format!("0x{}", hex::encode(commitment)),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        json!({ "data": data }).to_string()
+    }
+
+    fn beacon_routes(parent_root: H256, headers: &[(H256, u64, bool)]) -> HashMap<String, String> {
+        let headers = headers
+            .iter()
+            .map(|(root, slot, canonical)| {
+                json!({
+                    "root": root,
+                    "canonical": canonical,
+                    "header": { "message": { "slot": slot.to_string(), "parent_root": parent_root } },
+                })
+            })
+            .collect::<Vec<_>>();
+
+        HashMap::from([
+            (
+                "/eth/v1/beacon/genesis".to_string(),
+                json!({ "data": { "genesis_time": "1000" } }).to_string(),
+            ),
+            (
+                "/eth/v1/config/spec".to_string(),
+                json!({ "data": { "SECONDS_PER_SLOT": "12" } }).to_string(),
+            ),
+            (
+                "/eth/v1/beacon/headers".to_string(),
+                json!({ "data": headers }).to_string(),
+            ),
+        ])
+    }
+
+    fn l1_block(slot: u64, parent_beacon_block_root: H256) -> L1BlockInfo {
+        L1BlockInfo {
+            number: 100,
+            hash: H256::from_low_u64_be(100),
+            parent_hash: H256::from_low_u64_be(99),
+            timestamp: 1000 + slot * 12,
+            base_fee: U256::zero(),
+            mix_hash: H256::zero(),
+            parent_beacon_block_root: Some(parent_beacon_block_root),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_blobs_by_block_root() {
+        let commitment = [0xaa; 48];
+        let parent_root = H256::from_low_u64_be(1);
+        let root = H256::from_low_u64_be(2);
+        let forked_root = H256::from_low_u64_be(3);
+
+        // a sibling block at a later slot shares the same parent, e.g. after a missed slot
+        let mut routes = beacon_routes(parent_root, &[(forked_root, 6, false), (root, 5, true)]);
+        routes.insert(
+            format!("/eth/v1/beacon/blob_sidecars/{:?}", root),
+            sidecars_body(&[(1, &[4, 5, 6], &commitment)]),
+        );
+        let (beacon, requests) = serve_recorded(routes).await;
+
+        let fetcher = BlobFetcher::new(beacon);
+        let block = l1_block(5, parent_root);
+        let blobs = fetcher
+            .get_blobs(&block, &[(1, versioned_hash(&commitment))])
+            .await
+            .unwrap();
+
+        assert_eq!(blobs[0].blob, vec![4, 5, 6]);
+        assert!(requests.lock().unwrap().contains(&format!(
+            "/eth/v1/beacon/blob_sidecars/{:?}?indices=1",
+            root
+        )));
+    }
+
+    #[tokio::test]
+    async fn test_get_blobs_missed_slot() {
+        let parent_root = H256::from_low_u64_be(1);
+        let routes = beacon_routes(parent_root, &[(H256::from_low_u64_be(2), 6, true)]);
+        let beacon = serve(routes).await;
+
+        let fetcher = BlobFetcher::new(beacon);
+        let block = l1_block(5, parent_root);
+        let res = fetcher
+            .get_blobs(&block, &[(0, versioned_hash(&[0xaa; 48]))])
+            .await;
+
+        assert!(res.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_get_blobs_headers_from_fallback() {
+        let commitment = [0xaa; 48];
+        let parent_root = H256::from_low_u64_be(1);
+        let root = H256::from_low_u64_be(2);
+
+        // the beacon node serves the blobs but not the headers
+        let mut routes = beacon_routes(parent_root, &[]);
+        routes.remove("/eth/v1/beacon/headers");
+        routes.insert(
+            format!("/eth/v1/beacon/blob_sidecars/{:?}", root),
+            sidecars_body(&[(0, &[1, 2, 3], &commitment)]),
+        );
+        let beacon = serve(routes).await;
+        let archiver = serve(beacon_routes(parent_root, &[(root, 5, true)])).await;
+
+        let fetcher = BlobFetcher::new(beacon).with_fallback_urls(vec![archiver]);
+        let block = l1_block(5, parent_root);
+        let blobs = fetcher
+            .get_blobs(&block, &[(0, versioned_hash(&commitment))])
+            .await
+            .unwrap();
+
+        assert_eq!(blobs[0].blob, vec![1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_get_blobs_by_slot_without_headers() {
+        let commitment = [0xaa; 48];
+        let parent_root = H256::from_low_u64_be(1);
+
+        let mut routes = beacon_routes(parent_root, &[]);
+        routes.remove("/eth/v1/beacon/headers");
+        routes.insert(
+            "/eth/v1/beacon/blob_sidecars/5".to_string(),
+            sidecars_body(&[(0, &[1, 2, 3], &commitment)]),
+        );
+        let beacon = serve(routes).await;
+
+        let fetcher = BlobFetcher::new(beacon);
+        let block = l1_block(5, parent_root);
+        let blobs = fetcher
+            .get_blobs(&block, &[(0, versioned_hash(&commitment))])
+            .await
+            .unwrap();
+
+        assert_eq!(blobs[0].blob, vec![1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_get_blobs_cached() {
+        let commitment = [0xaa; 48];
+        let parent_root = H256::from_low_u64_be(1);
+        let root = H256::from_low_u64_be(2);
+        let cache_dir = tempfile::tempdir().unwrap();
+
+        let mut routes = beacon_routes(parent_root, &[(root, 5, true)]);
+        routes.insert(
+            format!("/eth/v1/beacon/blob_sidecars/{:?}", root),
+            sidecars_body(&[(0, &[1, 2, 3], &commitment)]),
+        );
+        let (beacon, requests) = serve_recorded(routes).await;
+
+        let fetcher = BlobFetcher::new(beacon).with_cache_dir(Some(cache_dir.path().to_path_buf()));
+        let block = l1_block(5, parent_root);
+        let hashes = [(0, versioned_hash(&commitment))];
+
+        fetcher.get_blobs(&block, &hashes).await.unwrap();
+        let request_count = requests.lock().unwrap().len();
+
+        // a reorg re-ingesting the block is served from memory
+        let blobs = fetcher.get_blobs(&block, &hashes).await.unwrap();
+        assert_eq!(blobs[0].blob, vec![1, 2, 3]);
+        assert_eq!(requests.lock().unwrap().len(), request_count);
+
+        // a restart is served from disk, without a reachable beacon node
+        let unreachable = serve(HashMap::new()).await;
+        let fetcher =
+            BlobFetcher::new(unreachable).with_cache_dir(Some(cache_dir.path().to_path_buf()));
+        let blobs = fetcher.get_blobs(&block, &hashes).await.unwrap();
+        assert_eq!(blobs[0].blob, vec![1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_blobs_from_beacon() {
+        let commitment = [0xaa; 48];
+        let route = "/eth/v1/beacon/blob_sidecars/7".to_string();
+        let beacon = serve(HashMap::from([(
+            route,
+            sidecars_body(&[(0, &[1, 2, 3], &[0xbb; 48]), (1, &[4, 5, 6], &commitment)]),
+        )]))
+        .await;
+
+        let fetcher = BlobFetcher::new(beacon);
+        let blobs = fetcher
+            .fetch_blobs("7", &[(1, versioned_hash(&commitment))])
+            .await
+            .unwrap();
+
+        assert_eq!(blobs.len(), 1);
+        assert_eq!(blobs[0].index, 1);
+        assert_eq!(blobs[0].blob, vec![4, 5, 6]);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_blobs_falls_back_when_pruned() {
+        let commitment = [0xaa; 48];
+        let route = "/eth/v1/beacon/blob_sidecars/7".to_string();
+
+        // the beacon node has pruned the slot and answers with a 404
+        let beacon = serve(HashMap::new()).await;
+        let archiver = serve(HashMap::from([(
+            route,
+            sidecars_body(&[(0, &[1, 2, 3], &commitment)]),
+        )]))
+        .await;
+
+        let fetcher = BlobFetcher::new(beacon).with_fallback_urls(vec![archiver]);
+        let blobs = fetcher
+            .fetch_blobs("7", &[(0, versioned_hash(&commitment))])
+            .await
+            .unwrap();
+
+        assert_eq!(blobs.len(), 1);
+        assert_eq!(blobs[0].blob, vec![1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_blobs_rejects_mismatched_fallback() {
+        let commitment = [0xaa; 48];
+        let route = "/eth/v1/beacon/blob_sidecars/7".to_string();
+
+        let beacon = serve(HashMap::new()).await;
+        let bad_archiver = serve(HashMap::from([(
+            route.clone(),
+            sidecars_body(&[(0, &[9, 9, 9], &[0xcc; 48])]),
+        )]))
+        .await;
+        let good_archiver = serve(HashMap::from([(
+            route,
+            sidecars_body(&[(0, &[1, 2, 3], &commitment)]),
+        )]))
+        .await;
+
+        let fetcher =
+            BlobFetcher::new(beacon).with_fallback_urls(vec![bad_archiver, good_archiver]);
+        let blobs = fetcher
+            .fetch_blobs("7", &[(0, versioned_hash(&commitment))])
+            .await
+            .unwrap();
+
+        assert_eq!(blobs[0].blob, vec![1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_blobs_fails_without_valid_source() {
+        let commitment = [0xaa; 48];
+        let route = "/eth/v1/beacon/blob_sidecars/7".to_string();
+
+        let beacon = serve(HashMap::new()).await;
+        let archiver = serve(HashMap::from([(
+            route,
+            sidecars_body(&[(0, &[9, 9, 9], &[0xcc; 48])]),
+        )]))
+        .await;
+
+        let fetcher = BlobFetcher::new(beacon).with_fallback_urls(vec![archiver]);
+        let res = fetcher
+            .fetch_blobs("7", &[(0, versioned_hash(&commitment))])
+            .await;
+
+        assert!(res.is_err());
+    }
+}
@@ -1,9 +1,13 @@
-use std::{collections::HashMap, sync::Arc, time::Duration};
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use bytes::Bytes;
 use ethers::{
     providers::{Http, HttpRateLimitRetryPolicy, Middleware, Provider, RetryClient},
-    types::{Address, Block, BlockNumber, Filter, Transaction, H256},
+    types::{Block, BlockNumber, Filter, Transaction, H256},
     utils::keccak256,
 };
 use eyre::Result;
@@ -16,11 +20,12 @@ use crate::{
     config::{Config, SystemConfig},
     derive::stages::attributes::UserDeposited,
     l1::decode_blob_data,
+    telemetry::metrics,
 };
 
-use super::{l1_info::L1BlockInfo, BlobFetcher, L1Info, SystemConfigUpdate};
+use super::{l1_info::L1BlockInfo, BlobFetcher, L1Info, SystemConfigLoader, SystemConfigUpdate};
 
-static CONFIG_UPDATE_TOPIC: Lazy<H256> =
+pub(crate) static CONFIG_UPDATE_TOPIC: Lazy<H256> =
     Lazy::new(|| H256::from_slice(&keccak256("ConfigUpdate(uint256,uint8,bytes)")));
 
 static TRANSACTION_DEPOSITED_TOPIC: Lazy<H256> = Lazy::new(|| {
@@ -33,6 +38,9 @@ static TRANSACTION_DEPOSITED_TOPIC: Lazy<H256> = Lazy::new(|| {
 /// according to EIP 4844.
 const BLOB_CARRYING_TRANSACTION_TYPE: u64 = 3;
 
+/// How often the L1 head is refreshed while catching up, to keep the head lag accurate
+const HEAD_REFRESH_INTERVAL: Duration = Duration::from_secs(12);
+
 /// The data contained in a batcher transaction.
 /// The actual source of this data can be either calldata or blobs.
 pub type BatcherTransactionData = Bytes;
@@ -50,6 +58,8 @@ pub struct ChainWatcher {
     l1_start_block: u64,
     /// The L2 starting block
     l2_start_block: u64,
+    /// L1 beacon node to fetch blobs. Shared across restarts to reuse its blob cache
+    blob_fetcher: Arc<BlobFetcher>,
     /// Channel for receiving block updates for each new block
     block_update_receiver: Option<mpsc::Receiver<BlockUpdate>>,
 }
@@ -59,7 +69,11 @@ pub enum BlockUpdate {
     /// A new block extending the current chain
     NewBlock(Box<L1Info>),
     /// Updates the most recent finalized block
-    FinalityUpdate(u64),
+    FinalityUpdate(BlockInfo),
+    /// Updates the most recent block at the L1 head
+    HeadUpdate(BlockInfo),
+    /// Updates the most recent safe block
+    SafeUpdate(BlockInfo),
     /// Reorg detected
     Reorg,
 }
@@ -77,8 +91,12 @@ struct InnerWatcher {
     block_update_sender: mpsc::Sender<BlockUpdate>,
     /// Most recent ingested block
     current_block: u64,
-    /// Most recent block
+    /// Most recent block with enough confirmations to be ingested
     head_block: u64,
+    /// Most recent block at the L1 head
+    latest_block: u64,
+    /// When the L1 head was last queried
+    head_refreshed_at: Option<Instant>,
     /// Most recent finalized block
     finalized_block: u64,
     /// List of blocks that have not been finalized yet
@@ -104,11 +122,18 @@ impl ChainWatcher {
     /// Creates a new ChainWatcher and begins the monitoring task.
     /// Errors if the rpc url in the config is invalid.
     pub fn new(l1_start_block: u64, l2_start_block: u64, config: Arc<Config>) -> Result<Self> {
+        let blob_fetcher = Arc::new(
+            BlobFetcher::new(config.l1_beacon_url.clone())
+                .with_fallback_urls(config.l1_beacon_fallback_urls.clone())
+                .with_cache_dir(config.data_dir.as_ref().map(|dir| dir.join("blobs"))),
+        );
+
         Ok(Self {
             handle: None,
             config,
             l1_start_block,
             l2_start_block,
+            blob_fetcher,
             block_update_receiver: None,
         })
     }
@@ -123,6 +148,7 @@ impl ChainWatcher {
             self.l1_start_block,
             self.l2_start_block,
             self.config.clone(),
+            self.blob_fetcher.clone(),
         )?;
 
         self.handle = Some(handle);
@@ -137,7 +163,12 @@ impl ChainWatcher {
             handle.abort();
         }
 
-        let (handle, recv) = start_watcher(l1_start_block, l2_start_block, self.config.clone())?;
+        let (handle, recv) = start_watcher(
+            l1_start_block,
+            l2_start_block,
+            self.config.clone(),
+            self.blob_fetcher.clone(),
+        )?;
 
         self.handle = Some(handle);
         self.block_update_receiver = Some(recv);
@@ -171,62 +202,36 @@ impl ChainWatcher {
 impl InnerWatcher {
     async fn new(
         config: Arc<Config>,
+        blob_fetcher: Arc<BlobFetcher>,
         block_update_sender: mpsc::Sender<BlockUpdate>,
         l1_start_block: u64,
         l2_start_block: u64,
-    ) -> Self {
+    ) -> Result<Self> {
         let provider = generate_http_provider(&config.l1_rpc_url);
-        let blob_fetcher = Arc::new(BlobFetcher::new(config.l1_beacon_url.clone()));
 
         let system_config = if l2_start_block == config.chain.l2_genesis.number {
             config.chain.system_config
         } else {
-            let l2_provider = generate_http_provider(&config.l2_rpc_url);
-
-            let block = l2_provider
-                .get_block_with_txs(l2_start_block - 1)
-                .await
-                .unwrap()
-                .unwrap();
-
-            let input = &block
-                .transactions
-                .first()
-                .expect(
-                    "Could not find the L1 attributes deposited transaction in the parent L2 block",
-                )
-                .input;
-
-            let batch_sender = Address::from_slice(&input[176..196]);
-            let l1_fee_overhead = alloy_primitives::U256::from_be_slice(&input[196..228]);
-            let l1_fee_scalar = alloy_primitives::U256::from_be_slice(&input[228..260]);
-            let mut gas_limit: [u8; 32] = [0; 32];
-            block.gas_limit.to_big_endian(&mut gas_limit);
-            let gas_limit = alloy_primitives::U256::from_be_slice(&gas_limit);
-
-            SystemConfig {
-                batch_sender: alloy_primitives::Address::from_slice(batch_sender.as_bytes()),
-                l1_fee_overhead,
-                l1_fee_scalar,
-                gas_limit,
-                // TODO: fetch from contract
-                unsafe_block_signer: config.chain.system_config.unsafe_block_signer,
-            }
+            SystemConfigLoader::new(provider.clone(), &config)
+                .load(l1_start_block)
+                .await?
         };
 
-        Self {
+        Ok(Self {
             config,
             provider,
             blob_fetcher,
             block_update_sender,
             current_block: l1_start_block,
             head_block: 0,
+            latest_block: 0,
+            head_refreshed_at: None,
             finalized_block: 0,
             unfinalized_blocks: Vec::new(),
             deposits: HashMap::new(),
             system_config,
             system_config_update: (l1_start_block, None),
-        }
+        })
     }
 
     async fn try_ingest_block(&mut self) -> Result<()> {
@@ -234,9 +239,9 @@ impl InnerWatcher {
             let finalized_block = self.get_finalized().await?;
 
             // Only update finalized block if it has changed to avoid spamming the channel.
-            if self.finalized_block < finalized_block {
-                tracing::debug!("[l1] finalized block updated to {}", finalized_block);
-                self.finalized_block = finalized_block;
+            if self.finalized_block < finalized_block.number {
+                tracing::debug!("[l1] finalized block updated to {}", finalized_block.number);
+                self.finalized_block = finalized_block.number;
                 self.block_update_sender
                     .send(BlockUpdate::FinalityUpdate(finalized_block))
                     .await?;
@@ -246,11 +251,30 @@ impl InnerWatcher {
             }
         }
 
-        if self.current_block > self.head_block {
-            let head_block = self.get_head().await?;
-            self.head_block = head_block;
+        let refresh_due = self
+            .head_refreshed_at
+            .is_none_or(|at| at.elapsed() >= HEAD_REFRESH_INTERVAL);
+
+        if self.current_block > self.head_block || refresh_due {
+            let latest = self.get_head().await?;
+            self.head_refreshed_at = Some(Instant::now());
+            if latest.number > self.latest_block {
+                self.block_update_sender
+                    .send(BlockUpdate::HeadUpdate(latest))
+                    .await?;
+
+                let safe = self.get_safe().await?;
+                self.block_update_sender
+                    .send(BlockUpdate::SafeUpdate(safe))
+                    .await?;
+            }
+
+            self.latest_block = latest.number;
+            self.head_block = self.latest_block.saturating_sub(self.config.l1_confs);
         }
 
+        metrics::L1_HEAD_LAG.set(self.latest_block.saturating_sub(self.current_block) as i64);
+
         if self.current_block <= self.head_block {
             self.update_system_config().await?;
 
@@ -309,34 +333,34 @@ impl InnerWatcher {
                 .to_block(to_block);
 
             let updates = self.provider.get_logs(&filter).await?;
-            let update = updates.into_iter().next();
+            let update_block = updates.first().and_then(|update| update.block_number);
 
-            let update_block = update.as_ref().and_then(|update| update.block_number);
-            let update = update.and_then(|update| SystemConfigUpdate::try_from(update).ok());
+            if let Some(update_block) = update_block {
+                let timestamp = self
+                    .provider
+                    .get_block(update_block)
+                    .await?
+                    .ok_or(eyre::eyre!("block not found"))?
+                    .timestamp
+                    .as_u64();
 
-            if let Some((update_block, update)) = update_block.zip(update) {
+                let ecotone = self.config.chain.is_ecotone(timestamp);
                 let mut config = self.system_config;
-                match update {
-                    SystemConfigUpdate::BatchSender(addr) => {
-                        config.batch_sender =
-                            alloy_primitives::Address::from_slice(addr.as_bytes());
-                    }
-                    SystemConfigUpdate::Fees(overhead, scalar) => {
-                        let mut oh: [u8; 32] = [0; 32];
-                        overhead.to_big_endian(&mut oh);
-                        config.l1_fee_overhead = alloy_primitives::U256::from_be_bytes(oh);
-                        let mut s: [u8; 32] = [0; 32];
-                        scalar.to_big_endian(&mut s);
-                        config.l1_fee_scalar = alloy_primitives::U256::from_be_bytes(s);
-                    }
-                    SystemConfigUpdate::Gas(gas) => {
-                        let mut g: [u8; 32] = [0; 32];
-                        gas.to_big_endian(&mut g);
-                        config.gas_limit = alloy_primitives::U256::from_be_bytes(g);
-                    }
-                    SystemConfigUpdate::UnsafeBlockSigner(addr) => {
-                        config.unsafe_block_signer =
-                            alloy_primitives::Address::from_slice(addr.as_bytes());
+
+                // Apply every update in the block, since the next search starts after it
+                for update in updates
+                    .into_iter()
+                    .filter(|update| update.block_number == Some(update_block))
+                {
+                    let res = SystemConfigUpdate::try_from(update)
+                        .and_then(|update| update.apply(&mut config, ecotone));
+
+                    if let Err(err) = res {
+                        tracing::warn!(
+                            "skipping system config update in block {}: {}",
+                            update_block,
+                            err
+                        );
                     }
                 }
 
@@ -370,31 +394,37 @@ impl InnerWatcher {
         }
     }
 
-    async fn get_finalized(&self) -> Result<u64> {
-        let block_number = match self.config.devnet {
-            false => BlockNumber::Finalized,
-            true => BlockNumber::Latest,
-        };
+    async fn get_finalized(&self) -> Result<BlockInfo> {
+        match self.config.devnet {
+            false => self.get_block_info(BlockNumber::Finalized).await,
+            true => self.get_block_info(BlockNumber::Latest).await,
+        }
+    }
 
-        Ok(self
-            .provider
-            .get_block(block_number)
-            .await?
-            .ok_or(eyre::eyre!("block not found"))?
-            .number
-            .ok_or(eyre::eyre!("block pending"))?
-            .as_u64())
+    async fn get_safe(&self) -> Result<BlockInfo> {
+        match self.config.devnet {
+            false => self.get_block_info(BlockNumber::Safe).await,
+            true => self.get_block_info(BlockNumber::Latest).await,
+        }
     }
 
-    async fn get_head(&self) -> Result<u64> {
-        Ok(self
+    async fn get_head(&self) -> Result<BlockInfo> {
+        self.get_block_info(BlockNumber::Latest).await
+    }
+
+    async fn get_block_info(&self, block_number: BlockNumber) -> Result<BlockInfo> {
+        let block = self
             .provider
-            .get_block(BlockNumber::Latest)
+            .get_block(block_number)
             .await?
-            .ok_or(eyre::eyre!("block not found"))?
-            .number
-            .ok_or(eyre::eyre!("block pending"))?
-            .as_u64())
+            .ok_or(eyre::eyre!("block not found"))?;
+
+        Ok(BlockInfo {
+            hash: block.hash.ok_or(eyre::eyre!("block pending"))?,
+            number: block.number.ok_or(eyre::eyre!("block pending"))?.as_u64(),
+            parent_hash: block.parent_hash,
+            timestamp: block.timestamp.as_u64(),
+        })
     }
 
     async fn get_block(&self, block_num: u64) -> Result<Block<Transaction>> {
@@ -452,7 +482,7 @@ impl InnerWatcher {
         let mut blob_index = 0;
 
         for tx in block.transactions.iter() {
-            let tx_blob_hashes: Vec<String> = tx
+            let tx_blob_hashes: Vec<H256> = tx
                 .other
                 .get_deserialized("blobVersionedHashes")
                 .unwrap_or(Ok(Vec::new()))
@@ -470,7 +500,7 @@ impl InnerWatcher {
             }
 
             for blob_hash in tx_blob_hashes {
-                indexed_blobs.push((blob_index, blob_hash));
+                indexed_blobs.push((blob_index as u64, blob_hash));
                 blob_index += 1;
             }
         }
@@ -480,22 +510,13 @@ impl InnerWatcher {
             return Ok(batcher_transactions_data);
         }
 
-        let slot = self
+        let block_info = L1BlockInfo::try_from(block)?;
+        let blobs = self
             .blob_fetcher
-            .get_slot_from_time(block.timestamp.as_u64())
+            .get_blobs(&block_info, &indexed_blobs)
             .await?;
 
-        // perf: fetch only the required indexes instead of all
-        let blobs = self.blob_fetcher.fetch_blob_sidecars(slot).await?;
-        tracing::debug!("fetched {} blobs for slot {}", blobs.len(), slot);
-
-        for (blob_index, _) in indexed_blobs {
-            let Some(blob_sidecar) = blobs.iter().find(|b| b.index == blob_index as u64) else {
-                // This can happen in the case the blob retention window has expired
-                // and the data is no longer available. This case is not handled yet.
-                eyre::bail!("blob index {} not found in fetched sidecars", blob_index);
-            };
-
+        for blob_sidecar in blobs {
             // decode the full blob
             let decoded_blob_data = decode_blob_data(&blob_sidecar.blob)?;
 
@@ -508,9 +529,8 @@ impl InnerWatcher {
     /// Check if a transaction was sent from the batch sender to the batch inbox.
     #[inline]
     fn is_valid_batcher_transaction(&self, tx: &Transaction) -> bool {
-        let batch_sender = ethers::types::Address::from_slice(
-            self.config.chain.system_config.batch_sender.as_slice(),
-        );
+        let batch_sender =
+            ethers::types::Address::from_slice(self.system_config.batch_sender.as_slice());
         let batch_inbox =
             ethers::types::Address::from_slice(self.config.chain.batch_inbox.as_slice());
         tx.from == batch_sender && tx.to.map(|to| to == batch_inbox).unwrap_or(false)
@@ -532,12 +552,29 @@ fn start_watcher(
     l1_start_block: u64,
     l2_start_block: u64,
     config: Arc<Config>,
+    blob_fetcher: Arc<BlobFetcher>,
 ) -> Result<(JoinHandle<()>, mpsc::Receiver<BlockUpdate>)> {
     let (block_update_sender, block_update_receiver) = mpsc::channel(1000);
 
     let handle = spawn(async move {
-        let mut watcher =
-            InnerWatcher::new(config, block_update_sender, l1_start_block, l2_start_block).await;
+        let mut watcher = loop {
+            let res = InnerWatcher::new(
+                config.clone(),
+                blob_fetcher.clone(),
+                block_update_sender.clone(),
+                l1_start_block,
+                l2_start_block,
+            )
+            .await;
+
+            match res {
+                Ok(watcher) => break watcher,
+                Err(err) => {
+                    tracing::warn!("failed to start chain watcher: {}", err);
+                    sleep(Duration::from_secs(1)).await;
+                }
+            }
+        };
 
         loop {
             tracing::debug!("fetching L1 data for block {}", watcher.current_block);
@@ -566,7 +603,7 @@ mod tests {
 
     use crate::{
         config::{ChainConfig, Config},
-        l1::chain_watcher::InnerWatcher,
+        l1::{chain_watcher::InnerWatcher, BlobFetcher},
     };
 
     #[tokio::test]
@@ -593,7 +630,10 @@ mod tests {
             .unwrap()
             .unwrap();
 
-        let watcher_inner = InnerWatcher::new(config, mpsc::channel(1).0, 0, 0).await;
+        let blob_fetcher = Arc::new(BlobFetcher::new(config.l1_beacon_url.clone()));
+        let watcher_inner = InnerWatcher::new(config, blob_fetcher, mpsc::channel(1).0, 0, 0)
+            .await
+            .unwrap();
 
         let batcher_transactions = watcher_inner
             .get_batcher_transactions(&l1_block)
@@ -1,84 +1,416 @@
-use ethers::types::{Address, Log, U256};
-use eyre::Result;
+use std::fmt;
+
+use ethers::types::{Address, Log, H256, U256};
+
+use crate::config::SystemConfig;
 
 /// Represents a system config update event
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum SystemConfigUpdate {
     /// The batch sender address has been updated
     BatchSender(Address),
-    /// The fee overhead and scalar have been updated
+    /// The fee overhead and scalar have been updated. After Ecotone the overhead is unused
+    /// and the scalar is versioned, see [L1FeeScalars].
     Fees(U256, U256),
     /// The gas has been updated
     Gas(U256),
     /// The unsafe block signer has been updated
     UnsafeBlockSigner(Address),
+    /// The EIP-1559 parameters have been updated (Holocene)
+    Eip1559Params {
+        /// Base fee max change denominator
+        denominator: u32,
+        /// Elasticity multiplier
+        elasticity: u32,
+    },
+    /// The operator fee parameters have been updated (Isthmus)
+    OperatorFee {
+        /// Operator fee scalar
+        scalar: u32,
+        /// Operator fee constant
+        constant: u64,
+    },
+}
+
+/// Errors that can occur when parsing or applying a system config update
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SystemConfigUpdateError {
+    /// The log is missing the version or update type topic
+    MissingTopic,
+    /// The log was emitted with an unsupported event version
+    UnsupportedVersion(H256),
+    /// The update type is unknown
+    UnknownType(H256),
+    /// The ABI offset of the update data is not 32
+    InvalidPointer(U256),
+    /// The ABI length of the update data does not match the update type
+    InvalidLength {
+        /// The expected length in bytes
+        expected: usize,
+        /// The encoded length
+        actual: U256,
+    },
+    /// The update data is shorter than its encoded length
+    DataTooShort(usize),
+    /// A value has non-zero bytes where zero padding is expected
+    NonZeroPadding,
+    /// The L1 fee scalar uses an unknown version byte
+    UnknownScalarVersion(u8),
+}
+
+impl fmt::Display for SystemConfigUpdateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingTopic => write!(f, "system config update is missing a topic"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported system config update version {:?}", version)
+            }
+            Self::UnknownType(update_type) => {
+                write!(f, "unknown system config update type {:?}", update_type)
+            }
+            Self::InvalidPointer(pointer) => {
+                write!(f, "invalid system config update data offset {}", pointer)
+            }
+            Self::InvalidLength { expected, actual } => write!(
+                f,
+                "invalid system config update data length {}, expected {}",
+                actual, expected
+            ),
+            Self::DataTooShort(len) => {
+                write!(f, "system config update data too short: {} bytes", len)
+            }
+            Self::NonZeroPadding => write!(f, "system config update has non-zero padding"),
+            Self::UnknownScalarVersion(version) => {
+                write!(f, "unknown L1 fee scalar version {}", version)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SystemConfigUpdateError {}
+
+/// The L1 fee scalars encoded in the system config scalar after Ecotone
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct L1FeeScalars {
+    /// Scalar applied to the L1 base fee
+    pub base_fee_scalar: u32,
+    /// Scalar applied to the L1 blob base fee
+    pub blob_base_fee_scalar: u32,
+}
+
+impl L1FeeScalars {
+    /// Decodes a versioned scalar. Version 0 holds only the base fee scalar in the
+    /// lowest 4 bytes. Version 1 holds the blob base fee scalar in bytes 24..28
+    /// and the base fee scalar in bytes 28..32.
+    pub fn decode(scalar: U256) -> Result<Self, SystemConfigUpdateError> {
+        let mut bytes = [0u8; 32];
+        scalar.to_big_endian(&mut bytes);
+
+        let (padding, blob_base_fee_scalar) = match bytes[0] {
+            0 => (&bytes[1..28], 0),
+            1 => (&bytes[1..24], read_u32(&bytes[24..28])),
+            version => return Err(SystemConfigUpdateError::UnknownScalarVersion(version)),
+        };
+
+        if padding.iter().any(|b| *b != 0) {
+            return Err(SystemConfigUpdateError::NonZeroPadding);
+        }
+
+        Ok(Self {
+            base_fee_scalar: read_u32(&bytes[28..32]),
+            blob_base_fee_scalar,
+        })
+    }
+}
+
+impl SystemConfigUpdate {
+    /// Applies this update to the given system config. Fee updates are decoded as
+    /// versioned scalars if Ecotone is active, in which case the overhead is cleared.
+    pub fn apply(
+        &self,
+        config: &mut SystemConfig,
+        ecotone: bool,
+    ) -> Result<(), SystemConfigUpdateError> {
+        match self {
+            Self::BatchSender(addr) => {
+                config.batch_sender = alloy_primitives::Address::from_slice(addr.as_bytes());
+            }
+            Self::Fees(overhead, scalar) => {
+                if ecotone {
+                    L1FeeScalars::decode(*scalar)?;
+                    config.l1_fee_overhead = alloy_primitives::U256::ZERO;
+                } else {
+                    config.l1_fee_overhead = to_alloy_u256(overhead);
+                }
+                config.l1_fee_scalar = to_alloy_u256(scalar);
+            }
+            Self::Gas(gas) => {
+                config.gas_limit = to_alloy_u256(gas);
+            }
+            Self::UnsafeBlockSigner(addr) => {
+                config.unsafe_block_signer = alloy_primitives::Address::from_slice(addr.as_bytes());
+            }
+            Self::Eip1559Params {
+                denominator,
+                elasticity,
+            } => {
+                config.eip1559_denominator = *denominator;
+                config.eip1559_elasticity = *elasticity;
+            }
+            Self::OperatorFee { scalar, constant } => {
+                config.operator_fee_scalar = *scalar;
+                config.operator_fee_constant = *constant;
+            }
+        }
+
+        Ok(())
+    }
 }
 
 impl TryFrom<Log> for SystemConfigUpdate {
-    type Error = eyre::Report;
+    type Error = SystemConfigUpdateError;
 
-    fn try_from(log: Log) -> Result<Self> {
+    fn try_from(log: Log) -> Result<Self, Self::Error> {
         let version = log
             .topics
             .get(1)
-            .ok_or(eyre::eyre!("invalid system config update"))?
-            .to_low_u64_be();
+            .ok_or(SystemConfigUpdateError::MissingTopic)?;
 
-        if version != 0 {
-            return Err(eyre::eyre!("invalid system config update"));
+        if !version.is_zero() {
+            return Err(SystemConfigUpdateError::UnsupportedVersion(*version));
         }
 
         let update_type = log
             .topics
             .get(2)
-            .ok_or(eyre::eyre!("invalid system config update"))?
-            .to_low_u64_be();
+            .ok_or(SystemConfigUpdateError::MissingTopic)?;
 
-        match update_type {
-            0 => {
-                let addr_bytes = log
-                    .data
-                    .get(76..96)
-                    .ok_or(eyre::eyre!("invalid system config update"))?;
+        if update_type[..24].iter().any(|b| *b != 0) {
+            return Err(SystemConfigUpdateError::UnknownType(*update_type));
+        }
 
-                let addr = Address::from_slice(addr_bytes);
-                Ok(Self::BatchSender(addr))
+        match update_type.to_low_u64_be() {
+            0 => {
+                let data = payload(&log.data, 32)?;
+                Ok(Self::BatchSender(read_address(data)?))
             }
             1 => {
-                let fee_overhead = log
-                    .data
-                    .get(64..96)
-                    .ok_or(eyre::eyre!("invalid system config update"))?;
-
-                let fee_scalar = log
-                    .data
-                    .get(96..128)
-                    .ok_or(eyre::eyre!("invalid system config update"))?;
-
-                let fee_overhead = U256::from_big_endian(fee_overhead);
-                let fee_scalar = U256::from_big_endian(fee_scalar);
-
+                let data = payload(&log.data, 64)?;
+                let fee_overhead = U256::from_big_endian(&data[..32]);
+                let fee_scalar = U256::from_big_endian(&data[32..64]);
                 Ok(Self::Fees(fee_overhead, fee_scalar))
             }
             2 => {
-                let gas_bytes = log
-                    .data
-                    .get(64..96)
-                    .ok_or(eyre::eyre!("invalid system config update"))?;
-
-                let gas = U256::from_big_endian(gas_bytes);
-                Ok(Self::Gas(gas))
+                let data = payload(&log.data, 32)?;
+                Ok(Self::Gas(U256::from_big_endian(data)))
             }
             3 => {
-                let addr_bytes = log
-                    .data
-                    .get(76..96)
-                    .ok_or(eyre::eyre!("invalid system config update"))?;
-
-                let addr = Address::from_slice(addr_bytes);
-                Ok(Self::UnsafeBlockSigner(addr))
+                let data = payload(&log.data, 32)?;
+                Ok(Self::UnsafeBlockSigner(read_address(data)?))
             }
-            _ => Err(eyre::eyre!("invalid system config update")),
+            4 => {
+                let data = payload(&log.data, 32)?;
+                check_padding(&data[..24])?;
+                Ok(Self::Eip1559Params {
+                    denominator: read_u32(&data[24..28]),
+                    elasticity: read_u32(&data[28..32]),
+                })
+            }
+            5 => {
+                let data = payload(&log.data, 32)?;
+                check_padding(&data[..20])?;
+                let mut constant = [0u8; 8];
+                constant.copy_from_slice(&data[24..32]);
+                Ok(Self::OperatorFee {
+                    scalar: read_u32(&data[20..24]),
+                    constant: u64::from_be_bytes(constant),
+                })
+            }
+            _ => Err(SystemConfigUpdateError::UnknownType(*update_type)),
+        }
+    }
+}
+
+/// Returns the ABI encoded `bytes` payload of an update, checking that it has the expected length
+fn payload(data: &[u8], expected: usize) -> Result<&[u8], SystemConfigUpdateError> {
+    let too_short = || SystemConfigUpdateError::DataTooShort(data.len());
+
+    let pointer = U256::from_big_endian(data.get(..32).ok_or_else(too_short)?);
+    if pointer != U256::from(32) {
+        return Err(SystemConfigUpdateError::InvalidPointer(pointer));
+    }
+
+    let length = U256::from_big_endian(data.get(32..64).ok_or_else(too_short)?);
+    if length != U256::from(expected) {
+        return Err(SystemConfigUpdateError::InvalidLength {
+            expected,
+            actual: length,
+        });
+    }
+
+    data.get(64..64 + expected).ok_or_else(too_short)
+}
+
+/// Reads a left padded address from a 32 byte word
+fn read_address(word: &[u8]) -> Result<Address, SystemConfigUpdateError> {
+    check_padding(&word[..12])?;
+    Ok(Address::from_slice(&word[12..32]))
+}
+
+/// Reads a big endian u32 from a 4 byte slice
+pub(crate) fn read_u32(bytes: &[u8]) -> u32 {
+    let mut out = [0u8; 4];
+    out.copy_from_slice(bytes);
+    u32::from_be_bytes(out)
+}
+
+/// Errors if any padding byte is non-zero
+fn check_padding(padding: &[u8]) -> Result<(), SystemConfigUpdateError> {
+    if padding.iter().any(|b| *b != 0) {
+        return Err(SystemConfigUpdateError::NonZeroPadding);
+    }
+
+    Ok(())
+}
+
+/// Converts an ethers [U256] into an alloy U256
+fn to_alloy_u256(value: &U256) -> alloy_primitives::U256 {
+    let mut bytes = [0u8; 32];
+    value.to_big_endian(&mut bytes);
+    alloy_primitives::U256::from_be_bytes(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::{Address, Bytes, Log, H256, U256};
+
+    use crate::{config::ChainConfig, l1::chain_watcher::CONFIG_UPDATE_TOPIC};
+
+    use super::{L1FeeScalars, SystemConfigUpdate, SystemConfigUpdateError};
+
+    fn word(value: U256) -> [u8; 32] {
+        let mut out = [0u8; 32];
+        value.to_big_endian(&mut out);
+        out
+    }
+
+    fn update_log(version: u64, update_type: u64, payload: &[[u8; 32]]) -> Log {
+        let mut data = word(U256::from(32)).to_vec();
+        data.extend_from_slice(&word(U256::from(payload.len() * 32)));
+        payload.iter().for_each(|w| data.extend_from_slice(w));
+
+        Log {
+            topics: vec![
+                *CONFIG_UPDATE_TOPIC,
+                H256::from_low_u64_be(version),
+                H256::from_low_u64_be(update_type),
+            ],
+            data: Bytes::from(data),
+            ..Default::default()
         }
     }
+
+    fn ecotone_scalar(blob_base_fee_scalar: u32, base_fee_scalar: u32) -> U256 {
+        let mut bytes = [0u8; 32];
+        bytes[0] = 1;
+        bytes[24..28].copy_from_slice(&blob_base_fee_scalar.to_be_bytes());
+        bytes[28..32].copy_from_slice(&base_fee_scalar.to_be_bytes());
+        U256::from_big_endian(&bytes)
+    }
+
+    #[test]
+    fn test_parse_batch_sender() {
+        let addr = Address::random();
+        let log = update_log(0, 0, &[H256::from(addr).0]);
+
+        let update = SystemConfigUpdate::try_from(log).unwrap();
+        assert_eq!(update, SystemConfigUpdate::BatchSender(addr));
+    }
+
+    #[test]
+    fn test_decode_l1_fee_scalars() {
+        let scalars = L1FeeScalars::decode(ecotone_scalar(810949, 1368)).unwrap();
+        assert_eq!(scalars.base_fee_scalar, 1368);
+        assert_eq!(scalars.blob_base_fee_scalar, 810949);
+
+        let scalars = L1FeeScalars::decode(U256::from(684000)).unwrap();
+        assert_eq!(scalars.base_fee_scalar, 684000);
+        assert_eq!(scalars.blob_base_fee_scalar, 0);
+
+        let mut bytes = word(U256::from(1));
+        bytes[0] = 2;
+        let err = L1FeeScalars::decode(U256::from_big_endian(&bytes)).unwrap_err();
+        assert_eq!(err, SystemConfigUpdateError::UnknownScalarVersion(2));
+
+        let mut bytes = word(U256::from(1));
+        bytes[10] = 1;
+        let err = L1FeeScalars::decode(U256::from_big_endian(&bytes)).unwrap_err();
+        assert_eq!(err, SystemConfigUpdateError::NonZeroPadding);
+    }
+
+    #[test]
+    fn test_apply_fees_at_active_fork() {
+        let scalar = ecotone_scalar(810949, 1368);
+        let log = update_log(0, 1, &[word(U256::from(188)), word(scalar)]);
+        let update = SystemConfigUpdate::try_from(log).unwrap();
+
+        let mut config = ChainConfig::optimism().system_config;
+        update.apply(&mut config, false).unwrap();
+        assert_eq!(config.l1_fee_overhead, alloy_primitives::U256::from(188));
+
+        update.apply(&mut config, true).unwrap();
+        assert_eq!(config.l1_fee_overhead, alloy_primitives::U256::ZERO);
+        assert_eq!(
+            config.l1_fee_scalar,
+            alloy_primitives::U256::from_be_bytes(word(scalar))
+        );
+    }
+
+    #[test]
+    fn test_apply_eip1559_and_operator_fee() {
+        let mut params = [0u8; 32];
+        params[24..28].copy_from_slice(&250u32.to_be_bytes());
+        params[28..32].copy_from_slice(&6u32.to_be_bytes());
+        let eip1559 = SystemConfigUpdate::try_from(update_log(0, 4, &[params])).unwrap();
+
+        let mut params = [0u8; 32];
+        params[20..24].copy_from_slice(&7u32.to_be_bytes());
+        params[24..32].copy_from_slice(&1000u64.to_be_bytes());
+        let operator_fee = SystemConfigUpdate::try_from(update_log(0, 5, &[params])).unwrap();
+
+        let mut config = ChainConfig::optimism().system_config;
+        eip1559.apply(&mut config, true).unwrap();
+        operator_fee.apply(&mut config, true).unwrap();
+
+        assert_eq!(config.eip1559_denominator, 250);
+        assert_eq!(config.eip1559_elasticity, 6);
+        assert_eq!(config.operator_fee_scalar, 7);
+        assert_eq!(config.operator_fee_constant, 1000);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let gas = word(U256::from(30_000_000));
+
+        let err = SystemConfigUpdate::try_from(update_log(1, 2, &[gas])).unwrap_err();
+        assert_eq!(
+            err,
+            SystemConfigUpdateError::UnsupportedVersion(H256::from_low_u64_be(1))
+        );
+
+        let err = SystemConfigUpdate::try_from(update_log(0, 6, &[gas])).unwrap_err();
+        assert_eq!(
+            err,
+            SystemConfigUpdateError::UnknownType(H256::from_low_u64_be(6))
+        );
+
+        let err = SystemConfigUpdate::try_from(update_log(0, 1, &[gas])).unwrap_err();
+        assert_eq!(
+            err,
+            SystemConfigUpdateError::InvalidLength {
+                expected: 64,
+                actual: U256::from(32)
+            }
+        );
+    }
 }
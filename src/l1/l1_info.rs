@@ -26,6 +26,8 @@ pub struct L1BlockInfo {
     pub number: u64,
     /// L1 block hash
     pub hash: H256,
+    /// L1 parent block hash
+    pub parent_hash: H256,
     /// L1 block timestamp
     pub timestamp: u64,
     /// L1 base fee per gas
@@ -60,6 +62,7 @@ impl TryFrom<&Block<Transaction>> for L1BlockInfo {
         Ok(L1BlockInfo {
             number,
             hash,
+            parent_hash: value.parent_hash,
             timestamp,
             base_fee,
             mix_hash,
@@ -7,6 +7,10 @@ pub use chain_watcher::{BlockUpdate, ChainWatcher};
 pub mod config_updates;
 pub use config_updates::SystemConfigUpdate;
 
+/// Module responsible for loading the system config in effect at a given L1 block
+pub mod system_config;
+pub use system_config::SystemConfigLoader;
+
 /// L1 block info
 pub mod l1_info;
 pub use l1_info::L1Info;
@@ -16,6 +20,10 @@ pub use l1_info::L1Info;
 pub mod blob_fetcher;
 pub use blob_fetcher::{BlobFetcher, BlobSidecar};
 
+/// Module responsible for caching fetched blobs across reorgs and restarts
+pub mod blob_cache;
+pub use blob_cache::BlobCache;
+
 /// Helper module for decoding blob data
 pub mod blob_encoding;
 pub use blob_encoding::decode_blob_data;
@@ -0,0 +1,416 @@
+use std::{fs, path::PathBuf, sync::Arc};
+
+use ethers::{
+    providers::{JsonRpcError, Middleware, MiddlewareError},
+    types::{Address, BlockNumber, Bytes, Filter, TransactionRequest},
+    utils::keccak256,
+};
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{ChainConfig, Config, SystemConfig};
+
+use super::{chain_watcher::CONFIG_UPDATE_TOPIC, config_updates::read_u32, SystemConfigUpdate};
+
+/// The number of L1 blocks to query for config update logs in a single request.
+const LOG_RANGE: u64 = 10_000;
+
+/// Loads the [SystemConfig] in effect at a given L1 block.
+///
+/// The config is read from the system config contract getters at that block. Nodes that
+/// have pruned the state for the block cannot serve these calls, so in that case the config
+/// is rebuilt by replaying `ConfigUpdate` logs on top of the genesis config. The result is
+/// checkpointed to disk, so later replays only need to cover the blocks since then.
+#[derive(Debug)]
+pub struct SystemConfigLoader<M> {
+    /// L1 provider
+    provider: Arc<M>,
+    /// Address of the system config contract
+    contract: Address,
+    /// Chain config, for the genesis system config and fork activation times
+    chain: ChainConfig,
+    /// Path of the on-disk checkpoint, if enabled
+    checkpoint_path: Option<PathBuf>,
+}
+
+/// A system config and the L1 block it is in effect at
+#[derive(Debug, Serialize, Deserialize)]
+struct Checkpoint {
+    /// The L1 block number
+    l1_block: u64,
+    /// The system config after applying all updates up to and including `l1_block`
+    system_config: SystemConfig,
+}
+
+impl<M: Middleware + 'static> SystemConfigLoader<M> {
+    /// Creates a new [SystemConfigLoader] for the chain in the given config.
+    pub fn new(provider: Arc<M>, config: &Config) -> Self {
+        let checkpoint_path = config.data_dir.as_ref().map(|dir| {
+            dir.join("system_config")
+                .join(format!("{}.json", config.chain.l2_chain_id))
+        });
+
+        Self {
+            provider,
+            contract: Address::from_slice(config.chain.system_config_contract.as_slice()),
+            chain: config.chain.clone(),
+            checkpoint_path,
+        }
+    }
+
+    /// Returns the system config in effect at the given L1 block. Reads the contract
+    /// getters, falling back to replaying config update logs if that fails.
+    pub async fn load(&self, l1_block: u64) -> Result<SystemConfig> {
+        let system_config = match self.fetch_from_contract(l1_block).await {
+            Ok(system_config) => system_config,
+            Err(err) => {
+                tracing::warn!(
+                    "failed to read system config contract at block {}: {}; replaying config updates",
+                    l1_block,
+                    err
+                );
+                self.replay_updates(l1_block).await?
+            }
+        };
+
+        self.write_checkpoint(l1_block, system_config).await;
+        Ok(system_config)
+    }
+
+    /// Reads the system config from the contract getters at the given L1 block.
+    pub async fn fetch_from_contract(&self, l1_block: u64) -> Result<SystemConfig> {
+        let batcher_hash = self.call("batcherHash()", l1_block).await?;
+        let gas_limit = self.call("gasLimit()", l1_block).await?;
+        let overhead = self.call("overhead()", l1_block).await?;
+        let scalar = self.call("scalar()", l1_block).await?;
+        let unsafe_block_signer = self.call("unsafeBlockSigner()", l1_block).await?;
+
+        // These getters only exist once the contract is upgraded for Holocene and Isthmus,
+        // so a reverting call leaves the value unset.
+        let eip1559_denominator = self.try_call("eip1559Denominator()", l1_block).await?;
+        let eip1559_elasticity = self.try_call("eip1559Elasticity()", l1_block).await?;
+        let operator_fee_scalar = self.try_call("operatorFeeScalar()", l1_block).await?;
+        let operator_fee_constant = self.try_call("operatorFeeConstant()", l1_block).await?;
+
+        Ok(SystemConfig {
+            batch_sender: alloy_primitives::Address::from_slice(&batcher_hash[12..]),
+            gas_limit: alloy_primitives::U256::from_be_bytes(gas_limit),
+            l1_fee_overhead: alloy_primitives::U256::from_be_bytes(overhead),
+            l1_fee_scalar: alloy_primitives::U256::from_be_bytes(scalar),
+            unsafe_block_signer: alloy_primitives::Address::from_slice(&unsafe_block_signer[12..]),
+            eip1559_denominator: eip1559_denominator
+                .map(|w| read_u32(&w[28..]))
+                .unwrap_or_default(),
+            eip1559_elasticity: eip1559_elasticity
+                .map(|w| read_u32(&w[28..]))
+                .unwrap_or_default(),
+            operator_fee_scalar: operator_fee_scalar
+                .map(|w| read_u32(&w[28..]))
+                .unwrap_or_default(),
+            operator_fee_constant: operator_fee_constant
+                .map(|w| u64::from_be_bytes(w[24..].try_into().unwrap_or_default()))
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Rebuilds the system config at the given L1 block by applying config update logs
+    /// to the most recent checkpoint before it, or to the genesis config.
+    pub async fn replay_updates(&self, l1_block: u64) -> Result<SystemConfig> {
+        let genesis_block = self.chain.l1_start_epoch.number;
+        let (mut from_block, mut system_config) = self
+            .read_checkpoint()
+            .filter(|c| c.l1_block >= genesis_block && c.l1_block <= l1_block)
+            .map(|c| (c.l1_block, c.system_config))
+            .unwrap_or((genesis_block, self.chain.system_config));
+
+        tracing::info!(
+            "replaying system config updates from block {} to {}",
+            from_block,
+            l1_block
+        );
+
+        while from_block < l1_block {
+            let to_block = (from_block + LOG_RANGE).min(l1_block);
+            let filter = Filter::new()
+                .address(self.contract)
+                .topic0(*CONFIG_UPDATE_TOPIC)
+                .from_block(from_block + 1)
+                .to_block(to_block);
+
+            for log in self.provider.get_logs(&filter).await? {
+                let block = log
+                    .block_number
+                    .ok_or(eyre::eyre!("config update log without block number"))?;
+
+                let timestamp = self
+                    .provider
+                    .get_block(block)
+                    .await?
+                    .ok_or(eyre::eyre!("block not found"))?
+                    .timestamp
+                    .as_u64();
+
+                let res = SystemConfigUpdate::try_from(log).and_then(|update| {
+                    update.apply(&mut system_config, self.chain.is_ecotone(timestamp))
+                });
+
+                if let Err(err) = res {
+                    tracing::warn!("skipping system config update in block {}: {}", block, err);
+                }
+            }
+
+            from_block = to_block;
+        }
+
+        Ok(system_config)
+    }
+
+    /// Calls a getter on the system config contract and returns the first returned word.
+    async fn call(&self, signature: &str, l1_block: u64) -> Result<[u8; 32]> {
+        self.try_call(signature, l1_block)
+            .await?
+            .ok_or(eyre::eyre!("{} reverted", signature))
+    }
+
+    /// Calls a getter that may not exist on the contract yet. Returns `None` if the call
+    /// reverts, and any other error as is.
+    async fn try_call(&self, signature: &str, l1_block: u64) -> Result<Option<[u8; 32]>> {
+        let tx = TransactionRequest::new()
+            .to(self.contract)
+            .data(Bytes::from(keccak256(signature)[..4].to_vec()));
+
+        let res = match self.provider.call(&tx.into(), Some(l1_block.into())).await {
+            Ok(res) => res,
+            Err(err) if err.as_error_response().is_some_and(JsonRpcError::is_revert) => {
+                return Ok(None)
+            }
+            Err(err) => return Err(err.into()),
+        };
+        let word = res
+            .get(..32)
+            .ok_or(eyre::eyre!("invalid {} response: {}", signature, res))?;
+
+        let mut out = [0u8; 32];
+        out.copy_from_slice(word);
+        Ok(Some(out))
+    }
+
+    /// Reads the on-disk checkpoint, if any.
+    fn read_checkpoint(&self) -> Option<Checkpoint> {
+        let data = fs::read(self.checkpoint_path.as_ref()?).ok()?;
+        serde_json::from_slice(&data).ok()
+    }
+
+    /// Writes the on-disk checkpoint. Only finalized blocks are checkpointed since
+    /// the config at an unfinalized block could still be reorged.
+    async fn write_checkpoint(&self, l1_block: u64, system_config: SystemConfig) {
+        let Some(path) = &self.checkpoint_path else {
+            return;
+        };
+
+        let finalized = match self.provider.get_block(BlockNumber::Finalized).await {
+            Ok(block) => block.and_then(|b| b.number).map(|n| n.as_u64()),
+            Err(err) => {
+                tracing::debug!("failed to fetch finalized block: {}", err);
+                None
+            }
+        };
+
+        if !matches!(finalized, Some(f) if l1_block <= f) {
+            return;
+        }
+
+        let checkpoint = Checkpoint {
+            l1_block,
+            system_config,
+        };
+
+        let res = path
+            .parent()
+            .map(fs::create_dir_all)
+            .transpose()
+            .map_err(eyre::Report::from)
+            .and_then(|_| Ok(serde_json::to_vec(&checkpoint)?))
+            .and_then(|data| Ok(fs::write(path, data)?));
+
+        if let Err(err) = res {
+            tracing::warn!(
+                "failed to write system config checkpoint {:?}: {}",
+                path,
+                err
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use ethers::{
+        providers::{JsonRpcError, MockProvider, MockResponse, Provider},
+        types::{Address, Block, Bytes, Log, TxHash, H256, U256, U64},
+    };
+
+    use crate::{
+        config::{ChainConfig, Config},
+        l1::chain_watcher::CONFIG_UPDATE_TOPIC,
+    };
+
+    use super::SystemConfigLoader;
+
+    fn respond<T: serde::Serialize + Send + Sync>(mock: &MockProvider, value: T) {
+        mock.push::<T, _>(value).unwrap();
+    }
+
+    fn word(value: impl Into<U256>) -> Bytes {
+        let mut out = [0u8; 32];
+        value.into().to_big_endian(&mut out);
+        Bytes::from(out.to_vec())
+    }
+
+    fn address_word(addr: Address) -> Bytes {
+        word(U256::from_big_endian(addr.as_bytes()))
+    }
+
+    fn block(number: u64) -> Block<TxHash> {
+        Block {
+            number: Some(U64::from(number)),
+            ..Default::default()
+        }
+    }
+
+    fn gas_update(block: u64, gas: u64) -> Log {
+        let mut data = word(32).to_vec();
+        data.extend_from_slice(&word(32));
+        data.extend_from_slice(&word(gas));
+
+        Log {
+            topics: vec![*CONFIG_UPDATE_TOPIC, H256::zero(), H256::from_low_u64_be(2)],
+            data: Bytes::from(data),
+            block_number: Some(U64::from(block)),
+            ..Default::default()
+        }
+    }
+
+    fn rpc_error() -> MockResponse {
+        MockResponse::Error(JsonRpcError {
+            code: -32000,
+            message: "missing trie node".to_string(),
+            data: None,
+        })
+    }
+
+    fn revert() -> MockResponse {
+        MockResponse::Error(JsonRpcError {
+            code: 3,
+            message: "execution reverted".to_string(),
+            data: None,
+        })
+    }
+
+    fn config(data_dir: Option<std::path::PathBuf>) -> Config {
+        Config {
+            chain: ChainConfig::optimism(),
+            data_dir,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_load_from_contract() {
+        let (provider, mock) = Provider::mocked();
+        let batcher = Address::random();
+        let signer = Address::random();
+
+        // responses are returned in reverse order, the operator fee getters are not deployed
+        mock.push_response(revert());
+        mock.push_response(revert());
+        respond(&mock, word(6));
+        respond(&mock, word(250));
+        respond(&mock, address_word(signer));
+        respond(&mock, word(684000));
+        respond(&mock, word(188));
+        respond(&mock, word(30_000_000));
+        respond(&mock, address_word(batcher));
+
+        let loader = SystemConfigLoader::new(Arc::new(provider), &config(None));
+        let system_config = loader.load(100).await.unwrap();
+
+        assert_eq!(system_config.batch_sender.as_slice(), batcher.as_bytes());
+        assert_eq!(
+            system_config.gas_limit,
+            alloy_primitives::U256::from(30_000_000)
+        );
+        assert_eq!(
+            system_config.l1_fee_overhead,
+            alloy_primitives::U256::from(188)
+        );
+        assert_eq!(
+            system_config.l1_fee_scalar,
+            alloy_primitives::U256::from(684000)
+        );
+        assert_eq!(
+            system_config.unsafe_block_signer.as_slice(),
+            signer.as_bytes()
+        );
+        assert_eq!(system_config.eip1559_denominator, 250);
+        assert_eq!(system_config.eip1559_elasticity, 6);
+        assert_eq!(system_config.operator_fee_scalar, 0);
+        assert_eq!(system_config.operator_fee_constant, 0);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_from_contract_returns_rpc_errors() {
+        let (provider, mock) = Provider::mocked();
+
+        // A failing optional getter is an error rather than an unset value
+        mock.push_response(rpc_error());
+        respond(&mock, address_word(Address::random()));
+        respond(&mock, word(684000));
+        respond(&mock, word(188));
+        respond(&mock, word(30_000_000));
+        respond(&mock, address_word(Address::random()));
+
+        let loader = SystemConfigLoader::new(Arc::new(provider), &config(None));
+        assert!(loader.fetch_from_contract(100).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_load_replays_updates_from_checkpoint() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = config(Some(dir.path().to_path_buf()));
+        let genesis = config.chain.l1_start_epoch.number;
+
+        // State is pruned, so the first load replays from genesis and checkpoints.
+        let (provider, mock) = Provider::mocked();
+        respond(&mock, block(genesis + 100));
+        respond(&mock, block(genesis + 10));
+        respond(&mock, vec![gas_update(genesis + 10, 25_000_000)]);
+        mock.push_response(rpc_error());
+
+        let loader = SystemConfigLoader::new(Arc::new(provider), &config);
+        let system_config = loader.load(genesis + 20).await.unwrap();
+        assert_eq!(
+            system_config.gas_limit,
+            alloy_primitives::U256::from(25_000_000)
+        );
+        assert_eq!(
+            system_config.batch_sender,
+            config.chain.system_config.batch_sender
+        );
+
+        // The second load starts from the checkpoint, so only later logs are replayed.
+        let (provider, mock) = Provider::mocked();
+        respond(&mock, block(genesis + 100));
+        respond(&mock, Vec::<Log>::new());
+        mock.push_response(rpc_error());
+
+        let loader = SystemConfigLoader::new(Arc::new(provider), &config);
+        let system_config = loader.load(genesis + 50).await.unwrap();
+        assert_eq!(
+            system_config.gas_limit,
+            alloy_primitives::U256::from(25_000_000)
+        );
+    }
+}
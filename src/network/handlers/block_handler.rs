@@ -1,4 +1,3 @@
-use std::sync::mpsc::{channel, Receiver, Sender};
 use std::time::SystemTime;
 
 use ethers::types::{Address, Bytes, Signature, H256};
@@ -6,7 +5,10 @@ use ethers::utils::keccak256;
 use eyre::Result;
 use libp2p::gossipsub::{IdentTopic, Message, MessageAcceptance, TopicHash};
 use ssz_rs::{prelude::*, List, Vector, U256};
-use tokio::sync::watch;
+use tokio::sync::{
+    mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+    watch,
+};
 
 use crate::{common::RawTransaction, engine::ExecutionPayload};
 
@@ -17,7 +19,7 @@ pub struct BlockHandler {
     /// Chain ID of the L2 blockchain. Used to filter out gossip messages intended for other blockchains.
     chain_id: u64,
     /// A channel sender to forward new blocks to other modules
-    block_sender: Sender<ExecutionPayload>,
+    block_sender: UnboundedSender<ExecutionPayload>,
     /// A [watch::Receiver] to monitor changes to the unsafe block signer.
     unsafe_signer_recv: watch::Receiver<Address>,
     /// The libp2p topic for pre Canyon/Shangai blocks: `/optimism/{chain_id}/0/blocks`
@@ -78,8 +80,8 @@ impl BlockHandler {
     pub fn new(
         chain_id: u64,
         unsafe_recv: watch::Receiver<Address>,
-    ) -> (Self, Receiver<ExecutionPayload>) {
-        let (sender, recv) = channel();
+    ) -> (Self, UnboundedReceiver<ExecutionPayload>) {
+        let (sender, recv) = unbounded_channel();
 
         let handler = Self {
             chain_id,
@@ -168,6 +170,46 @@ fn decode_post_ecotone_block_msg(data: Vec<u8>) -> Result<ExecutionPayloadEnvelo
     })
 }
 
+/// Decodes an SSZ encoded [ExecutionPayload] received via `payload_by_number` requests.
+/// Version 0 and 1 payloads are the V1 and V2 SSZ payloads, and version 2 payloads are
+/// prefixed by the parent beacon block root.
+pub(crate) fn decode_payload(
+    version: u32,
+    data: &[u8],
+) -> Result<(ExecutionPayload, Option<H256>)> {
+    match version {
+        0 => Ok((deserialize::<ExecutionPayloadV1SSZ>(data)?.into(), None)),
+        1 => Ok((deserialize::<ExecutionPayloadV2SSZ>(data)?.into(), None)),
+        2 => {
+            if data.len() < 32 {
+                eyre::bail!("payload envelope too short");
+            }
+
+            let parent_beacon_block_root = H256::from_slice(&data[..32]);
+            let payload = deserialize::<ExecutionPayloadV3SSZ>(&data[32..])?;
+            Ok((payload.into(), Some(parent_beacon_block_root)))
+        }
+        _ => eyre::bail!("unknown payload version {}", version),
+    }
+}
+
+/// Encodes an [ExecutionPayload] to serve `payload_by_number` requests, returning its version
+/// and SSZ encoding. The version follows the fields set on the payload.
+pub(crate) fn encode_payload(
+    payload: &ExecutionPayload,
+    parent_beacon_block_root: Option<H256>,
+) -> Result<(u32, Vec<u8>)> {
+    if payload.excess_blob_gas.is_some() {
+        let root = parent_beacon_block_root.unwrap_or_default();
+        let data = serialize(&ExecutionPayloadV3SSZ::try_from(payload)?)?;
+        Ok((2, [root.as_bytes(), data.as_slice()].concat()))
+    } else if payload.withdrawals.is_some() {
+        Ok((1, serialize(&ExecutionPayloadV2SSZ::try_from(payload)?)?))
+    } else {
+        Ok((0, serialize(&ExecutionPayloadV1SSZ::try_from(payload)?)?))
+    }
+}
+
 /// Represents the Keccak256 hash of the block
 struct PayloadHash(H256);
 
@@ -236,6 +278,29 @@ struct ExecutionPayloadV1SSZ {
     pub transactions: List<Transaction, 1048576>,
 }
 
+impl TryFrom<&ExecutionPayload> for ExecutionPayloadV1SSZ {
+    type Error = eyre::Report;
+
+    fn try_from(payload: &ExecutionPayload) -> Result<Self> {
+        Ok(Self {
+            parent_hash: to_bytes32(payload.parent_hash)?,
+            fee_recipient: to_vec_address(payload.fee_recipient)?,
+            state_root: to_bytes32(payload.state_root)?,
+            receipts_root: to_bytes32(payload.receipts_root)?,
+            logs_bloom: to_byte_vector(&payload.logs_bloom)?,
+            prev_randao: to_bytes32(payload.prev_randao)?,
+            block_number: payload.block_number.as_u64(),
+            gas_limit: payload.gas_limit.as_u64(),
+            gas_used: payload.gas_used.as_u64(),
+            timestamp: payload.timestamp.as_u64(),
+            extra_data: to_byte_list(&payload.extra_data)?,
+            base_fee_per_gas: U256::from(payload.base_fee_per_gas.as_u64()),
+            block_hash: to_bytes32(payload.block_hash)?,
+            transactions: to_tx_list(&payload.transactions)?,
+        })
+    }
+}
+
 impl From<ExecutionPayloadV1SSZ> for ExecutionPayload {
     fn from(value: ExecutionPayloadV1SSZ) -> Self {
         Self {
@@ -309,6 +374,30 @@ struct Withdrawal {
     amount: u64,
 }
 
+impl TryFrom<&ExecutionPayload> for ExecutionPayloadV2SSZ {
+    type Error = eyre::Report;
+
+    fn try_from(payload: &ExecutionPayload) -> Result<Self> {
+        Ok(Self {
+            parent_hash: to_bytes32(payload.parent_hash)?,
+            fee_recipient: to_vec_address(payload.fee_recipient)?,
+            state_root: to_bytes32(payload.state_root)?,
+            receipts_root: to_bytes32(payload.receipts_root)?,
+            logs_bloom: to_byte_vector(&payload.logs_bloom)?,
+            prev_randao: to_bytes32(payload.prev_randao)?,
+            block_number: payload.block_number.as_u64(),
+            gas_limit: payload.gas_limit.as_u64(),
+            gas_used: payload.gas_used.as_u64(),
+            timestamp: payload.timestamp.as_u64(),
+            extra_data: to_byte_list(&payload.extra_data)?,
+            base_fee_per_gas: U256::from(payload.base_fee_per_gas.as_u64()),
+            block_hash: to_bytes32(payload.block_hash)?,
+            transactions: to_tx_list(&payload.transactions)?,
+            withdrawals: List::default(),
+        })
+    }
+}
+
 impl From<ExecutionPayloadV2SSZ> for ExecutionPayload {
     /// Converts an ExecutionPayloadV2SSZ received via p2p gossip into an [ExecutionPayload] used by the engine.
     fn from(value: ExecutionPayloadV2SSZ) -> Self {
@@ -355,6 +444,32 @@ struct ExecutionPayloadV3SSZ {
     pub excess_blob_gas: u64,
 }
 
+impl TryFrom<&ExecutionPayload> for ExecutionPayloadV3SSZ {
+    type Error = eyre::Report;
+
+    fn try_from(payload: &ExecutionPayload) -> Result<Self> {
+        Ok(Self {
+            parent_hash: to_bytes32(payload.parent_hash)?,
+            fee_recipient: to_vec_address(payload.fee_recipient)?,
+            state_root: to_bytes32(payload.state_root)?,
+            receipts_root: to_bytes32(payload.receipts_root)?,
+            logs_bloom: to_byte_vector(&payload.logs_bloom)?,
+            prev_randao: to_bytes32(payload.prev_randao)?,
+            block_number: payload.block_number.as_u64(),
+            gas_limit: payload.gas_limit.as_u64(),
+            gas_used: payload.gas_used.as_u64(),
+            timestamp: payload.timestamp.as_u64(),
+            extra_data: to_byte_list(&payload.extra_data)?,
+            base_fee_per_gas: U256::from(payload.base_fee_per_gas.as_u64()),
+            block_hash: to_bytes32(payload.block_hash)?,
+            transactions: to_tx_list(&payload.transactions)?,
+            withdrawals: List::default(),
+            blob_gas_used: payload.blob_gas_used.unwrap_or_default().as_u64(),
+            excess_blob_gas: payload.excess_blob_gas.unwrap_or_default().as_u64(),
+        })
+    }
+}
+
 impl From<ExecutionPayloadV3SSZ> for ExecutionPayload {
     fn from(value: ExecutionPayloadV3SSZ) -> Self {
         Self {
@@ -411,3 +526,33 @@ fn convert_uint(value: U256) -> ethers::types::U64 {
 fn convert_tx_list(value: List<Transaction, 1048576>) -> Vec<RawTransaction> {
     value.iter().map(|tx| RawTransaction(tx.to_vec())).collect()
 }
+
+/// Converts [H256] into [Bytes32]
+fn to_bytes32(hash: H256) -> Result<Bytes32> {
+    to_byte_vector(hash.as_bytes())
+}
+
+/// Converts [Address] into [VecAddress]
+fn to_vec_address(address: Address) -> Result<VecAddress> {
+    to_byte_vector(address.as_bytes())
+}
+
+/// Converts bytes into an [ssz_rs::Vector] of bytes
+fn to_byte_vector<const N: usize>(bytes: &[u8]) -> Result<Vector<u8, N>> {
+    Vector::try_from(bytes.to_vec()).map_err(|(_, err)| eyre::eyre!(err))
+}
+
+/// Converts bytes into an [ssz_rs::List] of bytes
+fn to_byte_list<const N: usize>(bytes: &[u8]) -> Result<List<u8, N>> {
+    List::try_from(bytes.to_vec()).map_err(|(_, err)| eyre::eyre!(err))
+}
+
+/// Converts a slice of [RawTransaction] into an [ssz_rs::List] of [Transaction]
+fn to_tx_list(txs: &[RawTransaction]) -> Result<List<Transaction, 1048576>> {
+    let txs = txs
+        .iter()
+        .map(|tx| to_byte_list(&tx.0))
+        .collect::<Result<Vec<_>>>()?;
+
+    List::try_from(txs).map_err(|(_, err)| eyre::eyre!(err))
+}
@@ -1,4 +1,6 @@
 /// A module for managing incoming p2p gossip messages
 pub mod handlers;
+/// A module to record unsafe blocks received via gossip to disk, and replay them
+pub mod recorder;
 /// A module for managing the Discv5 discovery & libp2p services
 pub mod service;
@@ -0,0 +1,300 @@
+use std::{
+    fmt,
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::Path,
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+use crate::engine::ExecutionPayload;
+
+use super::handlers::block_handler::{decode_payload, encode_payload};
+
+/// The file format of a block recording
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordFormat {
+    /// One JSON [BlockRecord] per line
+    Jsonl,
+    /// Back to back binary records: the receive time in milliseconds as a u64, the payload
+    /// version and the SSZ length as u32s, all little endian, followed by the SSZ payload
+    Ssz,
+}
+
+impl RecordFormat {
+    /// Returns the format of a recording from its file extension: [RecordFormat::Ssz] for
+    /// `.ssz` files, and [RecordFormat::Jsonl] otherwise
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("ssz") => Self::Ssz,
+            _ => Self::Jsonl,
+        }
+    }
+}
+
+impl FromStr for RecordFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "jsonl" => Ok(Self::Jsonl),
+            "ssz" => Ok(Self::Ssz),
+            _ => Err("invalid record format".to_string()),
+        }
+    }
+}
+
+impl fmt::Display for RecordFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Jsonl => write!(f, "jsonl"),
+            Self::Ssz => write!(f, "ssz"),
+        }
+    }
+}
+
+/// An unsafe block received via gossip
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockRecord {
+    /// When the block was received, in milliseconds since the unix epoch
+    pub received_at: u64,
+    /// The block
+    pub payload: ExecutionPayload,
+}
+
+/// Writes validated unsafe blocks to a file as they are received
+pub struct BlockRecorder {
+    /// The file the blocks are written to
+    file: BufWriter<File>,
+    /// The format blocks are written in
+    format: RecordFormat,
+}
+
+impl BlockRecorder {
+    /// Creates a new [BlockRecorder], creating or truncating the file at `path`
+    pub fn create(path: &Path, format: RecordFormat) -> Result<Self> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        Ok(Self {
+            file: BufWriter::new(File::create(path)?),
+            format,
+        })
+    }
+
+    /// Records a block received now
+    pub fn record(&mut self, payload: &ExecutionPayload) -> Result<()> {
+        let received_at = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64;
+        self.write(&BlockRecord {
+            received_at,
+            payload: payload.clone(),
+        })
+    }
+
+    /// Writes a [BlockRecord] and flushes it to disk
+    pub fn write(&mut self, record: &BlockRecord) -> Result<()> {
+        match self.format {
+            RecordFormat::Jsonl => {
+                serde_json::to_writer(&mut self.file, record)?;
+                self.file.write_all(b"\n")?;
+            }
+            RecordFormat::Ssz => {
+                let (version, data) = encode_payload(&record.payload, None)?;
+                self.file.write_all(&record.received_at.to_le_bytes())?;
+                self.file.write_all(&version.to_le_bytes())?;
+                self.file.write_all(&(data.len() as u32).to_le_bytes())?;
+                self.file.write_all(&data)?;
+            }
+        }
+
+        self.file.flush()?;
+        Ok(())
+    }
+}
+
+/// Reads every [BlockRecord] in a recording
+pub fn read_records(path: &Path, format: RecordFormat) -> Result<Vec<BlockRecord>> {
+    match format {
+        RecordFormat::Jsonl => fs::read_to_string(path)?
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| Ok(serde_json::from_str(line)?))
+            .collect(),
+        RecordFormat::Ssz => {
+            let bytes = fs::read(path)?;
+            let mut records = Vec::new();
+            let mut rest = bytes.as_slice();
+
+            while !rest.is_empty() {
+                if rest.len() < 16 {
+                    eyre::bail!("truncated block record");
+                }
+
+                let received_at = u64::from_le_bytes(rest[..8].try_into()?);
+                let version = u32::from_le_bytes(rest[8..12].try_into()?);
+                let len = u32::from_le_bytes(rest[12..16].try_into()?) as usize;
+                let data = rest
+                    .get(16..16 + len)
+                    .ok_or(eyre::eyre!("truncated block record"))?;
+
+                let (payload, _) = decode_payload(version, data)?;
+                records.push(BlockRecord {
+                    received_at,
+                    payload,
+                });
+                rest = &rest[16 + len..];
+            }
+
+            Ok(records)
+        }
+    }
+}
+
+/// Records every block sent on `blocks` with a [BlockRecorder], and forwards it to the
+/// returned receiver
+pub fn record_blocks(
+    mut recorder: BlockRecorder,
+    mut blocks: UnboundedReceiver<ExecutionPayload>,
+) -> UnboundedReceiver<ExecutionPayload> {
+    let (sender, recv) = unbounded_channel();
+
+    tokio::spawn(async move {
+        while let Some(payload) = blocks.recv().await {
+            if let Err(err) = recorder.record(&payload) {
+                tracing::warn!("failed to record unsafe block: {}", err);
+            }
+
+            if sender.send(payload).is_err() {
+                break;
+            }
+        }
+    });
+
+    recv
+}
+
+/// Replays a recording as if its blocks were received via gossip
+pub struct BlockReplay {
+    /// The recorded blocks
+    records: Vec<BlockRecord>,
+    /// How many times faster than recorded the blocks are sent. Sent without delay if zero.
+    speed: u32,
+    /// A channel sender to forward the replayed blocks
+    block_sender: UnboundedSender<ExecutionPayload>,
+}
+
+impl BlockReplay {
+    /// Creates a new [BlockReplay] of the recording at `path`, along with the receiver of the
+    /// replayed blocks
+    pub fn new(path: &Path, speed: u32) -> Result<(Self, UnboundedReceiver<ExecutionPayload>)> {
+        let records = read_records(path, RecordFormat::from_path(path))?;
+        let (sender, recv) = unbounded_channel();
+
+        let replay = Self {
+            records,
+            speed,
+            block_sender: sender,
+        };
+
+        Ok((replay, recv))
+    }
+
+    /// Sends the recorded blocks, spaced out by the time between their receipt divided by
+    /// the replay speed
+    pub fn start(self) {
+        tracing::info!(
+            "replaying {} unsafe blocks at {}x speed",
+            self.records.len(),
+            self.speed
+        );
+
+        tokio::spawn(async move {
+            let mut last_received = None;
+
+            for record in self.records {
+                if let (Some(last), true) = (last_received, self.speed > 0) {
+                    let gap = record.received_at.saturating_sub(last);
+                    tokio::time::sleep(Duration::from_millis(gap / self.speed as u64)).await;
+                }
+                last_received = Some(record.received_at);
+
+                if self.block_sender.send(record.payload).is_err() {
+                    break;
+                }
+            }
+
+            tracing::info!("finished replaying unsafe blocks");
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::H256;
+
+    use crate::engine::ExecutionPayload;
+
+    use super::{read_records, BlockRecord, BlockRecorder, BlockReplay, RecordFormat};
+
+    fn record(number: u64, received_at: u64) -> BlockRecord {
+        BlockRecord {
+            received_at,
+            payload: ExecutionPayload {
+                block_hash: H256::random(),
+                block_number: number.into(),
+                logs_bloom: vec![0; 256].into(),
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn test_record_blocks() {
+        let dir = tempfile::tempdir().unwrap();
+        let records = vec![record(42, 1_700_000_000_000), record(43, 1_700_000_002_000)];
+
+        for (file, format) in [
+            ("blocks.jsonl", RecordFormat::Jsonl),
+            ("blocks.ssz", RecordFormat::Ssz),
+        ] {
+            let path = dir.path().join(file);
+            assert_eq!(RecordFormat::from_path(&path), format);
+
+            let mut recorder = BlockRecorder::create(&path, format).unwrap();
+            records.iter().for_each(|r| recorder.write(r).unwrap());
+
+            let read = read_records(&path, format).unwrap();
+            assert_eq!(read.len(), 2);
+            for (read, record) in read.iter().zip(&records) {
+                assert_eq!(read.received_at, record.received_at);
+                assert_eq!(read.payload.block_hash, record.payload.block_hash);
+                assert_eq!(read.payload.block_number, record.payload.block_number);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_replay_blocks() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("blocks.jsonl");
+        let records = vec![record(1, 0), record(2, 2_000), record(3, 4_000)];
+
+        let mut recorder = BlockRecorder::create(&path, RecordFormat::Jsonl).unwrap();
+        records.iter().for_each(|r| recorder.write(r).unwrap());
+
+        let (replay, mut blocks) = BlockReplay::new(&path, 0).unwrap();
+        replay.start();
+
+        for record in &records {
+            let payload = blocks.recv().await.unwrap();
+            assert_eq!(payload.block_hash, record.payload.block_hash);
+        }
+        assert!(blocks.recv().await.is_none());
+    }
+}
@@ -0,0 +1,430 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use discv5::enr::{CombinedKey, Enr};
+use eyre::Result;
+use libp2p::{gossipsub::TopicHash, Multiaddr, PeerId, Swarm};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, oneshot};
+
+use super::{
+    discovery::{self, Discovery},
+    peers::{Direction, PeerManager},
+    Behaviour,
+};
+
+/// How long to wait for the swarm task to answer a [Command]
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A request sent to the [super::Service] swarm task, answered on a oneshot channel
+#[derive(Debug)]
+pub enum Command {
+    /// Returns the [PeerInfo] of the node itself
+    LocalPeer(oneshot::Sender<PeerInfo>),
+    /// Returns a [PeerDump] of the connected peers, or of all known peers if false
+    Peers(bool, oneshot::Sender<PeerDump>),
+    /// Returns the [PeerStats] of the node
+    PeerStats(oneshot::Sender<PeerStats>),
+    /// Returns the ENRs in the discovery routing table
+    DiscoveryTable(oneshot::Sender<Vec<String>>),
+    /// Blocks a peer until it is unblocked, disconnecting it
+    BlockPeer(PeerId, oneshot::Sender<()>),
+    /// Unblocks a blocked or banned peer
+    UnblockPeer(PeerId, oneshot::Sender<()>),
+    /// Dials a peer
+    ConnectPeer(Multiaddr, oneshot::Sender<Result<()>>),
+}
+
+/// Sends [Command]s to the [super::Service] swarm task. Used by the `opp2p` RPC namespace to
+/// inspect and control the node's networking.
+#[derive(Debug, Clone)]
+pub struct NetworkHandle {
+    /// Channel to send [Command]s to the swarm task
+    sender: mpsc::Sender<Command>,
+    /// Set once the swarm task is running and answering [Command]s
+    started: Arc<AtomicBool>,
+}
+
+impl NetworkHandle {
+    /// Creates a new [NetworkHandle], along with the [CommandReceiver] to pass to the
+    /// [super::Service]
+    pub fn new() -> (Self, CommandReceiver) {
+        let (sender, receiver) = mpsc::channel(64);
+        let started = Arc::new(AtomicBool::new(false));
+        let commands = CommandReceiver {
+            receiver,
+            started: started.clone(),
+        };
+
+        (Self { sender, started }, commands)
+    }
+
+    /// Returns the [PeerInfo] of the node itself
+    pub async fn local_peer(&self) -> Result<PeerInfo> {
+        self.request(Command::LocalPeer).await
+    }
+
+    /// Returns a [PeerDump] of the connected peers, or of all known peers if `connected` is false
+    pub async fn peers(&self, connected: bool) -> Result<PeerDump> {
+        self.request(|respond| Command::Peers(connected, respond))
+            .await
+    }
+
+    /// Returns the [PeerStats] of the node
+    pub async fn peer_stats(&self) -> Result<PeerStats> {
+        self.request(Command::PeerStats).await
+    }
+
+    /// Returns the ENRs in the discovery routing table
+    pub async fn discovery_table(&self) -> Result<Vec<String>> {
+        self.request(Command::DiscoveryTable).await
+    }
+
+    /// Blocks a peer until it is unblocked, disconnecting it
+    pub async fn block_peer(&self, peer: PeerId) -> Result<()> {
+        self.request(|respond| Command::BlockPeer(peer, respond))
+            .await
+    }
+
+    /// Unblocks a blocked or banned peer
+    pub async fn unblock_peer(&self, peer: PeerId) -> Result<()> {
+        self.request(|respond| Command::UnblockPeer(peer, respond))
+            .await
+    }
+
+    /// Dials a peer
+    pub async fn connect_peer(&self, addr: Multiaddr) -> Result<()> {
+        self.request(|respond| Command::ConnectPeer(addr, respond))
+            .await?
+    }
+
+    /// Sends a [Command] and waits for the swarm task to answer it. Fails if networking has not
+    /// started yet.
+    async fn request<T>(&self, command: impl FnOnce(oneshot::Sender<T>) -> Command) -> Result<T> {
+        let (respond, recv) = oneshot::channel();
+        let not_running = || eyre::eyre!("p2p service is not running");
+
+        if !self.started.load(Ordering::Relaxed) {
+            return Err(not_running());
+        }
+
+        self.sender
+            .try_send(command(respond))
+            .map_err(|_| not_running())?;
+
+        tokio::time::timeout(COMMAND_TIMEOUT, recv)
+            .await
+            .map_err(|_| not_running())?
+            .map_err(|_| not_running())
+    }
+}
+
+/// Receives the [Command]s sent by a [NetworkHandle]. Until it is started, the handle fails
+/// requests immediately instead of waiting for an answer.
+#[derive(Debug)]
+pub struct CommandReceiver {
+    /// Channel to receive [Command]s from the [NetworkHandle]
+    receiver: mpsc::Receiver<Command>,
+    /// Shared with the [NetworkHandle]
+    started: Arc<AtomicBool>,
+}
+
+impl CommandReceiver {
+    /// Marks the swarm task as running, so the [NetworkHandle] sends it [Command]s
+    pub fn start(&self) {
+        self.started.store(true, Ordering::Relaxed);
+    }
+
+    /// Receives the next [Command], or `None` once every [NetworkHandle] is dropped
+    pub async fn recv(&mut self) -> Option<Command> {
+        self.receiver.recv().await
+    }
+}
+
+/// The details of a peer, matching the op-node `opp2p` API
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeerInfo {
+    /// The libp2p peer ID
+    #[serde(rename = "peerID")]
+    pub peer_id: String,
+    /// The discovery node ID, if known
+    #[serde(rename = "nodeID")]
+    pub node_id: String,
+    /// The peer's user agent, if known
+    pub user_agent: String,
+    /// The peer's protocol version, if known
+    pub protocol_version: String,
+    /// The peer's ENR, if it was found by discovery
+    #[serde(rename = "ENR")]
+    pub enr: String,
+    /// The peer's multiaddrs
+    pub addresses: Vec<String>,
+    /// The libp2p protocols the peer supports
+    pub protocols: Vec<String>,
+    /// 0 if not connected, 1 if connected
+    pub connectedness: u8,
+    /// 0 if unknown, 1 if the peer dialed the node, 2 if the node dialed the peer
+    pub direction: u8,
+    /// Whether the peer is protected from being disconnected. Always false.
+    pub protected: bool,
+    /// The chain ID of the network
+    #[serde(rename = "chainID")]
+    pub chain_id: u64,
+    /// The last ping round trip time in nanoseconds
+    pub latency: u64,
+    /// Whether the peer is subscribed to a block topic
+    pub gossip_blocks: bool,
+    /// The peer's scores
+    pub scores: PeerScores,
+}
+
+/// The scores of a peer
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PeerScores {
+    /// The gossipsub scores of the peer
+    pub gossip: GossipScores,
+}
+
+/// The gossipsub scores of a peer
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GossipScores {
+    /// The total gossipsub peer score
+    pub total: f64,
+}
+
+/// The known peers of the node, matching the op-node `opp2p_peers` response
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeerDump {
+    /// The number of connected peers
+    pub total_connected: u64,
+    /// The known peers by peer ID
+    pub peers: HashMap<String, PeerInfo>,
+    /// The peer IDs of the blocked and banned peers
+    pub banned_peers: Vec<String>,
+    /// Banned IP addresses. IPs are never banned, so this is always empty.
+    #[serde(rename = "bannedIPS")]
+    pub banned_ips: Vec<String>,
+    /// Banned subnets. Subnets are never banned, so this is always empty.
+    pub banned_subnets: Vec<String>,
+}
+
+/// Peer counts of the node, matching the op-node `opp2p_peerStats` response
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeerStats {
+    /// The number of connected peers
+    pub connected: u64,
+    /// The number of nodes in the discovery routing table
+    pub table: u64,
+    /// The number of peers subscribed to the v1 block topic
+    pub blocks_topic: u64,
+    /// The number of peers subscribed to the v2 block topic
+    pub blocks_topic_v2: u64,
+    /// The number of peers subscribed to the v3 block topic
+    pub blocks_topic_v3: u64,
+    /// The number of blocked and banned peers
+    pub banned: u64,
+    /// The number of connected and discovered peers
+    pub known: u64,
+}
+
+/// Answers a [Command] from the state of the swarm, the [PeerManager] and [Discovery]
+pub(super) fn handle_command(
+    command: Command,
+    swarm: &mut Swarm<Behaviour>,
+    peer_manager: &mut PeerManager,
+    discovery: &Discovery,
+    chain_id: u64,
+) {
+    match command {
+        Command::LocalPeer(respond) => {
+            let enr = discovery.local_enr.borrow().clone();
+            let addresses = swarm
+                .listeners()
+                .chain(swarm.external_addresses().map(|record| &record.addr))
+                .map(|addr| addr.to_string())
+                .collect();
+
+            _ = respond.send(PeerInfo {
+                peer_id: swarm.local_peer_id().to_string(),
+                node_id: hex::encode(enr.node_id().raw()),
+                enr: enr.to_base64(),
+                addresses,
+                protocols: Behaviour::protocols(chain_id),
+                connectedness: 1,
+                chain_id,
+                ..Default::default()
+            });
+        }
+        Command::Peers(connected, respond) => {
+            let table = discovery.table.borrow().clone();
+            let mut peers = connected_peers(swarm, peer_manager, &table, chain_id);
+
+            if !connected {
+                for enr in &table {
+                    if let Some(peer_id) = discovery::peer_id(enr) {
+                        peers
+                            .entry(peer_id.to_string())
+                            .or_insert_with(|| discovered_peer(peer_id, enr, chain_id));
+                    }
+                }
+            }
+
+            _ = respond.send(PeerDump {
+                total_connected: swarm.connected_peers().count() as u64,
+                peers,
+                banned_peers: peer_manager.banned().map(|p| p.to_string()).collect(),
+                ..Default::default()
+            });
+        }
+        Command::PeerStats(respond) => {
+            let gossipsub = &swarm.behaviour().gossipsub;
+            let topic_peers = |version: &str| {
+                let suffix = format!("/{}/blocks", version);
+                gossipsub
+                    .all_peers()
+                    .filter(|(_, topics)| topics.iter().any(|t| t.as_str().ends_with(&suffix)))
+                    .count() as u64
+            };
+
+            let table = discovery.table.borrow();
+            let connected = swarm.connected_peers().count() as u64;
+            let discovered = table
+                .iter()
+                .filter_map(discovery::peer_id)
+                .filter(|peer| !swarm.is_connected(peer))
+                .count() as u64;
+
+            _ = respond.send(PeerStats {
+                connected,
+                table: table.len() as u64,
+                blocks_topic: topic_peers("0"),
+                blocks_topic_v2: topic_peers("1"),
+                blocks_topic_v3: topic_peers("2"),
+                banned: peer_manager.banned().count() as u64,
+                known: connected + discovered,
+            });
+        }
+        Command::DiscoveryTable(respond) => {
+            let table = discovery.table.borrow();
+            _ = respond.send(table.iter().map(|enr| enr.to_base64()).collect());
+        }
+        Command::BlockPeer(peer, respond) => {
+            peer_manager.block(swarm, peer);
+            _ = respond.send(());
+        }
+        Command::UnblockPeer(peer, respond) => {
+            peer_manager.unblock(swarm, peer);
+            _ = respond.send(());
+        }
+        Command::ConnectPeer(addr, respond) => {
+            let res = swarm.dial(addr).map_err(eyre::Report::from);
+            _ = respond.send(res);
+        }
+    }
+}
+
+/// Returns the [PeerInfo] of every connected peer by peer ID
+fn connected_peers(
+    swarm: &Swarm<Behaviour>,
+    peer_manager: &PeerManager,
+    table: &[Enr<CombinedKey>],
+    chain_id: u64,
+) -> HashMap<String, PeerInfo> {
+    let gossipsub = &swarm.behaviour().gossipsub;
+    let topics = gossipsub
+        .all_peers()
+        .map(|(peer, topics)| (*peer, topics.into_iter().cloned().collect::<Vec<_>>()))
+        .collect::<HashMap<PeerId, Vec<TopicHash>>>();
+
+    peer_manager
+        .connections()
+        .map(|(peer_id, connection)| {
+            let enr = table
+                .iter()
+                .find(|enr| discovery::peer_id(enr).as_ref() == Some(peer_id));
+            let gossip_blocks = topics
+                .get(peer_id)
+                .map(|topics| topics.iter().any(|t| t.as_str().ends_with("/blocks")))
+                .unwrap_or_default();
+
+            let identity = connection.identity.as_ref();
+            let mut addresses = vec![connection.addr.to_string()];
+            if let Some(identity) = identity {
+                addresses.extend(identity.listen_addrs.iter().map(|addr| addr.to_string()));
+                addresses.dedup();
+            }
+
+            let info = PeerInfo {
+                peer_id: peer_id.to_string(),
+                node_id: enr
+                    .map(|enr| hex::encode(enr.node_id().raw()))
+                    .unwrap_or_default(),
+                enr: enr.map(|enr| enr.to_base64()).unwrap_or_default(),
+                user_agent: identity
+                    .map(|identity| identity.agent_version.clone())
+                    .unwrap_or_default(),
+                protocol_version: identity
+                    .map(|identity| identity.protocol_version.clone())
+                    .unwrap_or_default(),
+                addresses,
+                protocols: identity
+                    .map(|identity| identity.protocols.clone())
+                    .unwrap_or_default(),
+                connectedness: 1,
+                direction: match connection.direction {
+                    Direction::Inbound => 1,
+                    Direction::Outbound => 2,
+                },
+                chain_id,
+                latency: connection
+                    .latency
+                    .map(|latency| latency.as_nanos() as u64)
+                    .unwrap_or_default(),
+                gossip_blocks,
+                scores: PeerScores {
+                    gossip: GossipScores {
+                        total: gossipsub.peer_score(peer_id).unwrap_or_default(),
+                    },
+                },
+                ..Default::default()
+            };
+
+            (peer_id.to_string(), info)
+        })
+        .collect()
+}
+
+/// Returns the [PeerInfo] of a peer that was found by discovery but is not connected
+fn discovered_peer(peer_id: PeerId, enr: &Enr<CombinedKey>, chain_id: u64) -> PeerInfo {
+    let addresses = match (enr.ip4(), enr.tcp4()) {
+        (Some(ip), Some(port)) => vec![format!("/ip4/{}/tcp/{}", ip, port)],
+        _ => Vec::new(),
+    };
+
+    PeerInfo {
+        peer_id: peer_id.to_string(),
+        node_id: hex::encode(enr.node_id().raw()),
+        enr: enr.to_base64(),
+        addresses,
+        chain_id,
+        ..Default::default()
+    }
+}
+
+/// Receives the next [Command]. Waits forever if there is no receiver, and returns `None` once
+/// every [NetworkHandle] is dropped.
+pub(super) async fn next_command(commands: &mut Option<CommandReceiver>) -> Option<Command> {
+    match commands {
+        Some(commands) => commands.recv().await,
+        None => std::future::pending().await,
+    }
+}
@@ -1,82 +1,342 @@
-use std::{str::FromStr, time::Duration};
+use std::{
+    collections::HashMap,
+    fs,
+    net::{Ipv4Addr, SocketAddr},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, Instant},
+};
 
 use discv5::{
-    enr::{CombinedKey, Enr, EnrBuilder, NodeId},
-    Discv5, Discv5Config,
+    enr::{CombinedKey, CombinedPublicKey, Enr, EnrBuilder, EnrPublicKey, NodeId},
+    Discv5, Discv5Config, Discv5ConfigBuilder, Discv5Event,
 };
 use ethers::utils::rlp;
 use eyre::Result;
+use libp2p::{multiaddr::Protocol, Multiaddr, PeerId};
+use libp2p_identity::{secp256k1, PublicKey};
 use tokio::{
-    sync::mpsc::{self, Receiver},
-    time::sleep,
+    sync::{
+        mpsc::{self, Receiver},
+        watch,
+    },
+    time::interval,
 };
 use unsigned_varint::{decode, encode};
 
-use super::types::{NetworkAddress, Peer};
+use super::{
+    nat::{self, PortMapping, PortProtocol},
+    types::{NetworkAddress, Peer},
+};
+
+/// How often the routing table is written to disk
+const TABLE_SAVE_INTERVAL: Duration = Duration::from_secs(60);
+/// How often a random lookup is run to find new peers
+const LOOKUP_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Settings for the [Discv5] discovery service
+pub struct DiscoveryConfig {
+    /// The UDP address to listen on
+    pub addr: NetworkAddress,
+    /// The chain ID of the network
+    pub chain_id: u64,
+    /// The key used to sign the node's ENR
+    pub key: CombinedKey,
+    /// The TCP port libp2p listens on
+    pub tcp_port: u16,
+    /// The IP address advertised in the node's ENR. If unset, it is found with UPnP if
+    /// enabled, and voted on by the peers that discovery talks to.
+    pub advertise_ip: Option<Ipv4Addr>,
+    /// The TCP port advertised in the node's ENR
+    pub advertise_tcp_port: u16,
+    /// The UDP port advertised in the node's ENR
+    pub advertise_udp_port: u16,
+    /// Whether to forward the advertised ports to the listen ports with UPnP
+    pub upnp: bool,
+    /// Bootnodes to start discovery from. The built-in bootnodes are used if empty.
+    pub bootnodes: Vec<Enr<CombinedKey>>,
+    /// The file the routing table is persisted to, if any
+    pub table_path: Option<PathBuf>,
+}
+
+/// A running [Discv5] discovery service
+pub struct Discovery {
+    /// Receives the peers found by discovery
+    pub peers: Receiver<Peer>,
+    /// The node's own [Enr], updated when its external address changes
+    pub local_enr: watch::Receiver<Enr<CombinedKey>>,
+    /// The ENRs in the routing table, updated after every lookup
+    pub table: watch::Receiver<Vec<Enr<CombinedKey>>>,
+}
 
 /// Starts the [Discv5] discovery service and continually tries to find new peers.
-/// Returns a [Receiver] to receive [Peer] structs
-pub fn start(addr: NetworkAddress, chain_id: u64) -> Result<Receiver<Peer>> {
-    let bootnodes = bootnodes();
-    let mut disc = create_disc(chain_id)?;
+/// Returns a [Discovery] to receive [Peer] structs and inspect the routing table
+pub fn start(config: DiscoveryConfig) -> Result<Discovery> {
+    let DiscoveryConfig {
+        addr,
+        chain_id,
+        key,
+        tcp_port,
+        advertise_ip,
+        advertise_tcp_port,
+        advertise_udp_port,
+        upnp,
+        bootnodes,
+        table_path,
+    } = config;
+
+    let bootnodes = if bootnodes.is_empty() {
+        self::bootnodes()
+    } else {
+        bootnodes
+    };
+    let saved = table_path.as_deref().map(load_table).unwrap_or_default();
+    let mut disc = create_disc(
+        chain_id,
+        key,
+        advertise_ip,
+        advertise_tcp_port,
+        advertise_udp_port,
+    )?;
 
     let (sender, recv) = mpsc::channel::<Peer>(256);
+    let (table_sender, table) = watch::channel(Vec::new());
+    let (local_enr_sender, local_enr) = watch::channel(disc.local_enr());
 
     tokio::spawn(async move {
-        bootnodes.into_iter().for_each(|enr| _ = disc.add_enr(enr));
+        tracing::info!("loaded {} peers from the discovery table", saved.len());
+        bootnodes
+            .into_iter()
+            .chain(saved)
+            .for_each(|enr| _ = disc.add_enr(enr));
+
+        if upnp {
+            let mappings = vec![
+                PortMapping {
+                    protocol: PortProtocol::Tcp,
+                    external_port: advertise_tcp_port,
+                    internal_port: tcp_port,
+                },
+                PortMapping {
+                    protocol: PortProtocol::Udp,
+                    external_port: advertise_udp_port,
+                    internal_port: addr.port,
+                },
+            ];
+
+            match nat::map_ports(mappings).await {
+                Ok(ip) if advertise_ip.is_none() => {
+                    disc.update_local_enr_socket(
+                        SocketAddr::new(ip.into(), advertise_tcp_port),
+                        true,
+                    );
+                    disc.update_local_enr_socket(
+                        SocketAddr::new(ip.into(), advertise_udp_port),
+                        false,
+                    );
+                    _ = local_enr_sender.send(disc.local_enr());
+                }
+                Ok(_) => (),
+                Err(err) => tracing::warn!("failed to map ports with upnp: {}", err),
+            }
+        }
+
         disc.start(addr.into()).await.unwrap();
+        let mut events = disc.event_stream().await.unwrap();
 
         tracing::info!("started peer discovery");
 
+        let mut last_save = Instant::now();
+        let mut lookup = interval(LOOKUP_INTERVAL);
+
         loop {
-            let target = NodeId::random();
-            match disc.find_node(target).await {
-                Ok(nodes) => {
-                    let peers = nodes
-                        .iter()
-                        .filter(|node| is_valid_node(node, chain_id))
-                        .flat_map(Peer::try_from);
-
-                    for peer in peers {
-                        _ = sender.send(peer).await;
+            tokio::select! {
+                _ = lookup.tick() => {
+                    if let Some(path) = &table_path {
+                        if last_save.elapsed() >= TABLE_SAVE_INTERVAL {
+                            if let Err(err) = save_table(path, &disc.table_entries_enr()) {
+                                tracing::warn!("failed to save discovery table {:?}: {}", path, err);
+                            }
+                            last_save = Instant::now();
+                        }
                     }
-                }
-                Err(err) => {
-                    tracing::warn!("discovery error: {:?}", err);
-                }
-            }
 
-            sleep(Duration::from_secs(10)).await;
+                    let target = NodeId::random();
+                    match disc.find_node(target).await {
+                        Ok(nodes) => {
+                            let peers = nodes
+                                .iter()
+                                .filter(|node| is_valid_node(node, chain_id))
+                                .flat_map(Peer::try_from);
+
+                            for peer in peers {
+                                _ = sender.send(peer).await;
+                            }
+                        }
+                        Err(err) => {
+                            tracing::warn!("discovery error: {:?}", err);
+                        }
+                    }
+
+                    _ = table_sender.send(disc.table_entries_enr());
+                },
+                Some(event) = events.recv() => {
+                    if let Discv5Event::SocketUpdated(addr) = event {
+                        tracing::info!("external address voted by peers: {}", addr);
+                        _ = local_enr_sender.send(disc.local_enr());
+                    }
+                },
+            }
         }
     });
 
-    Ok(recv)
+    Ok(Discovery {
+        peers: recv,
+        local_enr,
+        table,
+    })
+}
+
+/// Returns the libp2p [PeerId] of the node an [Enr] belongs to
+pub fn peer_id(enr: &Enr<CombinedKey>) -> Option<PeerId> {
+    match enr.public_key() {
+        CombinedPublicKey::Secp256k1(key) => {
+            let key = secp256k1::PublicKey::try_from_bytes(&key.encode()).ok()?;
+            Some(PeerId::from(PublicKey::from(key)))
+        }
+        CombinedPublicKey::Ed25519(_) => None,
+    }
 }
 
 /// Returns `true` if a node [Enr] contains an `opstack` key and is on the same network.
 fn is_valid_node(node: &Enr<CombinedKey>, chain_id: u64) -> bool {
-    node.get_raw_rlp("opstack")
-        .map(|opstack| {
-            OpStackEnrData::try_from(opstack)
-                .map(|opstack| opstack.chain_id == chain_id && opstack.version == 0)
-                .unwrap_or_default()
-        })
+    opstack_data(node)
+        .map(|opstack| opstack.chain_id == chain_id && opstack.version == 0)
         .unwrap_or_default()
 }
 
-/// Generates an [Enr] and creates a [Discv5] service struct
-fn create_disc(chain_id: u64) -> Result<Discv5> {
+/// Decodes the `opstack` field of an [Enr], if it has one
+fn opstack_data(node: &Enr<CombinedKey>) -> Option<OpStackEnrData> {
+    node.get_raw_rlp("opstack")
+        .and_then(|opstack| OpStackEnrData::try_from(opstack).ok())
+}
+
+/// Returns the L2 chain ID in the `opstack` field of an [Enr], if it has one
+pub fn opstack_chain_id(node: &Enr<CombinedKey>) -> Option<u64> {
+    opstack_data(node).map(|opstack| opstack.chain_id)
+}
+
+/// Returns the libp2p [Multiaddr] of the node an [Enr] belongs to, if it has an IPv4
+/// address and TCP port
+pub fn enr_multiaddr(enr: &Enr<CombinedKey>) -> Option<Multiaddr> {
+    let addr = NetworkAddress::try_from(enr).ok()?;
+    let peer_id = peer_id(enr)?;
+
+    Some(Multiaddr::from(addr).with(Protocol::P2p(peer_id.into())))
+}
+
+/// Walks the discovery network for `duration` with random lookups, starting from the given
+/// bootnodes or the built-in bootnodes. Returns every node found, on any chain.
+pub async fn crawl(
+    listen: SocketAddr,
+    bootnodes: Vec<Enr<CombinedKey>>,
+    duration: Duration,
+) -> Result<Vec<Enr<CombinedKey>>> {
+    let key = CombinedKey::generate_secp256k1();
+    let enr = EnrBuilder::new("v4").build(&key)?;
+    let mut disc: Discv5 = Discv5::new(enr, key, Discv5Config::default())
+        .map_err(|_| eyre::eyre!("could not create disc service"))?;
+
+    let bootnodes = if bootnodes.is_empty() {
+        self::bootnodes()
+    } else {
+        bootnodes
+    };
+    bootnodes.into_iter().for_each(|enr| _ = disc.add_enr(enr));
+
+    disc.start(listen)
+        .await
+        .map_err(|err| eyre::eyre!("could not start discovery: {:?}", err))?;
+
+    let mut found = HashMap::new();
+    let deadline = Instant::now() + duration;
+
+    while Instant::now() < deadline {
+        match disc.find_node(NodeId::random()).await {
+            Ok(nodes) => nodes.into_iter().for_each(|enr| {
+                found.insert(enr.node_id(), enr);
+            }),
+            Err(err) => tracing::warn!("discovery error: {:?}", err),
+        }
+
+        for enr in disc.table_entries_enr() {
+            found.insert(enr.node_id(), enr);
+        }
+
+        tracing::info!("crawled {} nodes", found.len());
+    }
+
+    disc.shutdown();
+    Ok(found.into_values().collect())
+}
+
+/// Reads the ENRs saved in a routing table file, one per line. Returns no ENRs if the file
+/// does not exist.
+fn load_table(path: &Path) -> Vec<Enr<CombinedKey>> {
+    let Ok(table) = fs::read_to_string(path) else {
+        return Vec::new();
+    };
+
+    table
+        .lines()
+        .filter_map(|enr| Enr::from_str(enr.trim()).ok())
+        .collect()
+}
+
+/// Writes the ENRs in the routing table to a file, one per line
+fn save_table(path: &Path, enrs: &[Enr<CombinedKey>]) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let table = enrs
+        .iter()
+        .map(|enr| enr.to_base64())
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    fs::write(path, table)?;
+    Ok(())
+}
+
+/// Generates an [Enr] and creates a [Discv5] service struct. The ENR always carries the
+/// advertised ports. Without an advertised IP, the IP is voted on by the peers discovery
+/// talks to, and the ENR is updated once enough of them agree.
+fn create_disc(
+    chain_id: u64,
+    key: CombinedKey,
+    advertise_ip: Option<Ipv4Addr>,
+    tcp_port: u16,
+    udp_port: u16,
+) -> Result<Discv5> {
     let opstack = OpStackEnrData {
         chain_id,
         version: 0,
     };
     let opstack_data: Vec<u8> = opstack.into();
 
-    let key = CombinedKey::generate_secp256k1();
-    let enr = EnrBuilder::new("v4")
-        .add_value_rlp("opstack", opstack_data.into())
-        .build(&key)?;
-    let config = Discv5Config::default();
+    let mut builder = EnrBuilder::new("v4");
+    builder.add_value_rlp("opstack", opstack_data.into());
+    builder.tcp4(tcp_port).udp4(udp_port);
+
+    let mut config = Discv5ConfigBuilder::new();
+    if let Some(ip) = advertise_ip {
+        builder.ip4(ip);
+        config.disable_enr_update();
+    }
+
+    let enr = builder.build(&key)?;
+    let config = config.build();
 
     Discv5::new(enr, key, config).map_err(|_| eyre::eyre!("could not create disc service"))
 }
@@ -138,3 +398,37 @@ fn bootnodes() -> Vec<Enr<CombinedKey>> {
         .filter_map(|enr| Enr::from_str(enr).ok())
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use libp2p::PeerId;
+
+    use crate::network::service::identity::{discovery_key, load_keypair};
+
+    use super::{bootnodes, create_disc, load_table, peer_id, save_table};
+
+    #[test]
+    fn test_save_and_load_table() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("p2p").join("discovery-10.txt");
+
+        assert!(load_table(&path).is_empty());
+
+        let enrs = bootnodes();
+        save_table(&path, &enrs).unwrap();
+        assert_eq!(load_table(&path), enrs);
+    }
+
+    #[test]
+    fn test_enr_peer_id() {
+        let keypair = load_keypair(None).unwrap();
+        let disc = create_disc(10, discovery_key(&keypair).unwrap(), None, 9876, 9876).unwrap();
+
+        assert_eq!(
+            peer_id(&disc.local_enr()),
+            Some(PeerId::from(keypair.public()))
+        );
+        assert_eq!(disc.local_enr().tcp4(), Some(9876));
+        assert_eq!(disc.local_enr().ip4(), None);
+    }
+}
@@ -0,0 +1,70 @@
+use std::{fs, path::Path};
+
+use discv5::enr::CombinedKey;
+use eyre::Result;
+use libp2p_identity::{secp256k1, Keypair};
+
+/// Loads the node's secp256k1 key from a hex encoded file, generating and writing a new key
+/// if the file does not exist. Without a path, a new key is generated on every start.
+pub fn load_keypair(path: Option<&Path>) -> Result<Keypair> {
+    let Some(path) = path else {
+        return Ok(Keypair::generate_secp256k1());
+    };
+
+    if path.exists() {
+        let encoded = fs::read_to_string(path)?;
+        let bytes = hex::decode(encoded.trim().trim_start_matches("0x"))?;
+        let secret = secp256k1::SecretKey::try_from_bytes(bytes)?;
+        return Ok(secp256k1::Keypair::from(secret).into());
+    }
+
+    let keypair = secp256k1::Keypair::generate();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, hex::encode(keypair.secret().to_bytes()))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+    }
+
+    tracing::info!("generated new p2p node key at {:?}", path);
+    Ok(keypair.into())
+}
+
+/// Converts the node's [Keypair] into the [CombinedKey] used to sign its discovery record, so
+/// that peers derive the same peer ID from its ENR as from its libp2p connections.
+pub fn discovery_key(keypair: &Keypair) -> Result<CombinedKey> {
+    let keypair = keypair
+        .clone()
+        .try_into_secp256k1()
+        .map_err(|_| eyre::eyre!("node key must be secp256k1"))?;
+
+    let mut secret = keypair.secret().to_bytes();
+    Ok(CombinedKey::secp256k1_from_bytes(&mut secret)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use libp2p::PeerId;
+
+    use super::{discovery_key, load_keypair};
+
+    #[test]
+    fn test_load_keypair() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("p2p").join("node_key");
+
+        let keypair = load_keypair(Some(&path)).unwrap();
+        assert!(path.exists());
+
+        let reloaded = load_keypair(Some(&path)).unwrap();
+        assert_eq!(
+            PeerId::from(keypair.public()),
+            PeerId::from(reloaded.public())
+        );
+        assert!(discovery_key(&reloaded).is_ok());
+    }
+}
@@ -1,34 +1,116 @@
-use std::{net::SocketAddr, time::Duration};
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    path::PathBuf,
+    str::FromStr,
+    time::{Duration, Instant},
+};
 
+use discv5::enr::{CombinedKey, Enr};
 use eyre::Result;
 use futures::{prelude::*, select};
 use libp2p::{
-    gossipsub::{self, IdentTopic, Message, MessageId},
-    mplex::MplexConfig,
-    noise, ping,
-    swarm::{NetworkBehaviour, SwarmBuilder, SwarmEvent},
-    tcp, Multiaddr, PeerId, Swarm, Transport,
+    allow_block_list, connection_limits,
+    core::{muxing::StreamMuxerBox, transport::Boxed, upgrade::ProtocolName},
+    gossipsub::{self, IdentTopic, Message, MessageAcceptance, MessageId},
+    identify,
+    multiaddr::Protocol,
+    ping, request_response,
+    swarm::{AddressScore, NetworkBehaviour, SwarmBuilder, SwarmEvent},
+    Multiaddr, PeerId, Swarm,
 };
 use libp2p_identity::Keypair;
 use openssl::sha::sha256;
+use tokio::time::interval;
+
+use crate::{
+    config::{Config, Muxer},
+    version::Version,
+};
 
+use self::{
+    discovery::DiscoveryConfig,
+    payload_by_number::{PayloadByNumberCodec, PayloadByNumberProtocol, PayloadByNumberResponse},
+    peers::PeerManager,
+};
 use super::{handlers::Handler, service::types::NetworkAddress};
 
+/// A module for the commands used to inspect and control the swarm task
+mod commands;
+pub use commands::{
+    Command, CommandReceiver, GossipScores, NetworkHandle, PeerDump, PeerInfo, PeerScores,
+    PeerStats,
+};
 /// A module to handle peer discovery
 mod discovery;
+pub use discovery::{crawl, enr_multiaddr, opstack_chain_id};
+/// A module to load the node's persistent identity
+mod identity;
+/// A module for the `payload_by_number` request-response protocol
+mod payload_by_number;
+pub use payload_by_number::PayloadSync;
+/// A module to forward ports on the local internet gateway with UPnP
+mod nat;
+/// A module to limit the number of peers and ban misbehaving peers
+mod peers;
+/// A module to check that a single peer is reachable
+mod reachability;
+pub use reachability::{ping_peer, PingResult};
+/// A module for the gossipsub peer scoring parameters
+mod scoring;
+/// A module to build the libp2p transport
+mod transport;
 /// A module to handle commonly used types in the p2p system.
 mod types;
 
+/// How often disconnected static peers are dialed again
+const STATIC_PEER_REDIAL_INTERVAL: Duration = Duration::from_secs(30);
+/// How often peer scores are checked and peer metrics are updated
+const PEER_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
+/// The protocol version sent to peers with [libp2p::identify]
+const IDENTIFY_PROTOCOL_VERSION: &str = "/opstack/1.0.0";
+/// The protocol ID prefix [libp2p::gossipsub] is configured with
+const GOSSIPSUB_PROTOCOL_PREFIX: &str = "meshsub";
+/// The gossipsub versions served under [GOSSIPSUB_PROTOCOL_PREFIX]
+const GOSSIPSUB_PROTOCOL_VERSIONS: [&str; 2] = ["1.1.0", "1.0.0"];
+
 /// Responsible for management of the `Discv5` & `libp2p` services.
 pub struct Service {
     /// Handles validation & processing of inbound messages
     handlers: Vec<Box<dyn Handler>>,
     /// The socket address that the service is listening on.
     addr: SocketAddr,
+    /// The UDP port that peer discovery listens on
+    discovery_port: u16,
+    /// The IP address advertised to peers. Found by UPnP or voted on by peers if unset.
+    advertise_ip: Option<Ipv4Addr>,
+    /// The TCP and UDP ports advertised to peers. Default to the listen ports.
+    advertise_ports: Option<(u16, u16)>,
+    /// Whether to forward the advertised ports with UPnP
+    upnp: bool,
     /// The chain ID of the network
     chain_id: u64,
+    /// The L2 block time in seconds, used to scale the gossipsub peer scores
+    block_time: u64,
+    /// The number of peers to dial up to
+    target_peers: u32,
+    /// The maximum number of connected peers
+    max_peers: u32,
+    /// The stream multiplexers to negotiate over TCP, in order of preference
+    muxers: Vec<Muxer>,
+    /// The UDP port QUIC listens on, if enabled
+    quic_port: Option<u16>,
     /// A unique keypair to validate the node's identity
     keypair: Option<Keypair>,
+    /// Peers to always stay connected to
+    static_peers: Vec<(PeerId, Multiaddr)>,
+    /// Bootnodes to start discovery from, replacing the built-in bootnodes if set
+    bootnodes: Vec<Enr<CombinedKey>>,
+    /// The file the discovery routing table is persisted to
+    discovery_path: Option<PathBuf>,
+    /// Serves and sends `payload_by_number` requests
+    payload_sync: Option<PayloadSync>,
+    /// Receives [Command]s from a [NetworkHandle]
+    commands: Option<CommandReceiver>,
 }
 
 impl Service {
@@ -37,11 +119,77 @@ impl Service {
         Self {
             handlers: Vec::new(),
             addr,
+            discovery_port: addr.port(),
+            advertise_ip: None,
+            advertise_ports: None,
+            upnp: false,
             chain_id,
+            block_time: 2,
+            target_peers: 20,
+            max_peers: 30,
+            muxers: vec![Muxer::Yamux, Muxer::Mplex],
+            quic_port: None,
             keypair: None,
+            static_peers: Vec::new(),
+            bootnodes: Vec::new(),
+            discovery_path: None,
+            payload_sync: None,
+            commands: None,
         }
     }
 
+    /// Creates a new [Service] from the p2p settings in the given [Config]. The node key and
+    /// the discovery routing table are persisted in the `p2p` directory of the data dir.
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let p2p = &config.p2p;
+        let chain_id = config.chain.l2_chain_id;
+        let p2p_dir = config.data_dir.as_ref().map(|dir| dir.join("p2p"));
+
+        let key_path = p2p_dir.as_ref().map(|dir| dir.join("node_key"));
+        let keypair = identity::load_keypair(key_path.as_deref())?;
+
+        let static_peers = p2p
+            .static_peers
+            .iter()
+            .map(|addr| Multiaddr::from_str(addr).map_err(eyre::Report::from))
+            .collect::<Result<Vec<_>>>()?;
+
+        let bootnodes = p2p
+            .bootnodes
+            .iter()
+            .map(|enr| Enr::from_str(enr).map_err(|err| eyre::eyre!("invalid bootnode: {}", err)))
+            .collect::<Result<Vec<_>>>()?;
+
+        let addr = SocketAddr::new(p2p.listen_ip.into(), p2p.listen_tcp_port);
+        let mut service = Self::new(addr, chain_id)
+            .set_keypair(keypair)
+            .set_discovery_port(p2p.listen_udp_port)
+            .set_block_time(config.chain.blocktime)
+            .set_peer_limits(p2p.target_peers, p2p.max_peers)
+            .set_muxers(p2p.muxers.clone())
+            .set_advertise_ports(
+                p2p.advertise_tcp_port.unwrap_or(p2p.listen_tcp_port),
+                p2p.advertise_udp_port.unwrap_or(p2p.listen_udp_port),
+            )
+            .set_upnp(p2p.upnp)
+            .set_static_peers(static_peers)?
+            .set_bootnodes(bootnodes);
+
+        if let Some(port) = p2p.quic_port {
+            service = service.set_quic_port(port);
+        }
+
+        if let Some(ip) = p2p.advertise_ip {
+            service = service.set_advertise_ip(ip);
+        }
+
+        if let Some(dir) = p2p_dir {
+            service = service.set_discovery_path(dir.join(format!("discovery-{}.txt", chain_id)));
+        }
+
+        Ok(service)
+    }
+
     /// Adds a handler to [Service]
     pub fn add_handler(mut self, handler: Box<dyn Handler>) -> Self {
         self.handlers.push(handler);
@@ -54,37 +202,228 @@ impl Service {
         self
     }
 
+    /// Sets the UDP port peer discovery listens on. Defaults to the TCP listen port.
+    pub fn set_discovery_port(mut self, port: u16) -> Self {
+        self.discovery_port = port;
+        self
+    }
+
+    /// Sets the L2 block time in seconds, which the gossipsub peer scores are scaled to
+    pub fn set_block_time(mut self, block_time: u64) -> Self {
+        self.block_time = block_time;
+        self
+    }
+
+    /// Sets the number of peers to dial up to, and the maximum number of connected peers
+    pub fn set_peer_limits(mut self, target_peers: u32, max_peers: u32) -> Self {
+        self.target_peers = target_peers;
+        self.max_peers = max_peers;
+        self
+    }
+
+    /// Sets the stream multiplexers to negotiate over TCP, in order of preference
+    pub fn set_muxers(mut self, muxers: Vec<Muxer>) -> Self {
+        self.muxers = muxers;
+        self
+    }
+
+    /// Enables QUIC, listening on the given UDP port
+    pub fn set_quic_port(mut self, port: u16) -> Self {
+        self.quic_port = Some(port);
+        self
+    }
+
+    /// Sets the IP address advertised to peers in the node's ENR, instead of finding it with
+    /// UPnP or peer votes
+    pub fn set_advertise_ip(mut self, ip: Ipv4Addr) -> Self {
+        self.advertise_ip = Some(ip);
+        self
+    }
+
+    /// Sets the TCP and UDP ports advertised to peers in the node's ENR. Defaults to the
+    /// listen ports.
+    pub fn set_advertise_ports(mut self, tcp_port: u16, udp_port: u16) -> Self {
+        self.advertise_ports = Some((tcp_port, udp_port));
+        self
+    }
+
+    /// Sets whether to forward the advertised ports to the listen ports with UPnP
+    pub fn set_upnp(mut self, upnp: bool) -> Self {
+        self.upnp = upnp;
+        self
+    }
+
+    /// Sets the peers to always stay connected to. Each address must end with the peer's
+    /// `/p2p/` ID.
+    pub fn set_static_peers(mut self, peers: Vec<Multiaddr>) -> Result<Self> {
+        self.static_peers = peers
+            .into_iter()
+            .map(|addr| {
+                let peer_id = match addr.iter().last() {
+                    Some(Protocol::P2p(hash)) => PeerId::from_multihash(hash).ok(),
+                    _ => None,
+                };
+
+                peer_id
+                    .map(|peer_id| (peer_id, addr.clone()))
+                    .ok_or(eyre::eyre!("static peer {} has no peer id", addr))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(self)
+    }
+
+    /// Sets the bootnodes to start discovery from, replacing the built-in bootnodes
+    pub fn set_bootnodes(mut self, bootnodes: Vec<Enr<CombinedKey>>) -> Self {
+        self.bootnodes = bootnodes;
+        self
+    }
+
+    /// Sets the file the discovery routing table is persisted to and reloaded from
+    pub fn set_discovery_path(mut self, path: PathBuf) -> Self {
+        self.discovery_path = Some(path);
+        self
+    }
+
+    /// Sets the [PayloadSync] used to serve and send `payload_by_number` requests
+    pub fn set_payload_sync(mut self, payload_sync: PayloadSync) -> Self {
+        self.payload_sync = Some(payload_sync);
+        self
+    }
+
+    /// Sets the receiver of the [Command]s sent by a [NetworkHandle]
+    pub fn set_commands(mut self, commands: CommandReceiver) -> Self {
+        self.commands = Some(commands);
+        self
+    }
+
     /// Starts the Discv5 peer discovery & libp2p services
     /// and continually listens for new peers and messages to handle
     pub fn start(mut self) -> Result<()> {
+        if self.quic_port == Some(self.discovery_port) {
+            eyre::bail!(
+                "quic port {} is already used for discovery",
+                self.discovery_port
+            );
+        }
+
         let addr = NetworkAddress::try_from(self.addr)?;
         let keypair = self.keypair.unwrap_or_else(Keypair::generate_secp256k1);
+        let (advertise_tcp_port, advertise_udp_port) = self
+            .advertise_ports
+            .unwrap_or((addr.port, self.discovery_port));
 
-        let mut swarm = create_swarm(keypair, &self.handlers)?;
-        let mut peer_recv = discovery::start(addr, self.chain_id)?;
+        let mut discovery = discovery::start(DiscoveryConfig {
+            addr: NetworkAddress {
+                ip: addr.ip,
+                port: self.discovery_port,
+            },
+            chain_id: self.chain_id,
+            key: identity::discovery_key(&keypair)?,
+            tcp_port: addr.port,
+            advertise_ip: self.advertise_ip,
+            advertise_tcp_port,
+            advertise_udp_port,
+            upnp: self.upnp,
+            bootnodes: self.bootnodes,
+            table_path: self.discovery_path,
+        })?;
+        let mut peer_manager = PeerManager::new(self.target_peers, self.max_peers);
+        let transport = transport::build(&keypair, &self.muxers, self.quic_port.is_some())?;
+        let mut swarm = create_swarm(
+            keypair,
+            transport,
+            &self.handlers,
+            self.chain_id,
+            self.block_time,
+            &peer_manager,
+        )?;
 
         let multiaddr = Multiaddr::from(addr);
         swarm
             .listen_on(multiaddr)
             .map_err(|_| eyre::eyre!("swarm listen failed"))?;
 
+        if let Some(port) = self.quic_port {
+            let multiaddr = Multiaddr::from(addr.ip)
+                .with(Protocol::Udp(port))
+                .with(Protocol::QuicV1);
+            swarm
+                .listen_on(multiaddr)
+                .map_err(|_| eyre::eyre!("quic listen failed"))?;
+        }
+
         let mut handlers = Vec::new();
         handlers.append(&mut self.handlers);
 
+        let mut payload_sync = self
+            .payload_sync
+            .unwrap_or_else(|| PayloadSync::new(None).0);
+
+        update_external_address(&mut swarm, &discovery.local_enr.borrow());
+
+        let static_peers = self.static_peers;
+        let mut redial = interval(STATIC_PEER_REDIAL_INTERVAL);
+        let mut heartbeat = interval(PEER_HEARTBEAT_INTERVAL);
+        let mut commands = self.commands;
+        let chain_id = self.chain_id;
+
+        if let Some(commands) = &commands {
+            commands.start();
+        }
+
         tokio::spawn(async move {
             loop {
                 select! {
-                    peer = peer_recv.recv().fuse() => {
+                    peer = discovery.peers.recv().fuse() => {
                         if let Some(peer) = peer {
-                            let peer = Multiaddr::from(peer);
-                            _ = swarm.dial(peer);
+                            if peer_manager.should_dial(swarm.connected_peers().count()) {
+                                _ = swarm.dial(Multiaddr::from(peer));
+                            }
+                        }
+                    },
+                    _ = redial.tick().fuse() => {
+                        for (peer_id, addr) in &static_peers {
+                            if !swarm.is_connected(peer_id) {
+                                _ = swarm.dial(addr.clone());
+                            }
                         }
                     },
-                    event = swarm.select_next_some() => {
-                        if let SwarmEvent::Behaviour(event) = event {
-                            event.handle(&mut swarm, &handlers);
+                    res = discovery.local_enr.changed().fuse() => {
+                        if res.is_ok() {
+                            update_external_address(&mut swarm, &discovery.local_enr.borrow());
                         }
                     },
+                    _ = heartbeat.tick().fuse() => {
+                        peer_manager.heartbeat(&mut swarm, Instant::now());
+                    },
+                    command = commands::next_command(&mut commands).fuse() => match command {
+                        Some(command) => commands::handle_command(
+                            command,
+                            &mut swarm,
+                            &mut peer_manager,
+                            &discovery,
+                            chain_id,
+                        ),
+                        None => commands = None,
+                    },
+                    event = payload_sync.next_event().fuse() => {
+                        payload_sync.handle_event(event, &mut swarm.behaviour_mut().payload_by_number);
+                    },
+                    event = swarm.select_next_some() => match event {
+                        SwarmEvent::Behaviour(event) => {
+                            event.handle(&mut swarm, &handlers, &mut payload_sync, &mut peer_manager);
+                        }
+                        SwarmEvent::ConnectionEstablished { peer_id, endpoint, .. } => {
+                            payload_sync.peer_connected(peer_id);
+                            peer_manager.peer_connected(peer_id, &endpoint);
+                        }
+                        SwarmEvent::ConnectionClosed { peer_id, num_established: 0, .. } => {
+                            payload_sync.peer_disconnected(&peer_id);
+                            peer_manager.peer_disconnected(&peer_id);
+                        }
+                        _ => (),
+                    },
                 }
             }
         });
@@ -93,6 +432,26 @@ impl Service {
     }
 }
 
+/// Advertises the IP address and TCP port in the node's [Enr] to peers via
+/// [libp2p::identify], replacing the previous external address
+fn update_external_address(swarm: &mut Swarm<Behaviour>, enr: &Enr<CombinedKey>) {
+    let Some(socket) = enr.tcp4_socket() else {
+        return;
+    };
+
+    let previous = swarm
+        .external_addresses()
+        .map(|record| record.addr.clone())
+        .collect::<Vec<_>>();
+    previous
+        .iter()
+        .for_each(|addr| _ = swarm.remove_external_address(addr));
+
+    let addr = Multiaddr::from(*socket.ip()).with(Protocol::Tcp(socket.port()));
+    tracing::info!("advertising external address {}", addr);
+    swarm.add_external_address(addr, AddressScore::Infinite);
+}
+
 /// Computes the message ID of a `gossipsub` message
 fn compute_message_id(msg: &Message) -> MessageId {
     let mut decoder = snap::raw::Decoder::new();
@@ -121,14 +480,15 @@ fn compute_message_id(msg: &Message) -> MessageId {
 }
 
 /// Creates the libp2p [Swarm]
-fn create_swarm(keypair: Keypair, handlers: &[Box<dyn Handler>]) -> Result<Swarm<Behaviour>> {
-    let transport = tcp::tokio::Transport::new(tcp::Config::default())
-        .upgrade(libp2p::core::upgrade::Version::V1Lazy)
-        .authenticate(noise::Config::new(&keypair)?)
-        .multiplex(MplexConfig::default())
-        .boxed();
-
-    let behaviour = Behaviour::new(handlers)?;
+fn create_swarm(
+    keypair: Keypair,
+    transport: Boxed<(PeerId, StreamMuxerBox)>,
+    handlers: &[Box<dyn Handler>],
+    chain_id: u64,
+    block_time: u64,
+    peer_manager: &PeerManager,
+) -> Result<Swarm<Behaviour>> {
+    let behaviour = Behaviour::new(&keypair, handlers, chain_id, block_time, peer_manager)?;
 
     Ok(
         SwarmBuilder::with_tokio_executor(transport, behaviour, PeerId::from(keypair.public()))
@@ -144,14 +504,34 @@ struct Behaviour {
     ping: ping::Behaviour,
     /// Adds [libp2p::gossipsub] to enable gossipsub as the routing layer
     gossipsub: gossipsub::Behaviour,
+    /// Adds [libp2p::identify] to exchange agent versions, protocols and addresses with peers
+    identify: identify::Behaviour,
+    /// Adds the `payload_by_number` protocol to request and serve unsafe blocks
+    payload_by_number: request_response::Behaviour<PayloadByNumberCodec>,
+    /// Denies connections to and from banned peers
+    blocked_peers: allow_block_list::Behaviour<allow_block_list::BlockedPeers>,
+    /// Denies connections beyond the max peer count
+    connection_limits: connection_limits::Behaviour,
 }
 
 impl Behaviour {
     /// Configures the swarm behaviors, subscribes to the gossip topics, and returns a new [Behaviour]
-    fn new(handlers: &[Box<dyn Handler>]) -> Result<Self> {
+    fn new(
+        keypair: &Keypair,
+        handlers: &[Box<dyn Handler>],
+        chain_id: u64,
+        block_time: u64,
+        peer_manager: &PeerManager,
+    ) -> Result<Self> {
         let ping = ping::Behaviour::default();
 
+        let identify = identify::Behaviour::new(
+            identify::Config::new(IDENTIFY_PROTOCOL_VERSION.to_string(), keypair.public())
+                .with_agent_version(Version::build().to_string()),
+        );
+
         let gossipsub_config = gossipsub::ConfigBuilder::default()
+            .protocol_id_prefix(GOSSIPSUB_PROTOCOL_PREFIX)
             .mesh_n(8)
             .mesh_n_low(6)
             .mesh_n_high(12)
@@ -171,6 +551,19 @@ impl Behaviour {
             gossipsub::Behaviour::new(gossipsub::MessageAuthenticity::Anonymous, gossipsub_config)
                 .map_err(|_| eyre::eyre!("gossipsub behaviour creation failed"))?;
 
+        let topics = handlers
+            .iter()
+            .flat_map(|handler| handler.topics())
+            .map(|topic| IdentTopic::new(topic.to_string()).hash())
+            .collect::<Vec<_>>();
+
+        gossipsub
+            .with_peer_score(
+                scoring::peer_score_params(block_time, &topics),
+                scoring::peer_score_thresholds(),
+            )
+            .map_err(|err| eyre::eyre!("gossipsub peer scoring failed: {}", err))?;
+
         handlers
             .iter()
             .flat_map(|handler| {
@@ -187,24 +580,83 @@ impl Behaviour {
             })
             .collect::<Result<Vec<bool>>>()?;
 
-        Ok(Self { ping, gossipsub })
+        let payload_by_number = request_response::Behaviour::new(
+            PayloadByNumberCodec,
+            [(
+                PayloadByNumberProtocol::new(chain_id),
+                request_response::ProtocolSupport::Full,
+            )],
+            request_response::Config::default(),
+        );
+
+        Ok(Self {
+            ping,
+            gossipsub,
+            identify,
+            payload_by_number,
+            blocked_peers: allow_block_list::Behaviour::default(),
+            connection_limits: connection_limits::Behaviour::new(peer_manager.connection_limits()),
+        })
+    }
+
+    /// Returns the protocols the node accepts inbound streams for, as advertised to peers
+    /// by identify
+    fn protocols(chain_id: u64) -> Vec<String> {
+        let identify = [
+            identify::PROTOCOL_NAME.as_slice(),
+            identify::PUSH_PROTOCOL_NAME,
+        ];
+        let gossipsub = GOSSIPSUB_PROTOCOL_VERSIONS
+            .iter()
+            .map(|version| format!("/{}/{}", GOSSIPSUB_PROTOCOL_PREFIX, version));
+        let payload_by_number = PayloadByNumberProtocol::new(chain_id);
+
+        identify
+            .into_iter()
+            .chain([ping::PROTOCOL_NAME, payload_by_number.protocol_name()])
+            .map(|protocol| String::from_utf8_lossy(protocol).to_string())
+            .chain(gossipsub)
+            .collect()
     }
 }
 
 /// The type of message received
 enum Event {
     /// Represents a [ping::Event]
-    #[allow(dead_code)]
     Ping(ping::Event),
     /// Represents a [gossipsub::Event]
     Gossipsub(gossipsub::Event),
+    /// Represents an [identify::Event]
+    Identify(Box<identify::Event>),
+    /// Represents a `payload_by_number` [request_response::Event]
+    PayloadByNumber(request_response::Event<u64, PayloadByNumberResponse>),
 }
 
 impl Event {
-    /// Handles received gossipsub messages. Ping messages are ignored.
+    /// Handles received gossipsub messages and `payload_by_number` requests, and records ping latencies.
     /// Reports back to [libp2p::gossipsub] to apply peer scoring and forward the message to other peers if accepted.
-    fn handle(self, swarm: &mut Swarm<Behaviour>, handlers: &[Box<dyn Handler>]) {
-        if let Self::Gossipsub(gossipsub::Event::Message {
+    /// Peers that propagate rejected messages are banned.
+    fn handle(
+        self,
+        swarm: &mut Swarm<Behaviour>,
+        handlers: &[Box<dyn Handler>],
+        payload_sync: &mut PayloadSync,
+        peer_manager: &mut PeerManager,
+    ) {
+        if let Self::Ping(ping::Event {
+            peer,
+            result: Ok(ping::Success::Ping { rtt }),
+        }) = self
+        {
+            peer_manager.record_latency(&peer, rtt);
+        } else if let Self::Identify(event) = self {
+            if let identify::Event::Received { peer_id, info } = *event {
+                peer_manager.record_identity(&peer_id, info);
+            }
+        } else if let Self::PayloadByNumber(event) = self {
+            payload_sync
+                .handle_behaviour_event(event, &mut swarm.behaviour_mut().payload_by_number);
+        } else if let Self::Gossipsub(gossipsub::Event::Message {
             propagation_source,
             message_id,
             message,
@@ -215,11 +667,16 @@ impl Event {
                 .find(|h| h.topics().contains(&message.topic));
             if let Some(handler) = handler {
                 let status = handler.handle(message);
+                let rejected = matches!(status, MessageAcceptance::Reject);
 
                 _ = swarm
                     .behaviour_mut()
                     .gossipsub
                     .report_message_validation_result(&message_id, &propagation_source, status);
+
+                if rejected {
+                    peer_manager.ban(swarm, propagation_source, Instant::now());
+                }
             }
         }
     }
@@ -238,3 +695,98 @@ impl From<gossipsub::Event> for Event {
         Event::Gossipsub(value)
     }
 }
+
+impl From<identify::Event> for Event {
+    /// Converts [identify::Event] to [Event]
+    fn from(value: identify::Event) -> Self {
+        Event::Identify(Box::new(value))
+    }
+}
+
+impl From<request_response::Event<u64, PayloadByNumberResponse>> for Event {
+    /// Converts a `payload_by_number` [request_response::Event] to [Event]
+    fn from(value: request_response::Event<u64, PayloadByNumberResponse>) -> Self {
+        Event::PayloadByNumber(value)
+    }
+}
+
+impl From<void::Void> for Event {
+    /// The [allow_block_list] and [connection_limits] behaviours emit no events
+    fn from(value: void::Void) -> Self {
+        void::unreachable(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libp2p::{
+        core::upgrade::ProtocolName,
+        swarm::{handler::UpgradeInfoSend, ConnectionHandler, ConnectionId, NetworkBehaviour},
+        Multiaddr,
+    };
+    use libp2p_identity::Keypair;
+
+    use crate::config::{Config, P2PConfig};
+
+    use super::{peers::PeerManager, Behaviour, Service};
+
+    #[test]
+    fn test_quic_port_conflict() {
+        let p2p = P2PConfig::default();
+        let config = Config {
+            p2p: P2PConfig {
+                quic_port: Some(p2p.listen_udp_port),
+                ..p2p
+            },
+            ..Default::default()
+        };
+
+        let err = Service::from_config(&config).unwrap().start().unwrap_err();
+        assert!(err.to_string().contains("quic port"));
+
+        let addr = "0.0.0.0:9876".parse().unwrap();
+        let err = Service::new(addr, 10)
+            .set_discovery_port(9000)
+            .set_quic_port(9000)
+            .start()
+            .unwrap_err();
+        assert!(err.to_string().contains("quic port"));
+    }
+
+    #[test]
+    fn test_protocols() {
+        let keypair = Keypair::generate_secp256k1();
+        let peer_manager = PeerManager::new(20, 30);
+        let mut behaviour = Behaviour::new(&keypair, &[], 10, 2, &peer_manager).unwrap();
+
+        // The advertised protocols are the ones the handlers accept
+        let addr = Multiaddr::empty();
+        let handler = behaviour
+            .handle_established_inbound_connection(
+                ConnectionId::new_unchecked(0),
+                keypair.public().to_peer_id(),
+                &addr,
+                &addr,
+            )
+            .unwrap();
+        let mut served = handler
+            .listen_protocol()
+            .upgrade()
+            .protocol_info()
+            .map(|protocol| String::from_utf8_lossy(protocol.protocol_name()).to_string())
+            .collect::<Vec<_>>();
+        let mut protocols = Behaviour::protocols(10);
+        served.sort();
+        protocols.sort();
+        assert_eq!(served, protocols);
+
+        for protocol in [
+            "/ipfs/id/1.0.0",
+            "/ipfs/ping/1.0.0",
+            "/meshsub/1.1.0",
+            "/opstack/req/payload_by_number/10/0",
+        ] {
+            assert!(protocols.iter().any(|p| p == protocol), "{}", protocol);
+        }
+    }
+}
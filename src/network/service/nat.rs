@@ -0,0 +1,126 @@
+use std::{
+    fmt,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+use eyre::Result;
+use igd_next::{
+    aio::{
+        tokio::{search_gateway, Tokio},
+        Gateway,
+    },
+    PortMappingProtocol, SearchOptions,
+};
+use tokio::net::UdpSocket;
+
+/// How long to wait for a gateway to answer the SSDP search
+const SEARCH_TIMEOUT: Duration = Duration::from_secs(3);
+/// How long port mappings are leased for. They are renewed at half this.
+const LEASE_DURATION: Duration = Duration::from_secs(3600);
+
+/// The transport protocol of a port mapping
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PortProtocol {
+    /// A TCP port
+    Tcp,
+    /// A UDP port
+    Udp,
+}
+
+impl fmt::Display for PortProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tcp => write!(f, "TCP"),
+            Self::Udp => write!(f, "UDP"),
+        }
+    }
+}
+
+impl From<PortProtocol> for PortMappingProtocol {
+    fn from(protocol: PortProtocol) -> Self {
+        match protocol {
+            PortProtocol::Tcp => Self::TCP,
+            PortProtocol::Udp => Self::UDP,
+        }
+    }
+}
+
+/// A port forwarded from the gateway to this node
+#[derive(Debug, Clone, Copy)]
+pub struct PortMapping {
+    /// The transport protocol
+    pub protocol: PortProtocol,
+    /// The port on the gateway's external IP
+    pub external_port: u16,
+    /// The local port the node listens on
+    pub internal_port: u16,
+}
+
+/// Maps ports on the local internet gateway with UPnP, and keeps renewing their leases in
+/// the background. Returns the gateway's external IP address.
+pub async fn map_ports(mappings: Vec<PortMapping>) -> Result<Ipv4Addr> {
+    let mut options = SearchOptions::default();
+    options.timeout = Some(SEARCH_TIMEOUT);
+    let gateway = search_gateway(options).await?;
+    let local_ip = local_ip(gateway.addr).await?;
+
+    let external_ip = match gateway.get_external_ip().await? {
+        IpAddr::V4(ip) => ip,
+        IpAddr::V6(ip) => eyre::bail!("gateway returned an ipv6 external ip {}", ip),
+    };
+
+    for mapping in &mappings {
+        add_port_mapping(&gateway, local_ip, mapping).await?;
+        tracing::info!(
+            "mapped {} port {}:{} to local port {} with upnp",
+            mapping.protocol,
+            external_ip,
+            mapping.external_port,
+            mapping.internal_port
+        );
+    }
+
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(LEASE_DURATION / 2).await;
+            for mapping in &mappings {
+                if let Err(err) = add_port_mapping(&gateway, local_ip, mapping).await {
+                    tracing::warn!("failed to renew upnp port mapping: {}", err);
+                }
+            }
+        }
+    });
+
+    Ok(external_ip)
+}
+
+/// Forwards a port to this node for [LEASE_DURATION]
+async fn add_port_mapping(
+    gateway: &Gateway<Tokio>,
+    local_ip: Ipv4Addr,
+    mapping: &PortMapping,
+) -> Result<()> {
+    gateway
+        .add_port(
+            mapping.protocol.into(),
+            mapping.external_port,
+            SocketAddr::new(local_ip.into(), mapping.internal_port),
+            LEASE_DURATION.as_secs() as u32,
+            "magi",
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Returns the local IP address used to reach the gateway
+async fn local_ip(gateway: SocketAddr) -> Result<Ipv4Addr> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await?;
+    socket.connect(gateway).await?;
+
+    match socket.local_addr()?.ip() {
+        IpAddr::V4(ip) => Ok(ip),
+        IpAddr::V6(_) => eyre::bail!("gateway is not reachable over ipv4"),
+    }
+}
@@ -0,0 +1,665 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    io::{self, Read, Write},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use ethers::{
+    providers::{Http, Middleware, Provider},
+    types::H256,
+};
+use eyre::Result;
+use futures::prelude::*;
+use libp2p::{
+    request_response::{self, Codec, ProtocolName, RequestId, ResponseChannel},
+    PeerId,
+};
+use tokio::{
+    sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+    time::{interval, Interval, MissedTickBehavior},
+};
+
+use crate::{
+    engine::ExecutionPayload,
+    network::handlers::block_handler::{decode_payload, encode_payload},
+};
+
+/// The result code of a successful response
+const RESULT_SUCCESS: u8 = 0;
+/// The result code sent when no payload exists at the requested number
+const RESULT_NOT_FOUND: u8 = 1;
+/// The result code sent when the payload could not be fetched for any other reason
+const RESULT_UNKNOWN: u8 = 3;
+
+/// The maximum size of an SSZ encoded payload, before and after compression
+const MAX_PAYLOAD_SIZE: u64 = 10 * 1024 * 1024;
+
+/// Requests per second sent to each peer
+const CLIENT_PEER_RATE: f64 = 3.0;
+/// Requests that can be sent to each peer at once
+const CLIENT_PEER_BURST: f64 = 3.0;
+/// Requests per second served to each peer
+const SERVER_PEER_RATE: f64 = 5.0;
+/// Requests that can be served to each peer at once
+const SERVER_PEER_BURST: f64 = 10.0;
+/// The longest an inbound request is delayed to stay within the peer's rate limit. Like
+/// op-node, requests that would wait longer are dropped without a response.
+const MAX_THROTTLE_DELAY: Duration = Duration::from_secs(20);
+
+/// The maximum number of requests waiting for a peer with capacity
+const MAX_PENDING_REQUESTS: usize = 1024;
+/// How often requests waiting for a peer are retried
+const DISPATCH_INTERVAL: Duration = Duration::from_millis(250);
+
+/// The `payload_by_number` protocol: `/opstack/req/payload_by_number/{chain_id}/0`
+#[derive(Debug, Clone)]
+pub struct PayloadByNumberProtocol(String);
+
+impl PayloadByNumberProtocol {
+    /// Creates the [PayloadByNumberProtocol] for the given chain
+    pub fn new(chain_id: u64) -> Self {
+        Self(format!("/opstack/req/payload_by_number/{}/0", chain_id))
+    }
+}
+
+impl ProtocolName for PayloadByNumberProtocol {
+    fn protocol_name(&self) -> &[u8] {
+        self.0.as_bytes()
+    }
+}
+
+/// A response to a `payload_by_number` request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PayloadByNumberResponse {
+    /// The payload at the requested number
+    Payload {
+        /// The canonical payload at the requested number
+        payload: Box<ExecutionPayload>,
+        /// The parent beacon block root, sent with post Ecotone payloads
+        parent_beacon_block_root: Option<H256>,
+    },
+    /// The peer has no payload at the requested number
+    NotFound,
+    /// The request failed, with the given result code
+    Error(u8),
+}
+
+/// Encodes `payload_by_number` requests as little endian block numbers, and responses as
+/// `<result><version><payload>`: a result code byte, a little endian `u32` payload version,
+/// and the snappy framed SSZ encoded payload. Only successful responses carry a version
+/// and payload.
+#[derive(Debug, Clone, Default)]
+pub struct PayloadByNumberCodec;
+
+#[async_trait]
+impl Codec for PayloadByNumberCodec {
+    type Protocol = PayloadByNumberProtocol;
+    type Request = u64;
+    type Response = PayloadByNumberResponse;
+
+    async fn read_request<T>(&mut self, _: &Self::Protocol, io: &mut T) -> io::Result<u64>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        let mut number = [0u8; 8];
+        io.read_exact(&mut number).await?;
+        Ok(u64::from_le_bytes(number))
+    }
+
+    async fn read_response<T>(
+        &mut self,
+        _: &Self::Protocol,
+        io: &mut T,
+    ) -> io::Result<PayloadByNumberResponse>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        let mut result = [0u8; 1];
+        io.read_exact(&mut result).await?;
+        match result[0] {
+            RESULT_SUCCESS => (),
+            RESULT_NOT_FOUND => return Ok(PayloadByNumberResponse::NotFound),
+            code => return Ok(PayloadByNumberResponse::Error(code)),
+        }
+
+        let mut version = [0u8; 4];
+        io.read_exact(&mut version).await?;
+        let version = u32::from_le_bytes(version);
+
+        let mut compressed = Vec::new();
+        io.take(MAX_PAYLOAD_SIZE + 1)
+            .read_to_end(&mut compressed)
+            .await?;
+
+        let mut data = Vec::new();
+        snap::read::FrameDecoder::new(compressed.as_slice())
+            .take(MAX_PAYLOAD_SIZE + 1)
+            .read_to_end(&mut data)?;
+
+        if compressed.len() as u64 > MAX_PAYLOAD_SIZE || data.len() as u64 > MAX_PAYLOAD_SIZE {
+            return Err(invalid_data("payload too large"));
+        }
+
+        let (payload, parent_beacon_block_root) =
+            decode_payload(version, &data).map_err(invalid_data)?;
+
+        Ok(PayloadByNumberResponse::Payload {
+            payload: Box::new(payload),
+            parent_beacon_block_root,
+        })
+    }
+
+    async fn write_request<T>(&mut self, _: &Self::Protocol, io: &mut T, req: u64) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        io.write_all(&req.to_le_bytes()).await
+    }
+
+    async fn write_response<T>(
+        &mut self,
+        _: &Self::Protocol,
+        io: &mut T,
+        res: PayloadByNumberResponse,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        let (payload, parent_beacon_block_root) = match res {
+            PayloadByNumberResponse::Payload {
+                payload,
+                parent_beacon_block_root,
+            } => (payload, parent_beacon_block_root),
+            PayloadByNumberResponse::NotFound => return io.write_all(&[RESULT_NOT_FOUND]).await,
+            PayloadByNumberResponse::Error(code) => return io.write_all(&[code]).await,
+        };
+
+        let (version, data) =
+            encode_payload(&payload, parent_beacon_block_root).map_err(invalid_data)?;
+
+        let mut encoder = snap::write::FrameEncoder::new(Vec::new());
+        encoder.write_all(&data)?;
+        let compressed = encoder.into_inner().map_err(|err| err.into_error())?;
+
+        io.write_all(&[RESULT_SUCCESS]).await?;
+        io.write_all(&version.to_le_bytes()).await?;
+        io.write_all(&compressed).await
+    }
+}
+
+/// Creates an [io::Error] for malformed messages
+fn invalid_data(err: impl ToString) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err.to_string())
+}
+
+/// A token bucket rate limiter for each peer
+#[derive(Debug)]
+pub struct PeerRateLimiter {
+    /// Tokens added per second
+    rate: f64,
+    /// The maximum number of tokens
+    burst: f64,
+    /// The tokens left for each peer, and when they were last updated
+    peers: HashMap<PeerId, (f64, Instant)>,
+}
+
+impl PeerRateLimiter {
+    /// Creates a new [PeerRateLimiter]
+    pub fn new(rate: f64, burst: f64) -> Self {
+        Self {
+            rate,
+            burst,
+            peers: HashMap::new(),
+        }
+    }
+
+    /// Takes a token for the peer, returning false if it has none left
+    pub fn try_acquire(&mut self, peer: &PeerId, now: Instant) -> bool {
+        let (tokens, updated) = self.peers.entry(*peer).or_insert((self.burst, now));
+
+        let elapsed = now.saturating_duration_since(*updated).as_secs_f64();
+        *tokens = (*tokens + elapsed * self.rate).min(self.burst);
+        *updated = now;
+
+        if *tokens >= 1.0 {
+            *tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Takes a token for the peer ahead of time, returning how long to wait until it is
+    /// available. Returns `None` without taking a token if that would exceed `max_delay`.
+    pub fn reserve(
+        &mut self,
+        peer: &PeerId,
+        now: Instant,
+        max_delay: Duration,
+    ) -> Option<Duration> {
+        let (tokens, updated) = self.peers.entry(*peer).or_insert((self.burst, now));
+
+        let elapsed = now.saturating_duration_since(*updated).as_secs_f64();
+        *tokens = (*tokens + elapsed * self.rate).min(self.burst);
+        *updated = now;
+
+        let delay = Duration::from_secs_f64((1.0 - *tokens).max(0.0) / self.rate);
+        if delay > max_delay {
+            return None;
+        }
+
+        *tokens -= 1.0;
+        Some(delay)
+    }
+
+    /// Stops tracking a peer
+    pub fn remove(&mut self, peer: &PeerId) {
+        self.peers.remove(peer);
+    }
+}
+
+/// An event for [PayloadSync] to handle
+pub enum SyncEvent {
+    /// A payload number was requested by the driver
+    Request(u64),
+    /// A response to an inbound request is ready to be sent
+    Served(
+        ResponseChannel<PayloadByNumberResponse>,
+        PayloadByNumberResponse,
+    ),
+    /// Requests waiting for a peer should be retried
+    Dispatch,
+}
+
+/// Serves and sends `payload_by_number` requests.
+///
+/// Block numbers requested by the driver are sent to connected peers within their rate
+/// limits, and the payloads received are forwarded back to the driver. Payloads are not
+/// signed, so the driver only applies them once they link to a block it already trusts.
+/// Inbound requests are served with canonical blocks from the L2 execution client.
+pub struct PayloadSync {
+    /// Provider for the local L2 execution RPC, used to serve requests
+    l2_provider: Option<Provider<Http>>,
+    /// Channel to receive payload numbers to request from
+    request_recv: UnboundedReceiver<u64>,
+    /// Channel to send received payloads to
+    payload_sender: UnboundedSender<ExecutionPayload>,
+    /// Channel to send served responses to, from the tasks fetching them
+    served_sender: UnboundedSender<(
+        ResponseChannel<PayloadByNumberResponse>,
+        PayloadByNumberResponse,
+    )>,
+    /// Channel to receive served responses from
+    served_recv: UnboundedReceiver<(
+        ResponseChannel<PayloadByNumberResponse>,
+        PayloadByNumberResponse,
+    )>,
+    /// Connected peers that have not rejected the protocol
+    peers: HashSet<PeerId>,
+    /// Payload numbers waiting for a peer with capacity
+    pending: VecDeque<u64>,
+    /// Payload numbers of requests waiting for a response
+    in_flight: HashMap<RequestId, u64>,
+    /// Rate limits for outbound requests
+    client_limits: PeerRateLimiter,
+    /// Rate limits for inbound requests
+    server_limits: PeerRateLimiter,
+    /// Timer to retry requests waiting for a peer
+    dispatch_interval: Option<Interval>,
+}
+
+impl PayloadSync {
+    /// Creates a new [PayloadSync], serving requests from the given L2 provider if set.
+    /// Returns a channel to request payload numbers, and a channel to receive the payloads.
+    pub fn new(
+        l2_provider: Option<Provider<Http>>,
+    ) -> (
+        Self,
+        UnboundedSender<u64>,
+        UnboundedReceiver<ExecutionPayload>,
+    ) {
+        let (request_sender, request_recv) = unbounded_channel();
+        let (payload_sender, payload_recv) = unbounded_channel();
+        let (served_sender, served_recv) = unbounded_channel();
+
+        let sync = Self {
+            l2_provider,
+            request_recv,
+            payload_sender,
+            served_sender,
+            served_recv,
+            peers: HashSet::new(),
+            pending: VecDeque::new(),
+            in_flight: HashMap::new(),
+            client_limits: PeerRateLimiter::new(CLIENT_PEER_RATE, CLIENT_PEER_BURST),
+            server_limits: PeerRateLimiter::new(SERVER_PEER_RATE, SERVER_PEER_BURST),
+            dispatch_interval: None,
+        };
+
+        (sync, request_sender, payload_recv)
+    }
+
+    /// Waits for the next [SyncEvent]
+    pub async fn next_event(&mut self) -> SyncEvent {
+        let dispatch_interval = self.dispatch_interval.get_or_insert_with(|| {
+            let mut dispatch_interval = interval(DISPATCH_INTERVAL);
+            dispatch_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            dispatch_interval
+        });
+
+        tokio::select! {
+            Some(number) = self.request_recv.recv() => SyncEvent::Request(number),
+            Some((channel, response)) = self.served_recv.recv() => {
+                SyncEvent::Served(channel, response)
+            }
+            _ = dispatch_interval.tick() => SyncEvent::Dispatch,
+        }
+    }
+
+    /// Handles a [SyncEvent]
+    pub fn handle_event(
+        &mut self,
+        event: SyncEvent,
+        behaviour: &mut request_response::Behaviour<PayloadByNumberCodec>,
+    ) {
+        match event {
+            SyncEvent::Request(number) => {
+                if !self.pending.contains(&number) && !self.in_flight.values().any(|n| *n == number)
+                {
+                    if self.pending.len() >= MAX_PENDING_REQUESTS {
+                        self.pending.pop_front();
+                    }
+                    self.pending.push_back(number);
+                }
+                self.dispatch(behaviour);
+            }
+            SyncEvent::Served(channel, response) => {
+                _ = behaviour.send_response(channel, response);
+            }
+            SyncEvent::Dispatch => self.dispatch(behaviour),
+        }
+    }
+
+    /// Tracks a new connection to a peer
+    pub fn peer_connected(&mut self, peer: PeerId) {
+        self.peers.insert(peer);
+    }
+
+    /// Stops tracking a peer once all its connections are closed
+    pub fn peer_disconnected(&mut self, peer: &PeerId) {
+        self.peers.remove(peer);
+        self.client_limits.remove(peer);
+        self.server_limits.remove(peer);
+    }
+
+    /// Handles an event from the request-response behaviour
+    pub fn handle_behaviour_event(
+        &mut self,
+        event: request_response::Event<u64, PayloadByNumberResponse>,
+        behaviour: &mut request_response::Behaviour<PayloadByNumberCodec>,
+    ) {
+        match event {
+            request_response::Event::Message { peer, message } => match message {
+                request_response::Message::Request {
+                    request, channel, ..
+                } => self.serve(peer, request, channel, behaviour),
+                request_response::Message::Response {
+                    request_id,
+                    response,
+                } => self.handle_response(peer, request_id, response),
+            },
+            request_response::Event::OutboundFailure {
+                peer,
+                request_id,
+                error,
+            } => {
+                tracing::debug!("payload request to {} failed: {}", peer, error);
+                self.in_flight.remove(&request_id);
+
+                if error == request_response::OutboundFailure::UnsupportedProtocols {
+                    self.peers.remove(&peer);
+                }
+            }
+            request_response::Event::InboundFailure { peer, error, .. } => {
+                tracing::debug!("payload request from {} failed: {}", peer, error);
+            }
+            request_response::Event::ResponseSent { .. } => (),
+        }
+    }
+
+    /// Sends waiting requests to peers with capacity left
+    fn dispatch(&mut self, behaviour: &mut request_response::Behaviour<PayloadByNumberCodec>) {
+        let now = Instant::now();
+
+        while let Some(number) = self.pending.front().copied() {
+            let peer = self
+                .peers
+                .iter()
+                .find(|peer| self.client_limits.try_acquire(peer, now))
+                .copied();
+
+            let Some(peer) = peer else {
+                break;
+            };
+
+            self.pending.pop_front();
+            let request_id = behaviour.send_request(&peer, number);
+            self.in_flight.insert(request_id, number);
+        }
+    }
+
+    /// Forwards a payload received from a peer to the driver if it has the requested number
+    fn handle_response(
+        &mut self,
+        peer: PeerId,
+        request_id: RequestId,
+        response: PayloadByNumberResponse,
+    ) {
+        let Some(number) = self.in_flight.remove(&request_id) else {
+            return;
+        };
+
+        match response {
+            PayloadByNumberResponse::Payload { payload, .. } => {
+                if payload.block_number.as_u64() == number {
+                    _ = self.payload_sender.send(*payload);
+                } else {
+                    tracing::debug!(
+                        "peer {} sent payload {} for {}",
+                        peer,
+                        payload.block_number,
+                        number
+                    );
+                }
+            }
+            PayloadByNumberResponse::NotFound => {
+                tracing::debug!("peer {} has no payload {}", peer, number);
+            }
+            PayloadByNumberResponse::Error(code) => {
+                tracing::debug!("peer {} failed payload request {}: {}", peer, number, code);
+            }
+        }
+    }
+
+    /// Serves an inbound request from the L2 execution client
+    fn serve(
+        &mut self,
+        peer: PeerId,
+        number: u64,
+        channel: ResponseChannel<PayloadByNumberResponse>,
+        behaviour: &mut request_response::Behaviour<PayloadByNumberCodec>,
+    ) {
+        // Dropping the channel closes the stream without a response
+        let Some(delay) = self
+            .server_limits
+            .reserve(&peer, Instant::now(), MAX_THROTTLE_DELAY)
+        else {
+            tracing::debug!("peer {} exceeded its payload request rate limit", peer);
+            return;
+        };
+
+        let Some(provider) = self.l2_provider.clone() else {
+            _ = behaviour.send_response(channel, PayloadByNumberResponse::NotFound);
+            return;
+        };
+
+        let served_sender = self.served_sender.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+
+            let response = match fetch_payload(&provider, number).await {
+                Ok(Some(response)) => response,
+                Ok(None) => PayloadByNumberResponse::NotFound,
+                Err(err) => {
+                    tracing::debug!("failed to serve payload {}: {}", number, err);
+                    PayloadByNumberResponse::Error(RESULT_UNKNOWN)
+                }
+            };
+
+            _ = served_sender.send((channel, response));
+        });
+    }
+}
+
+/// Fetches the canonical payload at the given number from the L2 execution client
+async fn fetch_payload(
+    provider: &Provider<Http>,
+    number: u64,
+) -> Result<Option<PayloadByNumberResponse>> {
+    let Some(block) = provider.get_block_with_txs(number).await? else {
+        return Ok(None);
+    };
+
+    let parent_beacon_block_root = block.parent_beacon_block_root;
+    let pre_canyon = block.withdrawals.is_none();
+
+    let mut payload = ExecutionPayload::try_from(block)?;
+    if pre_canyon {
+        payload.withdrawals = None;
+    }
+
+    Ok(Some(PayloadByNumberResponse::Payload {
+        payload: Box::new(payload),
+        parent_beacon_block_root,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use ethers::types::{H256, U64};
+    use futures::io::Cursor;
+    use libp2p::{request_response::Codec, PeerId};
+
+    use crate::{common::RawTransaction, engine::ExecutionPayload};
+
+    use super::{
+        PayloadByNumberCodec, PayloadByNumberProtocol, PayloadByNumberResponse, PeerRateLimiter,
+    };
+
+    async fn round_trip(response: PayloadByNumberResponse) -> PayloadByNumberResponse {
+        let protocol = PayloadByNumberProtocol::new(10);
+        let mut codec = PayloadByNumberCodec;
+
+        let mut buf = Cursor::new(Vec::new());
+        codec
+            .write_response(&protocol, &mut buf, response)
+            .await
+            .unwrap();
+
+        let mut buf = Cursor::new(buf.into_inner());
+        codec.read_response(&protocol, &mut buf).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_payload_by_number_codec() {
+        let protocol = PayloadByNumberProtocol::new(10);
+        let mut codec = PayloadByNumberCodec;
+
+        let mut buf = Cursor::new(Vec::new());
+        codec
+            .write_request(&protocol, &mut buf, 1234)
+            .await
+            .unwrap();
+        assert_eq!(buf.get_ref(), &1234u64.to_le_bytes());
+        let mut buf = Cursor::new(buf.into_inner());
+        assert_eq!(codec.read_request(&protocol, &mut buf).await.unwrap(), 1234);
+
+        let payload = ExecutionPayload {
+            parent_hash: H256::random(),
+            logs_bloom: vec![0; 256].into(),
+            block_number: U64::from(1234),
+            timestamp: U64::from(5678),
+            base_fee_per_gas: U64::from(7),
+            block_hash: H256::random(),
+            transactions: vec![RawTransaction(vec![1, 2, 3])],
+            withdrawals: Some(Vec::new()),
+            ..Default::default()
+        };
+
+        let response = PayloadByNumberResponse::Payload {
+            payload: Box::new(payload.clone()),
+            parent_beacon_block_root: None,
+        };
+        assert_eq!(round_trip(response.clone()).await, response);
+
+        let response = PayloadByNumberResponse::Payload {
+            payload: Box::new(ExecutionPayload {
+                blob_gas_used: Some(U64::zero()),
+                excess_blob_gas: Some(U64::from(1)),
+                ..payload
+            }),
+            parent_beacon_block_root: Some(H256::random()),
+        };
+        assert_eq!(round_trip(response.clone()).await, response);
+
+        let response = PayloadByNumberResponse::NotFound;
+        assert_eq!(round_trip(response.clone()).await, response);
+    }
+
+    #[test]
+    fn test_peer_rate_limiter() {
+        let mut limiter = PeerRateLimiter::new(2.0, 2.0);
+        let peer = PeerId::random();
+        let other = PeerId::random();
+        let start = Instant::now();
+
+        assert!(limiter.try_acquire(&peer, start));
+        assert!(limiter.try_acquire(&peer, start));
+        assert!(!limiter.try_acquire(&peer, start));
+
+        // Each peer has its own limit
+        assert!(limiter.try_acquire(&other, start));
+
+        // Refills at two tokens per second
+        assert!(limiter.try_acquire(&peer, start + Duration::from_millis(500)));
+        assert!(!limiter.try_acquire(&peer, start + Duration::from_millis(600)));
+    }
+
+    #[test]
+    fn test_peer_rate_limiter_reserve() {
+        let mut limiter = PeerRateLimiter::new(2.0, 2.0);
+        let peer = PeerId::random();
+        let start = Instant::now();
+        let max_delay = Duration::from_secs(1);
+
+        assert_eq!(
+            limiter.reserve(&peer, start, max_delay),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(
+            limiter.reserve(&peer, start, max_delay),
+            Some(Duration::ZERO)
+        );
+
+        // Later requests wait for their token, up to the maximum delay
+        assert_eq!(
+            limiter.reserve(&peer, start, max_delay),
+            Some(Duration::from_millis(500))
+        );
+        assert_eq!(limiter.reserve(&peer, start, max_delay), Some(max_delay));
+        assert_eq!(limiter.reserve(&peer, start, max_delay), None);
+    }
+}
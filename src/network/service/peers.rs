@@ -0,0 +1,257 @@
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    time::{Duration, Instant},
+};
+
+use libp2p::{
+    connection_limits::ConnectionLimits, core::ConnectedPoint, identify, Multiaddr, PeerId, Swarm,
+};
+
+use crate::telemetry::metrics;
+
+use super::Behaviour;
+
+/// How long a misbehaving peer is banned for
+const BAN_DURATION: Duration = Duration::from_secs(60 * 60);
+/// Peers with a gossipsub score below this are banned
+const BAN_THRESHOLD: f64 = -100.0;
+
+/// The score bands reported in the `peer_scores` metric, with their lower bounds
+const SCORE_BANDS: [(&str, f64); 4] = [
+    ("positive", 0.0),
+    ("negative", -10.0),
+    ("no_gossip", -40.0),
+    ("graylisted", f64::NEG_INFINITY),
+];
+
+/// Limits the number of connected peers, and temporarily bans misbehaving peers.
+///
+/// New peers from discovery are only dialed while below the target peer count, and
+/// connections beyond the max peer count are denied. Peers are banned for sending
+/// rejected messages, or once their gossipsub score drops below [BAN_THRESHOLD].
+#[derive(Debug)]
+pub struct PeerManager {
+    /// The number of peers to dial up to
+    target_peers: u32,
+    /// The maximum number of connected peers
+    max_peers: u32,
+    /// Banned peers, and when their bans expire. Peers blocked until unblocked have no expiry.
+    bans: HashMap<PeerId, Option<Instant>>,
+    /// The connected peers
+    connections: HashMap<PeerId, Connection>,
+}
+
+/// A connection to a peer
+#[derive(Debug, Clone)]
+pub struct Connection {
+    /// The peer's address
+    pub addr: Multiaddr,
+    /// Which side opened the connection
+    pub direction: Direction,
+    /// The last ping round trip time
+    pub latency: Option<Duration>,
+    /// The peer's agent version, protocol version, protocols and listen addresses, once
+    /// identified
+    pub identity: Option<identify::Info>,
+}
+
+/// Which side opened a [Connection]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// The peer dialed the node
+    Inbound,
+    /// The node dialed the peer
+    Outbound,
+}
+
+impl PeerManager {
+    /// Creates a new [PeerManager]
+    pub fn new(target_peers: u32, max_peers: u32) -> Self {
+        Self {
+            target_peers,
+            max_peers: max_peers.max(target_peers),
+            bans: HashMap::new(),
+            connections: HashMap::new(),
+        }
+    }
+
+    /// The connection limits enforcing the max peer count
+    pub fn connection_limits(&self) -> ConnectionLimits {
+        ConnectionLimits::default().with_max_established(Some(self.max_peers))
+    }
+
+    /// Returns true if more peers should be dialed
+    pub fn should_dial(&self, connected: usize) -> bool {
+        connected < self.target_peers as usize
+    }
+
+    /// Bans a peer for [BAN_DURATION], disconnecting it. Blocked peers stay blocked.
+    pub fn ban(&mut self, swarm: &mut Swarm<Behaviour>, peer: PeerId, now: Instant) {
+        if let Entry::Vacant(entry) = self.bans.entry(peer) {
+            tracing::warn!("banning peer {}", peer);
+            entry.insert(Some(now + BAN_DURATION));
+            swarm.behaviour_mut().blocked_peers.block_peer(peer);
+        }
+    }
+
+    /// Blocks a peer until it is unblocked, disconnecting it
+    pub fn block(&mut self, swarm: &mut Swarm<Behaviour>, peer: PeerId) {
+        tracing::info!("blocking peer {}", peer);
+        self.bans.insert(peer, None);
+        swarm.behaviour_mut().blocked_peers.block_peer(peer);
+    }
+
+    /// Lifts the block or ban of a peer
+    pub fn unblock(&mut self, swarm: &mut Swarm<Behaviour>, peer: PeerId) {
+        if self.bans.remove(&peer).is_some() {
+            tracing::info!("unblocking peer {}", peer);
+            swarm.behaviour_mut().blocked_peers.unblock_peer(peer);
+        }
+    }
+
+    /// Returns the blocked and banned peers
+    pub fn banned(&self) -> impl Iterator<Item = &PeerId> {
+        self.bans.keys()
+    }
+
+    /// Records a new connection to a peer
+    pub fn peer_connected(&mut self, peer: PeerId, endpoint: &ConnectedPoint) {
+        let (addr, direction) = match endpoint {
+            ConnectedPoint::Dialer { address, .. } => (address.clone(), Direction::Outbound),
+            ConnectedPoint::Listener { send_back_addr, .. } => {
+                (send_back_addr.clone(), Direction::Inbound)
+            }
+        };
+
+        self.connections.entry(peer).or_insert(Connection {
+            addr,
+            direction,
+            latency: None,
+            identity: None,
+        });
+    }
+
+    /// Forgets a peer once its last connection closed
+    pub fn peer_disconnected(&mut self, peer: &PeerId) {
+        self.connections.remove(peer);
+    }
+
+    /// Records the ping round trip time to a peer
+    pub fn record_latency(&mut self, peer: &PeerId, latency: Duration) {
+        if let Some(connection) = self.connections.get_mut(peer) {
+            connection.latency = Some(latency);
+        }
+    }
+
+    /// Records the identity a peer sent with [libp2p::identify]
+    pub fn record_identity(&mut self, peer: &PeerId, info: identify::Info) {
+        if let Some(connection) = self.connections.get_mut(peer) {
+            connection.identity = Some(info);
+        }
+    }
+
+    /// Returns the connected peers
+    pub fn connections(&self) -> impl Iterator<Item = (&PeerId, &Connection)> {
+        self.connections.iter()
+    }
+
+    /// Lifts expired bans, bans peers whose score is too low, and updates the peer metrics
+    pub fn heartbeat(&mut self, swarm: &mut Swarm<Behaviour>, now: Instant) {
+        for peer in self.expire_bans(now) {
+            swarm.behaviour_mut().blocked_peers.unblock_peer(peer);
+        }
+
+        let scores = swarm
+            .connected_peers()
+            .map(|peer| {
+                let score = swarm.behaviour().gossipsub.peer_score(peer);
+                (*peer, score.unwrap_or_default())
+            })
+            .collect::<Vec<_>>();
+
+        for (peer, score) in &scores {
+            if *score < BAN_THRESHOLD {
+                self.ban(swarm, *peer, now);
+            }
+        }
+
+        metrics::PEER_COUNT.set(scores.len() as i64);
+        metrics::BANNED_PEERS.set(self.bans.len() as i64);
+        for (band, count) in score_bands(scores.iter().map(|(_, score)| *score)) {
+            metrics::PEER_SCORES.with_label_values(&[band]).set(count);
+        }
+    }
+
+    /// Removes the bans that expired by `now`, returning the unbanned peers
+    fn expire_bans(&mut self, now: Instant) -> Vec<PeerId> {
+        let expired = self
+            .bans
+            .iter()
+            .filter(|(_, until)| until.map(|until| until <= now).unwrap_or_default())
+            .map(|(peer, _)| *peer)
+            .collect::<Vec<_>>();
+
+        for peer in &expired {
+            self.bans.remove(peer);
+        }
+
+        expired
+    }
+}
+
+/// Counts the scores in each of the [SCORE_BANDS]
+fn score_bands(scores: impl Iterator<Item = f64>) -> Vec<(&'static str, i64)> {
+    let mut counts = SCORE_BANDS.map(|(band, _)| (band, 0));
+    for score in scores {
+        if let Some(index) = SCORE_BANDS.iter().position(|(_, lower)| score >= *lower) {
+            counts[index].1 += 1;
+        }
+    }
+
+    counts.to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use libp2p::PeerId;
+
+    use super::{score_bands, PeerManager, BAN_DURATION};
+
+    #[test]
+    fn test_ban_expiry() {
+        let mut manager = PeerManager::new(20, 30);
+        let peer = PeerId::random();
+        let blocked = PeerId::random();
+        let now = Instant::now();
+
+        manager.bans.insert(peer, Some(now + BAN_DURATION));
+        manager.bans.insert(blocked, None);
+        assert!(manager.bans.contains_key(&peer));
+
+        assert!(manager
+            .expire_bans(now + Duration::from_secs(60))
+            .is_empty());
+        assert_eq!(manager.expire_bans(now + BAN_DURATION), vec![peer]);
+        assert!(!manager.bans.contains_key(&peer));
+        assert!(manager.bans.contains_key(&blocked));
+
+        assert!(manager.should_dial(19));
+        assert!(!manager.should_dial(20));
+    }
+
+    #[test]
+    fn test_score_bands() {
+        let bands = score_bands([5.0, 0.0, -3.0, -20.0, -50.0, -500.0].into_iter());
+        assert_eq!(
+            bands,
+            vec![
+                ("positive", 2),
+                ("negative", 1),
+                ("no_gossip", 1),
+                ("graylisted", 2)
+            ]
+        );
+    }
+}
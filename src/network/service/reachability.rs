@@ -0,0 +1,125 @@
+use std::time::Duration;
+
+use eyre::Result;
+use futures::StreamExt;
+use libp2p::{
+    identify, ping,
+    swarm::{NetworkBehaviour, SwarmBuilder, SwarmEvent},
+    Multiaddr, PeerId,
+};
+use libp2p_identity::Keypair;
+
+use crate::{config::Muxer, version::Version};
+
+use super::{transport, IDENTIFY_PROTOCOL_VERSION};
+
+/// The result of pinging a peer
+#[derive(Debug, Clone)]
+pub struct PingResult {
+    /// The peer ID of the peer
+    pub peer_id: PeerId,
+    /// The ping round trip time
+    pub rtt: Duration,
+    /// What the peer sent with [libp2p::identify], if it supports it
+    pub identity: Option<identify::Info>,
+}
+
+/// Dials a peer with a throwaway identity, then waits for a ping round trip and the peer's
+/// identify response. Fails if the peer cannot be reached within `timeout`.
+pub async fn ping_peer(addr: Multiaddr, timeout: Duration) -> Result<PingResult> {
+    let keypair = Keypair::generate_secp256k1();
+    let transport = transport::build(&keypair, &[Muxer::Yamux, Muxer::Mplex], true)?;
+    let behaviour = PingBehaviour {
+        ping: ping::Behaviour::default(),
+        identify: identify::Behaviour::new(
+            identify::Config::new(IDENTIFY_PROTOCOL_VERSION.to_string(), keypair.public())
+                .with_agent_version(Version::build().to_string()),
+        ),
+    };
+
+    let mut swarm =
+        SwarmBuilder::with_tokio_executor(transport, behaviour, PeerId::from(keypair.public()))
+            .build();
+    swarm.dial(addr)?;
+
+    let mut peer_id = None;
+    let mut rtt = None;
+    let mut identity = None;
+    let mut identified = false;
+
+    let res = tokio::time::timeout(timeout, async {
+        loop {
+            match swarm.select_next_some().await {
+                SwarmEvent::ConnectionEstablished { peer_id: id, .. } => peer_id = Some(id),
+                SwarmEvent::OutgoingConnectionError { error, .. } => {
+                    eyre::bail!("dial failed: {}", error)
+                }
+                SwarmEvent::Behaviour(PingEvent::Ping(event)) => match event.result {
+                    Ok(ping::Success::Ping { rtt: time }) => rtt = Some(time),
+                    Ok(ping::Success::Pong) => (),
+                    Err(err) => eyre::bail!("ping failed: {}", err),
+                },
+                SwarmEvent::Behaviour(PingEvent::Identify(event)) => match *event {
+                    identify::Event::Received { info, .. } => {
+                        identity = Some(info);
+                        identified = true;
+                    }
+                    identify::Event::Error { .. } => identified = true,
+                    _ => (),
+                },
+                _ => (),
+            }
+
+            if let (Some(peer_id), Some(rtt), true) = (peer_id, rtt, identified) {
+                return Ok((peer_id, rtt));
+            }
+        }
+    })
+    .await;
+
+    let (peer_id, rtt) = match res {
+        Ok(res) => res?,
+        Err(_) => match (peer_id, rtt) {
+            (Some(peer_id), Some(rtt)) => (peer_id, rtt),
+            _ => eyre::bail!("peer did not respond within {:?}", timeout),
+        },
+    };
+
+    Ok(PingResult {
+        peer_id,
+        rtt,
+        identity,
+    })
+}
+
+/// The [NetworkBehaviour] used to ping a single peer
+#[derive(NetworkBehaviour)]
+#[behaviour(out_event = "PingEvent")]
+struct PingBehaviour {
+    /// Measures the round trip time to the peer
+    ping: ping::Behaviour,
+    /// Asks the peer for its agent version and protocols
+    identify: identify::Behaviour,
+}
+
+/// The events emitted by [PingBehaviour]
+enum PingEvent {
+    /// Represents a [ping::Event]
+    Ping(ping::Event),
+    /// Represents an [identify::Event]
+    Identify(Box<identify::Event>),
+}
+
+impl From<ping::Event> for PingEvent {
+    /// Converts [ping::Event] to [PingEvent]
+    fn from(value: ping::Event) -> Self {
+        PingEvent::Ping(value)
+    }
+}
+
+impl From<identify::Event> for PingEvent {
+    /// Converts [identify::Event] to [PingEvent]
+    fn from(value: identify::Event) -> Self {
+        PingEvent::Identify(Box::new(value))
+    }
+}
@@ -0,0 +1,118 @@
+use std::time::Duration;
+
+use libp2p::gossipsub::{PeerScoreParams, PeerScoreThresholds, TopicHash, TopicScoreParams};
+
+/// The score a decayed counter is rounded down to zero at
+const DECAY_TO_ZERO: f64 = 0.01;
+/// The number of blocks in a scoring epoch
+const EPOCH_BLOCKS: u32 = 6;
+/// The weight of mesh delivery and mesh failure penalties
+const MESH_WEIGHT: f64 = -0.7;
+/// The maximum score for time spent in the mesh
+const MAX_IN_MESH_SCORE: f64 = 10.0;
+/// The number of epochs mesh delivery counters decay over
+const DECAY_EPOCHS: u32 = 5;
+
+/// Returns the decay factor that takes a counter to zero over `duration`, when decayed once
+/// per block
+fn score_decay(duration: Duration, slot: Duration) -> f64 {
+    let ticks = duration.as_secs_f64() / slot.as_secs_f64();
+    DECAY_TO_ZERO.powf(1.0 / ticks)
+}
+
+/// Returns the block time, defaulting to 2 seconds
+fn slot(block_time: u64) -> Duration {
+    match block_time {
+        0 => Duration::from_secs(2),
+        secs => Duration::from_secs(secs),
+    }
+}
+
+/// The gossipsub peer score parameters, following the op-node defaults in the [OP p2p spec].
+/// Scores decay once per block, and each block topic is scored with [topic_score_params].
+///
+/// [OP p2p spec]: https://specs.optimism.io/protocol/rollup-node-p2p.html#peer-scoring
+pub fn peer_score_params(block_time: u64, topics: &[TopicHash]) -> PeerScoreParams {
+    let slot = slot(block_time);
+    let epoch = slot * EPOCH_BLOCKS;
+
+    PeerScoreParams {
+        topics: topics
+            .iter()
+            .map(|topic| (topic.clone(), topic_score_params(block_time)))
+            .collect(),
+        topic_score_cap: 34.0,
+        app_specific_weight: 1.0,
+        ip_colocation_factor_weight: -35.0,
+        ip_colocation_factor_threshold: 10.0,
+        behaviour_penalty_weight: -16.0,
+        behaviour_penalty_threshold: 6.0,
+        behaviour_penalty_decay: score_decay(epoch * 50, slot),
+        decay_interval: slot,
+        decay_to_zero: DECAY_TO_ZERO,
+        retain_score: epoch * 100,
+        ..Default::default()
+    }
+}
+
+/// The score parameters of a block topic. Peers are rewarded for time in the mesh and for
+/// delivering new blocks first, and penalized for missing mesh deliveries and for invalid
+/// blocks.
+pub fn topic_score_params(block_time: u64) -> TopicScoreParams {
+    let slot = slot(block_time);
+    let epoch = slot * EPOCH_BLOCKS;
+    let in_mesh_cap = 3600.0 / slot.as_secs_f64();
+    let mesh_deliveries = (EPOCH_BLOCKS * DECAY_EPOCHS) as f64;
+
+    TopicScoreParams {
+        topic_weight: 0.8,
+        time_in_mesh_weight: MAX_IN_MESH_SCORE / in_mesh_cap,
+        time_in_mesh_quantum: slot,
+        time_in_mesh_cap: in_mesh_cap,
+        first_message_deliveries_weight: 1.0,
+        first_message_deliveries_decay: score_decay(epoch * 20, slot),
+        first_message_deliveries_cap: 23.0,
+        mesh_message_deliveries_weight: MESH_WEIGHT,
+        mesh_message_deliveries_decay: score_decay(epoch * DECAY_EPOCHS, slot),
+        mesh_message_deliveries_cap: mesh_deliveries,
+        mesh_message_deliveries_threshold: mesh_deliveries / 10.0,
+        mesh_message_deliveries_window: Duration::from_secs(2),
+        mesh_message_deliveries_activation: epoch * 4,
+        mesh_failure_penalty_weight: MESH_WEIGHT,
+        mesh_failure_penalty_decay: score_decay(epoch * DECAY_EPOCHS, slot),
+        invalid_message_deliveries_weight: -140.4475,
+        invalid_message_deliveries_decay: score_decay(epoch * 50, slot),
+    }
+}
+
+/// The gossipsub score thresholds. Peers below the gossip threshold are not gossiped to,
+/// and peers below the graylist threshold are ignored.
+pub fn peer_score_thresholds() -> PeerScoreThresholds {
+    PeerScoreThresholds {
+        gossip_threshold: -10.0,
+        publish_threshold: -40.0,
+        graylist_threshold: -40.0,
+        accept_px_threshold: 20.0,
+        opportunistic_graft_threshold: 0.05,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libp2p::gossipsub::IdentTopic;
+
+    use super::{peer_score_params, peer_score_thresholds};
+
+    #[test]
+    fn test_score_params_valid() {
+        let topic = IdentTopic::new("/optimism/10/0/blocks").hash();
+        let params = peer_score_params(2, std::slice::from_ref(&topic));
+
+        assert!(params.validate().is_ok());
+        assert!(peer_score_thresholds().validate().is_ok());
+
+        let topic_params = &params.topics[&topic];
+        assert_eq!(topic_params.time_in_mesh_cap, 1800.0);
+        assert!(topic_params.mesh_message_deliveries_decay < 1.0);
+    }
+}
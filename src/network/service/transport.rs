@@ -0,0 +1,91 @@
+use eyre::Result;
+use libp2p::{
+    core::{
+        muxing::StreamMuxerBox,
+        transport::{Boxed, OrTransport},
+        upgrade::{SelectUpgrade, Version},
+    },
+    futures::future::Either,
+    mplex::MplexConfig,
+    noise, tcp, yamux, PeerId, Transport,
+};
+use libp2p_identity::Keypair;
+use libp2p_quic as quic;
+
+use crate::config::Muxer;
+
+/// Builds the libp2p transport: TCP secured with noise and multiplexed with the given muxers,
+/// negotiated in order of preference. QUIC is added alongside TCP if enabled.
+pub fn build(
+    keypair: &Keypair,
+    muxers: &[Muxer],
+    quic: bool,
+) -> Result<Boxed<(PeerId, StreamMuxerBox)>> {
+    let tcp = tcp_transport(keypair, muxers)?;
+    if !quic {
+        return Ok(tcp);
+    }
+
+    let quic = quic::tokio::Transport::new(quic::Config::new(keypair));
+    let transport = OrTransport::new(quic, tcp)
+        .map(|output, _| match output {
+            Either::Left((peer_id, connection)) => (peer_id, StreamMuxerBox::new(connection)),
+            Either::Right((peer_id, muxer)) => (peer_id, muxer),
+        })
+        .boxed();
+
+    Ok(transport)
+}
+
+/// Builds the TCP transport, negotiating the first two distinct muxers
+fn tcp_transport(keypair: &Keypair, muxers: &[Muxer]) -> Result<Boxed<(PeerId, StreamMuxerBox)>> {
+    let mut preferred = Vec::new();
+    for muxer in muxers {
+        if !preferred.contains(muxer) {
+            preferred.push(*muxer);
+        }
+    }
+
+    let tcp = tcp::tokio::Transport::new(tcp::Config::default().nodelay(true))
+        .upgrade(Version::V1Lazy)
+        .authenticate(noise::Config::new(keypair)?);
+
+    let transport = match preferred.as_slice() {
+        [Muxer::Yamux] => tcp.multiplex(yamux::Config::default()).boxed(),
+        [Muxer::Mplex] => tcp.multiplex(MplexConfig::default()).boxed(),
+        [Muxer::Yamux, Muxer::Mplex] => tcp
+            .multiplex(SelectUpgrade::new(
+                yamux::Config::default(),
+                MplexConfig::default(),
+            ))
+            .boxed(),
+        [Muxer::Mplex, Muxer::Yamux] => tcp
+            .multiplex(SelectUpgrade::new(
+                MplexConfig::default(),
+                yamux::Config::default(),
+            ))
+            .boxed(),
+        _ => eyre::bail!("at least one muxer is required"),
+    };
+
+    Ok(transport)
+}
+
+#[cfg(test)]
+mod tests {
+    use libp2p_identity::Keypair;
+
+    use crate::config::Muxer;
+
+    use super::build;
+
+    #[test]
+    fn test_build_transport() {
+        let keypair = Keypair::generate_secp256k1();
+
+        assert!(build(&keypair, &[Muxer::Yamux, Muxer::Mplex], false).is_ok());
+        assert!(build(&keypair, &[Muxer::Mplex, Muxer::Mplex], false).is_ok());
+        assert!(build(&keypair, &[Muxer::Yamux], true).is_ok());
+        assert!(build(&keypair, &[], false).is_err());
+    }
+}
@@ -1,34 +1,51 @@
 use std::{fmt::Display, net::SocketAddr, sync::Arc};
 
 use crate::{
+    common::BlockInfo,
     config::{Config, ExternalChainConfig},
+    driver::{HeadInfo, SyncStatus},
+    network::service::NetworkHandle,
     version::Version,
 };
 
 use eyre::Result;
 
 use ethers::{
-    providers::{Middleware, Provider},
-    types::{Block, BlockId, H256},
+    providers::{Http, Middleware, Provider},
+    types::{Block, BlockId, BlockNumber, Transaction, H256},
     utils::keccak256,
 };
 
 use jsonrpsee::{
-    core::{async_trait, Error},
+    core::{async_trait, Error, SubscriptionResult},
     proc_macros::rpc,
-    server::ServerBuilder,
+    server::{PendingSubscriptionSink, ServerBuilder, SubscriptionMessage},
 };
+use tokio::sync::watch;
 
 use serde::{Deserialize, Serialize};
 
+/// The `opp2p` namespace for inspecting and controlling the p2p service
+mod p2p;
+pub use p2p::*;
+
 /// This trait defines a set of RPC methods that can be
 /// queried by clients under the `optimism` namespace
 #[rpc(server, namespace = "optimism")]
 pub trait Rpc {
-    /// Returns the L2 output information for a given block.
+    /// Returns the L2 output information for a given block number or tag. If `require_safe`
+    /// is true, blocks above the safe head are rejected.
     /// See the [Optimism spec](https://specs.optimism.io/protocol/rollup-node.html?highlight=rpc#l2-output-rpc-method) for more details
     #[method(name = "outputAtBlock")]
-    async fn output_at_block(&self, block_number: u64) -> Result<OutputRootResponse, Error>;
+    async fn output_at_block(
+        &self,
+        block: BlockParam,
+        require_safe: Option<bool>,
+    ) -> Result<OutputRootResponse, Error>;
+
+    /// Returns the L1 and L2 heads of the node, in the same shape as `op-node`.
+    #[method(name = "syncStatus")]
+    async fn sync_status(&self) -> Result<SyncStatusResponse, Error>;
 
     /// Returns the rollup configuration options.
     #[method(name = "rollupConfig")]
@@ -37,6 +54,12 @@ pub trait Rpc {
     /// Returns details about the Magi version of the node.
     #[method(name = "version")]
     async fn version(&self) -> Result<String, Error>;
+
+    /// Subscribes to sync status updates, in the same shape as `optimism_syncStatus`. The
+    /// current status is sent on subscription, then again whenever one of the node's heads
+    /// changes.
+    #[subscription(name = "subscribeSyncStatus" => "syncStatusUpdate", unsubscribe = "unsubscribeSyncStatus", item = SyncStatusResponse)]
+    async fn subscribe_sync_status(&self) -> SubscriptionResult;
 }
 
 /// The Magi RPC server which implements the same `optimism` namespace methods as `op-node`
@@ -46,26 +69,106 @@ pub struct RpcServerImpl {
     version: Version,
     /// The Magi [Config]
     config: Arc<Config>,
+    /// Channel to receive [SyncStatus] updates from the driver
+    sync_status: watch::Receiver<SyncStatus>,
+    /// Provider for the local L2 execution RPC
+    l2_provider: Provider<Http>,
+}
+
+impl RpcServerImpl {
+    /// Returns the [HeadInfo] of an L2 block, reading its L1 origin and sequence number from
+    /// its L1 info deposit
+    fn head_info(&self, block: Block<Transaction>) -> Result<HeadInfo, Error> {
+        if block.hash == Some(self.config.chain.l2_genesis.hash) {
+            return Ok(HeadInfo {
+                l2_block_info: convert_err(BlockInfo::try_from(block))?,
+                l1_epoch: self.config.chain.l1_start_epoch,
+                sequence_number: 0,
+            });
+        }
+
+        convert_err(HeadInfo::try_from_l2_block(&self.config, block))
+    }
+
+    /// Returns the [L2BlockRef] of an L2 head. Heads the driver has not set yet are returned
+    /// as is.
+    async fn l2_block_ref(&self, block: BlockInfo) -> Result<L2BlockRef, Error> {
+        if block.hash.is_zero() {
+            return Ok(L2BlockRef::from(HeadInfo {
+                l2_block_info: block,
+                ..Default::default()
+            }));
+        }
+
+        if block.hash == self.config.chain.l2_genesis.hash {
+            return Ok(L2BlockRef::from(HeadInfo {
+                l2_block_info: block,
+                l1_epoch: self.config.chain.l1_start_epoch,
+                sequence_number: 0,
+            }));
+        }
+
+        let l2_block = convert_err(self.l2_provider.get_block_with_txs(block.hash).await)?
+            .ok_or(Error::Custom("unable to get block".to_string()))?;
+
+        Ok(L2BlockRef::from(self.head_info(l2_block)?))
+    }
+
+    /// Returns the [SyncStatusResponse] for a [SyncStatus] from the driver
+    async fn sync_status_response(&self, status: SyncStatus) -> Result<SyncStatusResponse, Error> {
+        let (unsafe_l2, safe_l2, finalized_l2) = futures::try_join!(
+            self.l2_block_ref(status.unsafe_l2),
+            self.l2_block_ref(status.safe_l2),
+            self.l2_block_ref(status.finalized_l2),
+        )?;
+
+        Ok(SyncStatusResponse {
+            current_l1: status.current_l1.into(),
+            current_l1_finalized: status.finalized_l1.into(),
+            head_l1: status.head_l1.into(),
+            safe_l1: status.safe_l1.into(),
+            finalized_l1: status.finalized_l1.into(),
+            unsafe_l2,
+            safe_l2,
+            finalized_l2,
+            pending_safe_l2: safe_l2,
+        })
+    }
 }
 
 #[async_trait]
 impl RpcServer for RpcServerImpl {
-    /// Returns the L2 output information for a given block.
+    /// Returns the L2 output information for a given block number or tag. If `require_safe`
+    /// is true, blocks above the safe head are rejected.
     /// See the [Optimism spec](https://specs.optimism.io/protocol/rollup-node.html?highlight=rpc#l2-output-rpc-method) for more details
-    async fn output_at_block(&self, block_number: u64) -> Result<OutputRootResponse, Error> {
-        let l2_provider = convert_err(Provider::try_from(self.config.l2_rpc_url.clone()))?;
+    async fn output_at_block(
+        &self,
+        block: BlockParam,
+        require_safe: Option<bool>,
+    ) -> Result<OutputRootResponse, Error> {
+        let block = convert_err(
+            self.l2_provider
+                .get_block_with_txs(BlockNumber::from(block))
+                .await,
+        )?
+        .ok_or(Error::Custom("unable to get block".to_string()))?;
+        let block_ref = L2BlockRef::from(self.head_info(block.clone())?);
+
+        let status = *self.sync_status.borrow();
+        let sync_status = self.sync_status_response(status).await?;
+        if require_safe.unwrap_or_default() && block_ref.number > sync_status.safe_l2.number {
+            return Err(Error::Custom(format!(
+                "block {} is above the safe head {}",
+                block_ref.number, sync_status.safe_l2.number
+            )));
+        }
 
-        let block = convert_err(l2_provider.get_block(block_number).await)?
-            .ok_or(Error::Custom("unable to get block".to_string()))?;
         let state_root = block.state_root;
-        let block_hash = block
-            .hash
-            .ok_or(Error::Custom("block hash not found".to_string()))?;
         let locations = vec![];
-        let block_id = Some(BlockId::from(block_hash));
+        let block_id = Some(BlockId::from(block_ref.hash));
 
         let state_proof = convert_err(
-            l2_provider
+            self.l2_provider
                 .get_proof(
                     ethers::types::Address::from_slice(
                         self.config.chain.l2_to_l1_message_passer.as_slice(),
@@ -78,7 +181,8 @@ impl RpcServer for RpcServerImpl {
 
         let withdrawal_storage_root = state_proof.storage_hash;
 
-        let output_root = compute_l2_output_root(block, state_proof.storage_hash);
+        let output_root =
+            compute_l2_output_root(state_root, withdrawal_storage_root, block_ref.hash);
 
         let version: H256 = Default::default();
 
@@ -87,9 +191,17 @@ impl RpcServer for RpcServerImpl {
             version,
             state_root,
             withdrawal_storage_root,
+            block_ref,
+            sync_status,
         })
     }
 
+    /// Returns the L1 and L2 heads of the node, in the same shape as `op-node`.
+    async fn sync_status(&self) -> Result<SyncStatusResponse, Error> {
+        let status = *self.sync_status.borrow();
+        self.sync_status_response(status).await
+    }
+
     /// Returns the rollup configuration options.
     async fn rollup_config(&self) -> Result<ExternalChainConfig, Error> {
         let config = (*self.config).clone();
@@ -101,6 +213,29 @@ impl RpcServer for RpcServerImpl {
     async fn version(&self) -> Result<String, Error> {
         Ok(self.version.to_string())
     }
+
+    /// Sends the current [SyncStatusResponse], then every update until the subscriber
+    /// disconnects or the driver stops.
+    async fn subscribe_sync_status(&self, pending: PendingSubscriptionSink) -> SubscriptionResult {
+        let sink = pending.accept().await?;
+        let mut sync_status = self.sync_status.clone();
+
+        loop {
+            let status = *sync_status.borrow_and_update();
+            let response = self.sync_status_response(status).await?;
+            sink.send(SubscriptionMessage::from_json(&response)?)
+                .await?;
+
+            tokio::select! {
+                res = sync_status.changed() => {
+                    if res.is_err() {
+                        return Ok(());
+                    }
+                }
+                _ = sink.closed() => return Ok(()),
+            }
+        }
+    }
 }
 
 /// Converts a generic error to a [jsonrpsee::core::error] if one exists
@@ -110,14 +245,14 @@ fn convert_err<T, E: Display>(res: Result<T, E>) -> Result<T, Error> {
 
 /// Computes the L2 output root.
 /// Refer to the [Optimism Spec](https://specs.optimism.io/protocol/proposals.html#l2-output-commitment-construction) for details
-fn compute_l2_output_root(block: Block<H256>, storage_root: H256) -> H256 {
+fn compute_l2_output_root(state_root: H256, storage_root: H256, block_hash: H256) -> H256 {
     let version: H256 = Default::default();
     let digest = keccak256(
         [
             version.to_fixed_bytes(),
-            block.state_root.to_fixed_bytes(),
+            state_root.to_fixed_bytes(),
             storage_root.to_fixed_bytes(),
-            block.hash.unwrap().to_fixed_bytes(),
+            block_hash.to_fixed_bytes(),
         ]
         .concat(),
     );
@@ -125,8 +260,12 @@ fn compute_l2_output_root(block: Block<H256>, storage_root: H256) -> H256 {
     H256::from_slice(&digest)
 }
 
-/// Starts the Magi RPC server
-pub async fn run_server(config: Arc<Config>) -> Result<SocketAddr> {
+/// Starts the Magi RPC server, serving both HTTP and WebSocket requests
+pub async fn run_server(
+    config: Arc<Config>,
+    sync_status: watch::Receiver<SyncStatus>,
+    network: NetworkHandle,
+) -> Result<SocketAddr> {
     let port = config.rpc_port;
     let addr = config.rpc_addr.clone();
 
@@ -135,10 +274,14 @@ pub async fn run_server(config: Arc<Config>) -> Result<SocketAddr> {
         .await?;
     let addr = server.local_addr()?;
     let rpc_impl = RpcServerImpl {
+        l2_provider: Provider::try_from(config.l2_rpc_url.as_str())?,
         config,
         version: Version::build(),
+        sync_status,
     };
-    let handle = server.start(rpc_impl.into_rpc())?;
+    let mut module = rpc_impl.into_rpc();
+    module.merge(P2pRpcServerImpl::new(network).into_rpc())?;
+    let handle = server.start(module)?;
 
     // In this example we don't care about doing shutdown so let's it run forever.
     // You may use the `ServerHandle` to shut it down or manage it yourself.
@@ -160,6 +303,123 @@ pub struct OutputRootResponse {
     pub state_root: H256,
     /// The 32 byte storage root of the `L2toL1MessagePasser` contract address
     pub withdrawal_storage_root: H256,
+    /// The block the output is for
+    pub block_ref: L2BlockRef,
+    /// The sync status of the node when the output was computed
+    pub sync_status: SyncStatusResponse,
+}
+
+/// A block number, or one of the `latest`, `safe`, `finalized`, `pending` and `earliest`
+/// tags. Numbers may be sent as JSON numbers, or as hex or decimal strings.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum BlockParam {
+    /// A block number
+    Number(u64),
+    /// A block number string or tag
+    Tag(BlockNumber),
+}
+
+impl From<BlockParam> for BlockNumber {
+    fn from(block: BlockParam) -> Self {
+        match block {
+            BlockParam::Number(number) => BlockNumber::Number(number.into()),
+            BlockParam::Tag(tag) => tag,
+        }
+    }
+}
+
+/// The response for the `optimism_syncStatus` RPC method
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SyncStatusResponse {
+    /// The most recent L1 block ingested by the derivation pipeline
+    pub current_l1: L1BlockRef,
+    /// The finalized L1 block as seen by the derivation pipeline
+    pub current_l1_finalized: L1BlockRef,
+    /// The most recent block at the L1 head
+    pub head_l1: L1BlockRef,
+    /// The most recent safe L1 block
+    pub safe_l1: L1BlockRef,
+    /// The most recent finalized L1 block
+    pub finalized_l1: L1BlockRef,
+    /// The unsafe L2 head
+    pub unsafe_l2: L2BlockRef,
+    /// The safe L2 head
+    pub safe_l2: L2BlockRef,
+    /// The finalized L2 head
+    pub finalized_l2: L2BlockRef,
+    /// The safe L2 head, including blocks derived from the current L1 block. Always equal to
+    /// the safe head, as Magi only marks blocks safe once their L1 block is fully derived.
+    pub pending_safe_l2: L2BlockRef,
+}
+
+/// A reference to an L1 block
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct L1BlockRef {
+    /// The block hash
+    pub hash: H256,
+    /// The block number
+    pub number: u64,
+    /// The parent block hash
+    pub parent_hash: H256,
+    /// The block timestamp
+    pub timestamp: u64,
+}
+
+impl From<BlockInfo> for L1BlockRef {
+    fn from(block: BlockInfo) -> Self {
+        Self {
+            hash: block.hash,
+            number: block.number,
+            parent_hash: block.parent_hash,
+            timestamp: block.timestamp,
+        }
+    }
+}
+
+/// A reference to an L2 block, with the L1 block it was derived from
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct L2BlockRef {
+    /// The block hash
+    pub hash: H256,
+    /// The block number
+    pub number: u64,
+    /// The parent block hash
+    pub parent_hash: H256,
+    /// The block timestamp
+    pub timestamp: u64,
+    /// The L1 origin of the block
+    #[serde(rename = "l1origin")]
+    pub l1_origin: L1BlockId,
+    /// The position of the block in its epoch
+    pub sequence_number: u64,
+}
+
+impl From<HeadInfo> for L2BlockRef {
+    fn from(head: HeadInfo) -> Self {
+        Self {
+            hash: head.l2_block_info.hash,
+            number: head.l2_block_info.number,
+            parent_hash: head.l2_block_info.parent_hash,
+            timestamp: head.l2_block_info.timestamp,
+            l1_origin: L1BlockId {
+                hash: head.l1_epoch.hash,
+                number: head.l1_epoch.number,
+            },
+            sequence_number: head.sequence_number,
+        }
+    }
+}
+
+/// The hash and number of an L1 block
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct L1BlockId {
+    /// The block hash
+    pub hash: H256,
+    /// The block number
+    pub number: u64,
 }
 
 #[cfg(test)]
@@ -168,7 +428,7 @@ mod tests {
     use crate::config::{ChainConfig, CliConfig, ExternalChainConfig};
     use reqwest;
     use serde_json::json;
-    use std::{path::PathBuf, str::FromStr};
+    use std::path::PathBuf;
     use tokio::time::{sleep, Duration};
     use tracing_subscriber;
 
@@ -179,31 +439,40 @@ mod tests {
         id: u64,
     }
 
-    #[tokio::test]
-    async fn test_run_server() -> Result<()> {
-        // std::env::set_var("RUST_LOG", "trace");
+    fn config(rpc_port: Option<u16>) -> Config {
         let cli_config = CliConfig {
             l1_rpc_url: Some("".to_string()),
             l1_beacon_url: Some("".to_string()),
+            l1_beacon_fallback_urls: None,
             l2_rpc_url: None,
             l2_engine_url: None,
             jwt_secret: Some("".to_string()),
             checkpoint_sync_url: None,
-            rpc_port: Some(8080),
-            rpc_addr: Some("127.0.0.1".to_string()),
+            rpc_port,
+            rpc_addr: None,
             devnet: false,
+            data_dir: None,
+            l1_confs: None,
+            p2p: Default::default(),
         };
 
-        tracing_subscriber::fmt().init();
-
-        let config_path = PathBuf::from_str("config.toml")?;
-        let config = Arc::new(Config::new(
-            &config_path,
+        Config::new(
+            &PathBuf::from("config.toml"),
             cli_config,
             ChainConfig::optimism_sepolia(),
-        ));
+        )
+    }
+
+    #[tokio::test]
+    async fn test_run_server() -> Result<()> {
+        // std::env::set_var("RUST_LOG", "trace");
+        tracing_subscriber::fmt().init();
 
-        let addr = run_server(config.clone())
+        let config = Arc::new(config(Some(8080)));
+
+        let (_sync_status_sender, sync_status) = watch::channel(SyncStatus::default());
+        let (network, _commands) = NetworkHandle::new();
+        let addr = run_server(config.clone(), sync_status, network)
             .await
             .expect("Failed to start server");
 
@@ -240,4 +509,114 @@ mod tests {
         println!("{:#?}", rpc_chain_config);
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_subscribe_sync_status() -> Result<()> {
+        let config = config(None);
+
+        let (sync_status_sender, sync_status) = watch::channel(SyncStatus::default());
+        let rpc = RpcServerImpl {
+            version: Version::build(),
+            l2_provider: Provider::try_from(config.l2_rpc_url.as_str())?,
+            config: Arc::new(config),
+            sync_status,
+        }
+        .into_rpc();
+
+        let mut sub = rpc
+            .subscribe_unbounded(
+                "optimism_subscribeSyncStatus",
+                jsonrpsee::core::EmptyServerParams::new(),
+            )
+            .await?;
+
+        let (status, _) = sub.next::<SyncStatusResponse>().await.unwrap()?;
+        assert_eq!(status.safe_l2, L2BlockRef::default());
+
+        let mut update = SyncStatus::default();
+        update.safe_l2.number = 10;
+        update.unsafe_l2.number = 12;
+        sync_status_sender.send(update)?;
+
+        let (status, _) = sub.next::<SyncStatusResponse>().await.unwrap()?;
+        assert_eq!(status.safe_l2.number, 10);
+        assert_eq!(status.unsafe_l2.number, 12);
+        assert_eq!(status.pending_safe_l2, status.safe_l2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sync_status() -> Result<()> {
+        let config = config(None);
+
+        let genesis = config.chain.l2_genesis;
+        let start_epoch = config.chain.l1_start_epoch;
+
+        let mut status = SyncStatus::default();
+        status.head_l1.number = 100;
+        status.finalized_l1.number = 90;
+        status.safe_l2 = genesis;
+        status.finalized_l2 = genesis;
+
+        let (_sync_status_sender, sync_status) = watch::channel(status);
+        let rpc = RpcServerImpl {
+            version: Version::build(),
+            l2_provider: Provider::try_from(config.l2_rpc_url.as_str())?,
+            config: Arc::new(config),
+            sync_status,
+        }
+        .into_rpc();
+
+        let res: serde_json::Value = rpc
+            .call(
+                "optimism_syncStatus",
+                jsonrpsee::core::EmptyServerParams::new(),
+            )
+            .await?;
+
+        assert_eq!(res["head_l1"]["number"], 100);
+        assert_eq!(res["current_l1_finalized"]["number"], 90);
+        assert_eq!(res["safe_l2"]["number"], genesis.number);
+        assert_eq!(res["safe_l2"]["parentHash"], json!(genesis.parent_hash));
+        assert_eq!(res["safe_l2"]["l1origin"]["number"], start_epoch.number);
+        assert_eq!(res["safe_l2"]["sequenceNumber"], 0);
+        assert_eq!(res["pending_safe_l2"], res["safe_l2"]);
+        assert_eq!(res["unsafe_l2"]["number"], 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_block_param() -> Result<()> {
+        let params = [
+            json!(5),
+            json!("0x5"),
+            json!("5"),
+            json!("safe"),
+            json!("finalized"),
+        ];
+        let blocks = params
+            .into_iter()
+            .map(|param| {
+                Ok(BlockNumber::from(serde_json::from_value::<BlockParam>(
+                    param,
+                )?))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        assert_eq!(
+            blocks,
+            vec![
+                BlockNumber::Number(5.into()),
+                BlockNumber::Number(5.into()),
+                BlockNumber::Number(5.into()),
+                BlockNumber::Safe,
+                BlockNumber::Finalized,
+            ]
+        );
+        assert!(serde_json::from_value::<BlockParam>(json!("unsafe")).is_err());
+
+        Ok(())
+    }
 }
@@ -0,0 +1,144 @@
+use std::str::FromStr;
+
+use jsonrpsee::{
+    core::{async_trait, Error},
+    proc_macros::rpc,
+};
+use libp2p::{Multiaddr, PeerId};
+
+use crate::network::service::{NetworkHandle, PeerDump, PeerInfo, PeerStats};
+
+use super::convert_err;
+
+/// This trait defines a set of RPC methods that can be
+/// queried by clients under the `opp2p` namespace
+#[rpc(server, namespace = "opp2p")]
+pub trait P2pRpc {
+    /// Returns the peer ID, ENR and addresses of the node.
+    #[method(name = "self")]
+    async fn local_peer(&self) -> Result<PeerInfo, Error>;
+
+    /// Returns the connected peers with their scores and topics, or all known peers if
+    /// `connected` is false.
+    #[method(name = "peers")]
+    async fn peers(&self, connected: bool) -> Result<PeerDump, Error>;
+
+    /// Returns the number of connected, discovered and banned peers.
+    #[method(name = "peerStats")]
+    async fn peer_stats(&self) -> Result<PeerStats, Error>;
+
+    /// Returns the ENRs in the discovery routing table.
+    #[method(name = "discoveryTable")]
+    async fn discovery_table(&self) -> Result<Vec<String>, Error>;
+
+    /// Blocks a peer until it is unblocked, disconnecting it.
+    #[method(name = "blockPeer")]
+    async fn block_peer(&self, peer_id: String) -> Result<(), Error>;
+
+    /// Unblocks a blocked or banned peer.
+    #[method(name = "unblockPeer")]
+    async fn unblock_peer(&self, peer_id: String) -> Result<(), Error>;
+
+    /// Dials a peer by its multiaddr.
+    #[method(name = "connectPeer")]
+    async fn connect_peer(&self, addr: String) -> Result<(), Error>;
+}
+
+/// The Magi p2p RPC server which implements the same `opp2p` namespace methods as `op-node`
+#[derive(Debug)]
+pub struct P2pRpcServerImpl {
+    /// Sends commands to the p2p service
+    network: NetworkHandle,
+}
+
+impl P2pRpcServerImpl {
+    /// Creates a new [P2pRpcServerImpl]
+    pub fn new(network: NetworkHandle) -> Self {
+        Self { network }
+    }
+}
+
+#[async_trait]
+impl P2pRpcServer for P2pRpcServerImpl {
+    /// Returns the peer ID, ENR and addresses of the node.
+    async fn local_peer(&self) -> Result<PeerInfo, Error> {
+        convert_err(self.network.local_peer().await)
+    }
+
+    /// Returns the connected peers, or all known peers if `connected` is false.
+    async fn peers(&self, connected: bool) -> Result<PeerDump, Error> {
+        convert_err(self.network.peers(connected).await)
+    }
+
+    /// Returns the number of connected, discovered and banned peers.
+    async fn peer_stats(&self) -> Result<PeerStats, Error> {
+        convert_err(self.network.peer_stats().await)
+    }
+
+    /// Returns the ENRs in the discovery routing table.
+    async fn discovery_table(&self) -> Result<Vec<String>, Error> {
+        convert_err(self.network.discovery_table().await)
+    }
+
+    /// Blocks a peer until it is unblocked, disconnecting it.
+    async fn block_peer(&self, peer_id: String) -> Result<(), Error> {
+        let peer_id = convert_err(PeerId::from_str(&peer_id))?;
+        convert_err(self.network.block_peer(peer_id).await)
+    }
+
+    /// Unblocks a blocked or banned peer.
+    async fn unblock_peer(&self, peer_id: String) -> Result<(), Error> {
+        let peer_id = convert_err(PeerId::from_str(&peer_id))?;
+        convert_err(self.network.unblock_peer(peer_id).await)
+    }
+
+    /// Dials a peer by its multiaddr.
+    async fn connect_peer(&self, addr: String) -> Result<(), Error> {
+        let addr = convert_err(Multiaddr::from_str(&addr))?;
+        convert_err(self.network.connect_peer(addr).await)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jsonrpsee::core::EmptyServerParams;
+
+    use crate::network::service::{Command, NetworkHandle, PeerStats};
+
+    use super::{P2pRpcServer, P2pRpcServerImpl};
+
+    #[tokio::test]
+    async fn test_peer_stats() {
+        let (network, mut commands) = NetworkHandle::new();
+        let rpc = P2pRpcServerImpl::new(network).into_rpc();
+
+        // Requests fail without waiting until networking starts
+        let res: Result<PeerStats, _> = tokio::time::timeout(
+            std::time::Duration::from_secs(1),
+            rpc.call("opp2p_peerStats", EmptyServerParams::new()),
+        )
+        .await
+        .unwrap();
+        assert!(res.is_err());
+
+        commands.start();
+
+        tokio::spawn(async move {
+            if let Some(Command::PeerStats(respond)) = commands.recv().await {
+                _ = respond.send(PeerStats {
+                    connected: 3,
+                    ..Default::default()
+                });
+            }
+        });
+
+        let stats: PeerStats = rpc
+            .call("opp2p_peerStats", EmptyServerParams::new())
+            .await
+            .unwrap();
+        assert_eq!(stats.connected, 3);
+
+        let res: Result<PeerStats, _> = rpc.call("opp2p_peerStats", EmptyServerParams::new()).await;
+        assert!(res.is_err());
+    }
+}
@@ -16,16 +16,12 @@ use crate::{
     engine::{Engine, EngineApi, ExecutionPayload, ForkchoiceState, Status},
 };
 
-/// Temporary trusted/static peer used for checkpoint sync mode.
-// TODO: use a list of whitelisted bootnodes instead
-const TRUSTED_PEER_ENODE: &str = "enode://e85ba0beec172b17f53b373b0ab72238754259aa39f1ae5290e3244e0120882f4cf95acd203661a27c8618b27ca014d4e193266cb3feae43655ed55358eedb06@3.86.143.120:30303?discport=21693";
-
 /// The main entrypoint for starting a Magi node.
 /// Responsible for starting the syncing process.
 pub struct Runner {
     /// The Magi [Config]
     config: Config,
-    /// The [SyncMode] - currently full & checkpoint sync are supported
+    /// The [SyncMode] - currently full, checkpoint & execution layer sync are supported
     sync_mode: SyncMode,
     /// The L2 block hash to begin syncing from
     checkpoint_hash: Option<String>,
@@ -72,6 +68,7 @@ impl Runner {
             SyncMode::Challenge => self.challenge_sync().await,
             SyncMode::Full => self.full_sync().await,
             SyncMode::Checkpoint => self.checkpoint_sync().await,
+            SyncMode::ExecutionLayer => self.execution_layer_sync().await,
         }
     }
 
@@ -95,10 +92,18 @@ impl Runner {
         Ok(())
     }
 
+    /// Execution layer sync mode.
+    /// Lets the execution client sync itself from its own p2p network, following unsafe blocks
+    /// received via gossip, and then begins the normal derivation sync process via the [Driver]
+    pub async fn execution_layer_sync(&self) -> Result<()> {
+        self.start_driver().await?;
+        Ok(())
+    }
+
     /// Checkpoint sync mode.
     /// Syncs the execution client to a given checkpoint block, and then begins the normal derivation sync process via the [Driver]
     ///
-    /// Note: the `admin` RPC method must be available on the execution client as checkpoint_sync relies on `admin_addPeer`
+    /// Note: the execution client must be able to find peers on its own to sync to the checkpoint
     pub async fn checkpoint_sync(&self) -> Result<()> {
         let l2_provider = Provider::try_from(&self.config.l2_rpc_url)?;
         let checkpoint_sync_url =
@@ -170,11 +175,6 @@ impl Runner {
             return Ok(());
         }
 
-        // this is a temporary fix to allow execution layer peering to work
-        // TODO: use a list of whitelisted bootnodes instead
-        tracing::info!("adding trusted peer to the execution layer");
-        l2_provider.add_peer(TRUSTED_PEER_ENODE.to_string()).await?;
-
         // build the execution payload from the checkpoint block and send it to the execution client
         let checkpoint_payload = ExecutionPayload::try_from(checkpoint_block)?;
 
@@ -209,8 +209,9 @@ impl Runner {
 
     /// Creates and starts the [Driver] which handles the derivation sync process.
     async fn start_driver(&self) -> Result<()> {
-        let mut driver =
-            Driver::from_config(self.config.clone(), self.shutdown_recv.clone()).await?;
+        let mut driver = Driver::from_config(self.config.clone(), self.shutdown_recv.clone())
+            .await?
+            .with_execution_layer_sync(self.sync_mode == SyncMode::ExecutionLayer);
 
         if let Err(err) = driver.start().await {
             tracing::error!("driver failure: {}", err);
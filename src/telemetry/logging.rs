@@ -170,7 +170,7 @@ where
                 .metadata()
                 .name()
                 .split(' ')
-                .last()
+                .next_back()
                 .unwrap_or_default();
 
             let relative_path = current_dir()
@@ -1,7 +1,10 @@
 use eyre::{Result, WrapErr};
 use lazy_static::lazy_static;
 use prometheus_exporter::{
-    prometheus::{register_int_gauge, IntGauge},
+    prometheus::{
+        register_int_counter, register_int_gauge, register_int_gauge_vec, IntCounter, IntGauge,
+        IntGaugeVec,
+    },
     start,
 };
 
@@ -14,6 +17,24 @@ lazy_static! {
         register_int_gauge!("safe_head", "safe head number").unwrap();
            /// Monitors if the node is fully synced
     pub static ref SYNCED: IntGauge = register_int_gauge!("synced", "synced flag").unwrap();
+           /// Tracks how many blocks the L1 chain watcher is behind the L1 head.
+    pub static ref L1_HEAD_LAG: IntGauge =
+        register_int_gauge!("l1_head_lag", "L1 blocks behind the L1 head").unwrap();
+           /// Counts temporary driver errors that were retried.
+    pub static ref DRIVER_RETRIES: IntCounter =
+        register_int_counter!("driver_retries", "temporary driver errors retried").unwrap();
+           /// Counts driver pipeline resets.
+    pub static ref DRIVER_RESETS: IntCounter =
+        register_int_counter!("driver_resets", "driver pipeline resets").unwrap();
+           /// Tracks the number of connected p2p peers.
+    pub static ref PEER_COUNT: IntGauge =
+        register_int_gauge!("peer_count", "connected p2p peers").unwrap();
+           /// Tracks the number of connected p2p peers in each gossipsub score band.
+    pub static ref PEER_SCORES: IntGaugeVec =
+        register_int_gauge_vec!("peer_scores", "connected p2p peers by score band", &["band"]).unwrap();
+           /// Tracks the number of temporarily banned p2p peers.
+    pub static ref BANNED_PEERS: IntGauge =
+        register_int_gauge!("banned_peers", "banned p2p peers").unwrap();
 }
 
 /// Starts the metrics server on port 9200